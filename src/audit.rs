@@ -0,0 +1,198 @@
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    commands::{resolve_path, SUCCESS_CODE},
+    config, logging,
+    registry::{self, Kind},
+};
+
+const DEFAULT_LINES: u64 = 50;
+
+// the registry says which fields of a command to leave out; plugins have no
+// row there, so for them fields with these names are left out at any depth
+const SENSITIVE: [&str; 6] = ["content", "data", "key", "password", "token", "secret"];
+
+const REDACTED: &str = "[redacted]";
+
+// kept apart from the debug log, which rotates and can be turned down, so it
+// is a complete record of what the extension has been doing
+pub(crate) fn audit_file() -> PathBuf {
+    match config::get_string("audit.file") {
+        Some(file) => resolve_path(&file),
+        None => logging::log_directory().join("audit.jsonl"),
+    }
+}
+
+fn enabled() -> bool {
+    config::get_bool("audit.enabled").unwrap_or(true)
+}
+
+fn redact(value: &Value, sensitive: &[String]) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    let value = if sensitive.iter().any(|v| v == key) {
+                        json!(REDACTED)
+                    } else {
+                        redact(value, sensitive)
+                    };
+
+                    (key.clone(), value)
+                })
+                .collect::<Map<_, _>>(),
+        ),
+        Value::Array(values) => Value::Array(values.iter().map(|v| redact(v, sensitive)).collect()),
+        value => value.clone(),
+    }
+}
+
+// `write_many` is keyed by the paths it writes, which are worth keeping
+fn redact_field(kind: Kind, value: &Value) -> Value {
+    match (kind, value) {
+        (Kind::PathKeys, Value::Object(files)) => Value::Object(
+            files
+                .keys()
+                .map(|key| (key.clone(), json!(REDACTED)))
+                .collect(),
+        ),
+        _ => json!(REDACTED),
+    }
+}
+
+// the arguments as the registry row of the command allows them to be kept
+fn arguments(command: &Value, extra: &[String]) -> Value {
+    let name = command
+        .get("cmd")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+    let (Some(row), Value::Object(map)) = (registry::find(name), command) else {
+        let sensitive = SENSITIVE
+            .iter()
+            .map(|v| v.to_string())
+            .chain(extra.iter().cloned())
+            .collect::<Vec<_>>();
+        return redact(command, &sensitive);
+    };
+
+    Value::Object(
+        map.iter()
+            .map(|(key, value)| {
+                let field = row.fields.iter().find(|v| v.name == key);
+                let value = match field {
+                    Some(field) if field.redacted => redact_field(field.kind, value),
+                    _ if extra.contains(key) => json!(REDACTED),
+                    _ => redact(value, extra),
+                };
+
+                (key.clone(), value)
+            })
+            .collect(),
+    )
+}
+
+// one line per command; `audit.redact` names more fields to leave out, at
+// any depth
+pub(crate) fn record(command: &Value, reply: &Value, duration: Duration) {
+    if !enabled() {
+        return;
+    }
+
+    let mut arguments = arguments(command, &config::get_strings("audit.redact"));
+    if let Value::Object(map) = &mut arguments {
+        map.remove("cmd");
+    }
+
+    let entry = json!({
+        "time": SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        "cmd": command.get("cmd"),
+        "args": arguments,
+        "code": reply.get("code"),
+        "duration": duration.as_millis() as u64
+    });
+
+    let file = audit_file();
+    let result = file
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| {
+            let mut options = std::fs::OpenOptions::new();
+            options.create(true).append(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            options.open(&file)
+        })
+        .and_then(|mut handle| handle.write_all(format!("{}\n", entry).as_bytes()));
+
+    if let Err(err) = result {
+        error!(
+            "(audit::record) Failed to write {}, error: {}",
+            file.to_string_lossy(),
+            err
+        );
+    }
+}
+
+// the last `lines` entries, oldest first
+pub(crate) fn audit_tail(lines: Option<u64>) -> Value {
+    let lines = lines.unwrap_or(DEFAULT_LINES) as usize;
+    let file = audit_file();
+
+    let handle = match std::fs::File::open(&file) {
+        Ok(handle) => handle,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return json!({
+                "cmd": "audit_tail",
+                "code": SUCCESS_CODE,
+                "content": [],
+                "file": file
+            });
+        }
+
+        Err(err) => {
+            error!(
+                "(audit::audit_tail) Failed to open {}, error: {}",
+                file.to_string_lossy(),
+                err
+            );
+            return json!({
+                "cmd": "audit_tail",
+                "code": 2,
+                "error": err.to_string()
+            });
+        }
+    };
+
+    let mut entries = VecDeque::with_capacity(lines);
+    for line in BufReader::new(handle).lines().map_while(Result::ok) {
+        // a torn line from a crash is skipped
+        let Ok(entry) = serde_json::from_str::<Value>(&line) else {
+            continue;
+        };
+
+        if entries.len() == lines {
+            entries.pop_front();
+        }
+
+        if lines > 0 {
+            entries.push_back(entry);
+        }
+    }
+
+    json!({
+        "cmd": "audit_tail",
+        "code": SUCCESS_CODE,
+        "content": entries,
+        "file": file
+    })
+}
@@ -0,0 +1,187 @@
+use std::{
+    path::Path,
+    process::Command,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    clipboard, color,
+    commands::{self, find_program, is_executable, SUCCESS_CODE},
+    editor, picker, policy, scan, timers,
+};
+
+// probed once at startup; the tools rarely change while the browser runs, and
+// `capabilities` with `refresh` probes again
+static CACHE: Mutex<Option<(u64, Value)>> = Mutex::new(None);
+
+struct Feature {
+    name: &'static str,
+    commands: &'static [&'static str],
+    probe: fn() -> Option<String>,
+}
+
+// a command line names a tool that can run if its program can be found
+fn runnable(program: &str) -> Option<String> {
+    let path = Path::new(program);
+    let found = if path.components().count() > 1 {
+        is_executable(path)
+    } else {
+        find_program(program).is_some()
+    };
+
+    found.then(|| {
+        path.file_stem()
+            .map_or(program.into(), |v| v.to_string_lossy().to_string())
+    })
+}
+
+fn program(process: Command) -> Option<String> {
+    runnable(&process.get_program().to_string_lossy())
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn keyring() -> Option<String> {
+    runnable("secret-tool")
+}
+
+#[cfg(target_os = "macos")]
+fn keyring() -> Option<String> {
+    runnable("security")
+}
+
+#[cfg(windows)]
+fn keyring() -> Option<String> {
+    runnable("powershell")
+}
+
+const FEATURES: [Feature; 10] = [
+    // the shell `run` uses, unless shell execution is off
+    Feature {
+        name: "shell",
+        commands: &policy::SHELL_COMMANDS,
+        probe: || {
+            policy::shell_enabled()
+                .then(commands::shell)
+                .and_then(|v| runnable(&v))
+        },
+    },
+    Feature {
+        name: "clipboard",
+        commands: &["clipboard", "cliphist_list", "cliphist_get"],
+        probe: || clipboard::detect().map(|v| format!("{:?}", v)),
+    },
+    Feature {
+        name: "notifications",
+        commands: &["timer_start"],
+        probe: || program(timers::notifier("", "")?),
+    },
+    // the editor also needs a terminal, unless `editor.terminal` is false
+    Feature {
+        name: "editor",
+        commands: &["editor"],
+        probe: || {
+            editor::terminal().filter(|v| v.is_empty() || runnable(&v[0]).is_some())?;
+            runnable(&editor::editor()[0])
+        },
+    },
+    Feature {
+        name: "picker",
+        commands: &["pick"],
+        probe: || program(picker::launcher(None)?),
+    },
+    Feature {
+        name: "color_picker",
+        commands: &["pick_pixel"],
+        probe: || color::tool().map(String::from),
+    },
+    Feature {
+        name: "opener",
+        commands: &["open"],
+        probe: || program(commands::opener("")?),
+    },
+    Feature {
+        name: "keyring",
+        commands: &["secret"],
+        probe: keyring,
+    },
+    Feature {
+        name: "scanner",
+        commands: &["move"],
+        probe: || runnable(&scan::scanner(Path::new(""))?[0]),
+    },
+    Feature {
+        name: "directory_jumper",
+        commands: &["zoxide_query"],
+        probe: || {
+            runnable("zoxide").or_else(|| {
+                let data = std::env::var("AUTOJUMP_DATA_DIR")
+                    .map(std::path::PathBuf::from)
+                    .unwrap_or(dirs::data_dir()?.join("autojump"));
+                data.join("autojump.txt")
+                    .exists()
+                    .then(|| "autojump".into())
+            })
+        },
+    },
+];
+
+fn probe() -> (u64, Value) {
+    let mut features = Map::new();
+    for feature in &FEATURES {
+        let tool = (feature.probe)();
+        features.insert(
+            feature.name.into(),
+            json!({
+                "available": tool.is_some(),
+                "tool": tool,
+                "commands": feature.commands
+            }),
+        );
+    }
+
+    let probed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    (probed, Value::Object(features))
+}
+
+fn refresh() -> (u64, Value) {
+    let result = probe();
+    *CACHE.lock().unwrap() = Some(result.clone());
+
+    let missing = FEATURES
+        .iter()
+        .map(|v| v.name)
+        .filter(|v| result.1[v]["available"] == false)
+        .collect::<Vec<_>>();
+    info!("(capabilities::refresh) Unavailable: {:?}", missing);
+
+    result
+}
+
+// probes in the background so the first message is not held up
+pub(crate) fn start() {
+    std::thread::spawn(refresh);
+}
+
+// the probe time and the features
+pub(crate) fn cached() -> (u64, Value) {
+    let cached = CACHE.lock().unwrap().clone();
+    cached.unwrap_or_else(refresh)
+}
+
+pub(crate) fn capabilities(refresh_now: bool) -> Value {
+    let (probed, content) = if refresh_now { refresh() } else { cached() };
+
+    json!({
+        "cmd": "capabilities",
+        "code": SUCCESS_CODE,
+        "content": content,
+        "probed": probed
+    })
+}
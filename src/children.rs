@@ -0,0 +1,126 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    commands::{send_signal, Pid},
+    config,
+};
+
+const DEFAULT_GRACE: u64 = 2000;
+
+struct Child {
+    kind: &'static str,
+    command: String,
+}
+
+// every process the host spawned and has not reaped yet, detached ones excluded
+static CHILDREN: Mutex<BTreeMap<u32, Child>> = Mutex::new(BTreeMap::new());
+
+// detached processes still running, which `kill` may signal but `shutdown` leaves alone
+static DETACHED: Mutex<BTreeSet<u32>> = Mutex::new(BTreeSet::new());
+
+pub(crate) fn register(pid: u32, kind: &'static str, command: &str) {
+    CHILDREN.lock().unwrap().insert(
+        pid,
+        Child {
+            kind,
+            command: command.to_string(),
+        },
+    );
+}
+
+pub(crate) fn unregister(pid: u32) {
+    CHILDREN.lock().unwrap().remove(&pid);
+}
+
+pub(crate) fn register_detached(pid: u32) {
+    DETACHED.lock().unwrap().insert(pid);
+}
+
+pub(crate) fn unregister_detached(pid: u32) {
+    DETACHED.lock().unwrap().remove(&pid);
+}
+
+// whether the host started `pid` and has not reaped it yet
+pub(crate) fn is_tracked(pid: u32) -> bool {
+    CHILDREN.lock().unwrap().contains_key(&pid) || DETACHED.lock().unwrap().contains(&pid)
+}
+
+fn signal_all(signal: i32) {
+    for (pid, child) in CHILDREN.lock().unwrap().iter() {
+        info!(
+            "(children::shutdown) Sending signal {} to {} pid: {} ('{}')",
+            signal, child.kind, pid, child.command
+        );
+
+        if let Some(pid) = Pid::new((*pid).into()) {
+            let _ = send_signal(pid, signal);
+        }
+    }
+}
+
+// applies the `children.on_exit` policy: "leave" (default), "terminate" or "kill"
+pub(crate) fn shutdown() {
+    let policy = config::get_string("children.on_exit").unwrap_or("leave".into());
+    if CHILDREN.lock().unwrap().is_empty() {
+        return;
+    }
+
+    #[cfg(unix)]
+    let (term, kill) = (libc::SIGTERM, libc::SIGKILL);
+
+    #[cfg(not(unix))]
+    let (term, kill) = (15, 9);
+
+    match policy.as_str() {
+        "terminate" => {
+            signal_all(term);
+
+            // the threads waiting on each child unregister it once reaped
+            let grace = config::get_u64("children.grace").unwrap_or(DEFAULT_GRACE);
+            let deadline = Instant::now() + Duration::from_millis(grace);
+            while Instant::now() < deadline && !CHILDREN.lock().unwrap().is_empty() {
+                std::thread::sleep(Duration::from_millis(50));
+            }
+
+            signal_all(kill);
+        }
+
+        "kill" => signal_all(kill),
+        _ => {}
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn handle_signals() {
+    use signal_hook::{
+        consts::{SIGHUP, SIGINT, SIGTERM},
+        iterator::Signals,
+    };
+
+    let mut signals = match Signals::new([SIGTERM, SIGINT, SIGHUP]) {
+        Ok(signals) => signals,
+        Err(err) => {
+            error!(
+                "(children::handle_signals) Failed to install handlers, error: {}",
+                err
+            );
+
+            return;
+        }
+    };
+
+    std::thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            info!("(children::handle_signals) Received signal {}", signal);
+            shutdown();
+            std::process::exit(128 + signal);
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub(crate) fn handle_signals() {}
@@ -0,0 +1,191 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use serde_json::{json, Value};
+
+use crate::commands::{find_program, SUCCESS_CODE};
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Tool {
+    WlClipboard,
+    Xclip,
+    Xsel,
+    Pasteboard,
+    PowerShell,
+}
+
+pub(crate) fn detect() -> Option<Tool> {
+    if cfg!(target_os = "macos") {
+        return Some(Tool::Pasteboard);
+    }
+
+    if cfg!(windows) {
+        return Some(Tool::PowerShell);
+    }
+
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+    let x11 = std::env::var_os("DISPLAY").is_some();
+
+    if wayland && find_program("wl-paste").is_some() && find_program("wl-copy").is_some() {
+        Some(Tool::WlClipboard)
+    } else if x11 && find_program("xclip").is_some() {
+        Some(Tool::Xclip)
+    } else if x11 && find_program("xsel").is_some() {
+        Some(Tool::Xsel)
+    } else {
+        None
+    }
+}
+
+fn output(program: &str, arguments: &[&str]) -> Option<Vec<u8>> {
+    let output = Command::new(program)
+        .args(arguments)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    output.status.success().then_some(output.stdout)
+}
+
+pub(crate) fn read(tool: Tool) -> Option<String> {
+    let bytes = match tool {
+        Tool::WlClipboard => output("wl-paste", &["--no-newline"]),
+        Tool::Xclip => output("xclip", &["-selection", "clipboard", "-o"]),
+        Tool::Xsel => output("xsel", &["--clipboard", "--output"]),
+        Tool::Pasteboard => output("pbpaste", &[]),
+        Tool::PowerShell => output(
+            "powershell",
+            &["-NoProfile", "-Command", "Get-Clipboard -Raw"],
+        ),
+    }?;
+
+    Some(String::from_utf8_lossy(&bytes).to_string())
+}
+
+// the tools fork to keep serving the selection, so nothing is piped back
+// that would keep us waiting on them
+fn input(program: &str, arguments: &[&str], content: &str) -> std::io::Result<()> {
+    let mut child = Command::new(program)
+        .args(arguments)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(content.as_bytes())?;
+    }
+
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(std::io::Error::other(format!(
+            "{} exited with {}",
+            program, status
+        )));
+    }
+
+    Ok(())
+}
+
+pub(crate) fn write(tool: Tool, content: &str) -> std::io::Result<()> {
+    match tool {
+        Tool::WlClipboard => input("wl-copy", &[], content),
+        Tool::Xclip => input("xclip", &["-selection", "clipboard", "-i"], content),
+        Tool::Xsel => input("xsel", &["--clipboard", "--input"], content),
+        Tool::Pasteboard => input("pbcopy", &[], content),
+        Tool::PowerShell => input(
+            "powershell",
+            &[
+                "-NoProfile",
+                "-Command",
+                "[Console]::InputEncoding = [Text.Encoding]::UTF8; Set-Clipboard -Value ([Console]::In.ReadToEnd())",
+            ],
+            content,
+        ),
+    }
+}
+
+// the mime types on offer, which is how password managers flag their entries
+pub(crate) fn targets(tool: Tool) -> Vec<String> {
+    let bytes = match tool {
+        Tool::WlClipboard => output("wl-paste", &["--list-types"]),
+        Tool::Xclip => output("xclip", &["-selection", "clipboard", "-t", "TARGETS", "-o"]),
+        _ => None,
+    };
+
+    bytes
+        .map(|v| {
+            String::from_utf8_lossy(&v)
+                .lines()
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub(crate) fn clipboard(action: &str, content: Option<&str>) -> Value {
+    let Some(tool) = detect() else {
+        error!("(clipboard::clipboard) No clipboard tool found");
+        return json!({
+            "cmd": "clipboard",
+            "code": 2,
+            "error": "No clipboard tool found"
+        });
+    };
+
+    match (action, content) {
+        ("get", _) => match read(tool) {
+            Some(content) => {
+                info!("(clipboard::clipboard) Read clipboard with {:?}", tool);
+                json!({
+                    "cmd": "clipboard",
+                    "code": SUCCESS_CODE,
+                    "content": content,
+                    "tool": format!("{:?}", tool)
+                })
+            }
+
+            None => {
+                error!("(clipboard::clipboard) Failed to read with {:?}", tool);
+                json!({
+                    "cmd": "clipboard",
+                    "code": 2,
+                    "error": "Failed to read the clipboard"
+                })
+            }
+        },
+
+        ("set", Some(content)) => match write(tool, content) {
+            Ok(()) => {
+                info!("(clipboard::clipboard) Set clipboard with {:?}", tool);
+                json!({
+                    "cmd": "clipboard",
+                    "code": SUCCESS_CODE,
+                    "tool": format!("{:?}", tool)
+                })
+            }
+
+            Err(err) => {
+                error!(
+                    "(clipboard::clipboard) Failed to write with {:?}, error: {}",
+                    tool, err
+                );
+
+                json!({
+                    "cmd": "clipboard",
+                    "code": 2,
+                    "error": err.to_string()
+                })
+            }
+        },
+
+        _ => json!({
+            "cmd": "clipboard",
+            "code": 1,
+            "error": "Expected action get, or set with content"
+        }),
+    }
+}
@@ -0,0 +1,222 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use age::x25519::Identity;
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::{
+    clipboard,
+    commands::{write_atomic, SUCCESS_CODE},
+    config, keys, logging,
+};
+
+const DEFAULT_INTERVAL: u64 = 1000;
+const DEFAULT_MAX_ENTRIES: u64 = 100;
+const DEFAULT_MAX_SIZE: u64 = 64 * 1024;
+const PREVIEW_LENGTH: usize = 100;
+
+// set by password managers (KeePassXC, KDE, macOS) on entries they copy
+const SECRET_TARGETS: [&str; 2] = [
+    "x-kde-passwordManagerHint",
+    "application/x-nspasteboard-concealed-type",
+];
+
+fn store_path() -> PathBuf {
+    logging::data_directory().join("cliphist.age")
+}
+
+fn identity() -> Result<Identity, String> {
+    keys::identity("cliphist")
+}
+
+fn load(identity: &Identity) -> Result<Value, String> {
+    let Ok(ciphertext) = std::fs::read(store_path()) else {
+        return Ok(json!({ "next_id": 1, "entries": [] }));
+    };
+
+    let plaintext = age::decrypt(identity, &ciphertext).map_err(|e| e.to_string())?;
+    serde_json::from_slice(&plaintext).map_err(|e| e.to_string())
+}
+
+fn save(identity: &Identity, store: &Value) -> Result<(), String> {
+    let plaintext = store.to_string();
+    let ciphertext =
+        age::encrypt(&identity.to_public(), plaintext.as_bytes()).map_err(|e| e.to_string())?;
+
+    write_atomic(&store_path(), &ciphertext, Some(0o600)).map_err(|e| e.to_string())
+}
+
+fn is_excluded(content: &str, targets: &[String]) -> bool {
+    if targets.iter().any(|v| SECRET_TARGETS.contains(&v.as_str())) {
+        return true;
+    }
+
+    config::get_strings("cliphist.exclude")
+        .iter()
+        .filter_map(|pattern| Regex::new(pattern).ok())
+        .any(|pattern| pattern.is_match(content))
+}
+
+fn record(identity: &Identity, content: String) -> Result<(), String> {
+    let max_entries = config::get_u64("cliphist.max_entries").unwrap_or(DEFAULT_MAX_ENTRIES);
+
+    let mut store = load(identity)?;
+    let id = store["next_id"].as_u64().unwrap_or(1);
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let mut entries = store["entries"].as_array().cloned().unwrap_or_default();
+    entries.retain(|entry| entry["content"].as_str() != Some(content.as_str()));
+    entries.insert(
+        0,
+        json!({
+            "id": id,
+            "timestamp": timestamp,
+            "content": content
+        }),
+    );
+
+    entries.truncate(max_entries as usize);
+
+    store["next_id"] = json!(id + 1);
+    store["entries"] = Value::Array(entries);
+    save(identity, &store)
+}
+
+pub(crate) fn start() {
+    if !config::get_bool("cliphist.enabled").unwrap_or(false) {
+        return;
+    }
+
+    let Some(tool) = clipboard::detect() else {
+        error!("(cliphist::start) No clipboard tool available, history disabled");
+        return;
+    };
+
+    let identity = match identity() {
+        Ok(identity) => identity,
+        Err(err) => {
+            error!("(cliphist::start) Failed to load the key, error: {}", err);
+            return;
+        }
+    };
+
+    info!(
+        "(cliphist::start) Recording clipboard history using {:?}",
+        tool
+    );
+
+    std::thread::spawn(move || {
+        let interval = config::get_u64("cliphist.interval").unwrap_or(DEFAULT_INTERVAL);
+        let max_size = config::get_u64("cliphist.max_size").unwrap_or(DEFAULT_MAX_SIZE);
+
+        // whatever is on the clipboard at startup was already seen last time
+        let mut last = clipboard::read(tool);
+
+        loop {
+            std::thread::sleep(Duration::from_millis(interval));
+
+            let Some(content) = clipboard::read(tool) else {
+                continue;
+            };
+
+            if last.as_ref() == Some(&content) {
+                continue;
+            }
+
+            last = Some(content.clone());
+            if content.trim().is_empty()
+                || content.len() as u64 > max_size
+                || is_excluded(&content, &clipboard::targets(tool))
+            {
+                continue;
+            }
+
+            if let Err(err) = record(&identity, content) {
+                error!("(cliphist::start) Failed to record entry, error: {}", err);
+            }
+        }
+    });
+}
+
+fn open_store(name: &str) -> Result<Value, Value> {
+    identity()
+        .and_then(|identity| load(&identity))
+        .map_err(|err| {
+            error!(
+                "(cliphist::{}) Failed to open history, error: {}",
+                name, err
+            );
+            json!({
+                "cmd": name,
+                "code": 2
+            })
+        })
+}
+
+pub(crate) fn cliphist_list(query: &str, limit: Option<usize>) -> Value {
+    let store = match open_store("cliphist_list") {
+        Ok(store) => store,
+        Err(response) => return response,
+    };
+
+    let query = query.to_lowercase();
+    let content = store["entries"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|entry| {
+            entry["content"]
+                .as_str()
+                .is_some_and(|v| v.to_lowercase().contains(&query))
+        })
+        .take(limit.unwrap_or(usize::MAX))
+        .map(|entry| {
+            let text = entry["content"].as_str().unwrap_or_default();
+            json!({
+                "id": entry["id"],
+                "timestamp": entry["timestamp"],
+                "size": text.len(),
+                "preview": text.chars().take(PREVIEW_LENGTH).collect::<String>()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "cmd": "cliphist_list",
+        "code": SUCCESS_CODE,
+        "content": content
+    })
+}
+
+pub(crate) fn cliphist_get(id: u64) -> Value {
+    let store = match open_store("cliphist_get") {
+        Ok(store) => store,
+        Err(response) => return response,
+    };
+
+    let entry = store["entries"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|entry| entry["id"].as_u64() == Some(id));
+
+    match entry {
+        Some(entry) => json!({
+            "cmd": "cliphist_get",
+            "code": SUCCESS_CODE,
+            "content": entry["content"],
+            "timestamp": entry["timestamp"]
+        }),
+
+        None => json!({
+            "cmd": "cliphist_get",
+            "code": 1
+        }),
+    }
+}
@@ -0,0 +1,135 @@
+use std::process::{Command, Stdio};
+
+use serde_json::{json, Value};
+
+use crate::commands::{find_program, SUCCESS_CODE};
+
+fn output(program: &str, arguments: &[&str]) -> Option<Vec<u8>> {
+    let output = Command::new(program)
+        .args(arguments)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    output.status.success().then_some(output.stdout)
+}
+
+fn parse_hex(text: &str) -> Option<[u8; 3]> {
+    let hex = text.trim().trim_start_matches('#');
+    if hex.len() < 6 {
+        return None;
+    }
+
+    let channel = |index: usize| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+// the first pixel of a binary (P6) portable pixmap
+fn parse_ppm(bytes: &[u8]) -> Option<[u8; 3]> {
+    let mut fields = 0;
+    let mut index = 0;
+    while fields < 4 {
+        while bytes.get(index)?.is_ascii_whitespace() {
+            index += 1;
+        }
+
+        if bytes[index] == b'#' {
+            while *bytes.get(index)? != b'\n' {
+                index += 1;
+            }
+
+            continue;
+        }
+
+        while !bytes.get(index)?.is_ascii_whitespace() {
+            index += 1;
+        }
+
+        fields += 1;
+    }
+
+    let pixel = bytes.get(index + 1..index + 4)?;
+    bytes
+        .starts_with(b"P6")
+        .then(|| [pixel[0], pixel[1], pixel[2]])
+}
+
+fn pick_wayland() -> Option<(&'static str, [u8; 3])> {
+    if find_program("hyprpicker").is_some() {
+        let output = output("hyprpicker", &["--format=hex"])?;
+        return Some(("hyprpicker", parse_hex(&String::from_utf8_lossy(&output))?));
+    }
+
+    let point = output("slurp", &["-p"])?;
+    let point = String::from_utf8_lossy(&point);
+    let image = output("grim", &["-g", point.trim(), "-t", "ppm", "-"])?;
+
+    Some(("grim", parse_ppm(&image)?))
+}
+
+fn pick_x11() -> Option<(&'static str, [u8; 3])> {
+    let output = output("xcolor", &["--format", "hex"])?;
+    Some(("xcolor", parse_hex(&String::from_utf8_lossy(&output))?))
+}
+
+fn pick_windows() -> Option<(&'static str, [u8; 3])> {
+    const SCRIPT: &str = "Add-Type -AssemblyName System.Windows.Forms,System.Drawing; \
+        $p = [System.Windows.Forms.Cursor]::Position; \
+        $b = New-Object System.Drawing.Bitmap 1,1; \
+        $g = [System.Drawing.Graphics]::FromImage($b); \
+        $g.CopyFromScreen($p.X, $p.Y, 0, 0, $b.Size); \
+        $c = $b.GetPixel(0, 0); \
+        '{0:x2}{1:x2}{2:x2}' -f $c.R,$c.G,$c.B";
+
+    let output = output("powershell", &["-NoProfile", "-Command", SCRIPT])?;
+    Some(("powershell", parse_hex(&String::from_utf8_lossy(&output))?))
+}
+
+// the picker `pick_pixel` would use, without picking
+pub(crate) fn tool() -> Option<&'static str> {
+    if cfg!(windows) {
+        Some("powershell")
+    } else if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        if find_program("hyprpicker").is_some() {
+            Some("hyprpicker")
+        } else {
+            (find_program("slurp").is_some() && find_program("grim").is_some()).then_some("grim")
+        }
+    } else if std::env::var_os("DISPLAY").is_some() {
+        find_program("xcolor").map(|_| "xcolor")
+    } else {
+        None
+    }
+}
+
+pub(crate) fn pick_pixel() -> Value {
+    let result = if cfg!(windows) {
+        pick_windows()
+    } else if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        pick_wayland()
+    } else if std::env::var_os("DISPLAY").is_some() {
+        pick_x11()
+    } else {
+        None
+    };
+
+    let Some((tool, [r, g, b])) = result else {
+        error!("(color::pick_pixel) No color picker available or picking was cancelled");
+        return json!({
+            "cmd": "pick_pixel",
+            "code": 1
+        });
+    };
+
+    let hex = format!("#{:02x}{:02x}{:02x}", r, g, b);
+    info!("(color::pick_pixel) Picked {} using {}", hex, tool);
+
+    json!({
+        "cmd": "pick_pixel",
+        "code": SUCCESS_CODE,
+        "hex": hex,
+        "rgb": [r, g, b],
+        "tool": tool
+    })
+}
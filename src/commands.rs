@@ -1,420 +1,2524 @@
 use std::{
     fs::File,
-    io::{BufRead, Read, Write},
-    path::PathBuf,
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Output, Stdio},
+    thread::JoinHandle,
+    time::{Duration, Instant},
 };
 
 use base64::{prelude::BASE64_STANDARD, Engine};
 use regex::Regex;
 use serde_json::{json, Value};
 
-const NAME: &str = "tridactyl";
+use crate::{
+    children, config, events, jobs, journal, maintenance, metadata, migrations, policy, profiles,
+    rc, scan,
+};
+
+pub(crate) const NAME: &str = "tridactyl";
 const CONFIG: &str = "tridactylrc";
-const VERSION: &str = "0.5.0";
+const CONFIG_VARIABLE: &str = "TRIDACTYL_RC";
+pub(crate) const VERSION: &str = "0.5.0";
+
+pub(crate) const SUCCESS_CODE: u8 = 0;
+
+// `PermissionDenied` as `permission_denied`
+fn error_kind(kind: std::io::ErrorKind) -> String {
+    let mut name = String::new();
+    for (i, c) in format!("{:?}", kind).chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            name.push('_');
+        }
+
+        name.push(c.to_ascii_lowercase());
+    }
+
+    name
+}
 
-const SUCCESS_CODE: u8 = 0;
+// the message for people, and the kind and os error number for the
+// extension, which should not have to parse the message
+pub(crate) fn io_failure(cmd: &str, code: u8, err: &std::io::Error) -> Value {
+    json!({
+        "cmd": cmd,
+        "code": code,
+        "error": err.to_string(),
+        "errno": err.raw_os_error(),
+        "kind": error_kind(err.kind())
+    })
+}
 
-fn sanitize_file_name(file_name: &str) -> String {
+// a file name part built from outside input: letters and digits in any
+// script, `-`, `_` and single dots inside the name; separators, `..` and a
+// leading dot never make it through
+pub(crate) fn sanitize_file_name(file_name: &str) -> String {
     let mut result = String::new();
     for c in file_name.to_lowercase().chars() {
-        if c.is_alphanumeric() || c == '.' {
-            result.push(c);
+        match c {
+            c if c.is_alphanumeric() || c == '-' || c == '_' => result.push(c),
+            '.' if !result.is_empty() && !result.ends_with('.') => result.push(c),
+            c if c.is_whitespace() && !result.is_empty() && !result.ends_with('_') => {
+                result.push('_')
+            }
+            _ => {}
         }
     }
 
-    result.replace("..", ".")
+    result.trim_end_matches(['.', '_']).to_string()
+}
+
+// the lowercased host of a url, without credentials or port
+pub(crate) fn url_host(url: &str) -> Option<String> {
+    let regex = Regex::new(r"^[A-Za-z][A-Za-z0-9+.-]*://(?:[^@/?#]*@)?([^:/?#]+)").unwrap();
+    regex
+        .captures(url)
+        .and_then(|v| v.get(1))
+        .map(|v| v.as_str().to_lowercase())
 }
 
+// `~` and `~user`, like the shell; a user that cannot be found leaves the
+// path as it was
 fn expand_tilde(path: String) -> PathBuf {
-    if path.starts_with('~') {
-        let home = dirs::home_dir().unwrap();
-        return home.join(&path[1..path.len()]);
+    let Some(rest) = path.strip_prefix('~') else {
+        return PathBuf::from(path);
+    };
+
+    let (user, rest) = rest
+        .find(std::path::is_separator)
+        .map_or((rest, ""), |v| rest.split_at(v));
+
+    let home = if user.is_empty() {
+        dirs::home_dir()
+    } else {
+        user_home(user)
+    };
+
+    match home {
+        Some(home) => home.join(rest.trim_start_matches(std::path::is_separator)),
+        None => PathBuf::from(path),
+    }
+}
+
+#[cfg(unix)]
+fn user_home(user: &str) -> Option<PathBuf> {
+    use std::{ffi::CStr, ffi::CString, os::unix::ffi::OsStrExt};
+
+    let name = CString::new(user).ok()?;
+    let mut buffer = vec![0 as libc::c_char; 4096];
+    let mut entry = unsafe { std::mem::zeroed::<libc::passwd>() };
+    let mut result = std::ptr::null_mut();
+
+    loop {
+        let status = unsafe {
+            libc::getpwnam_r(
+                name.as_ptr(),
+                &mut entry,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            )
+        };
+
+        // the entry did not fit
+        if status == libc::ERANGE && buffer.len() < 1 << 20 {
+            buffer.resize(buffer.len() * 2, 0);
+            continue;
+        }
+
+        if status != 0 || result.is_null() || entry.pw_dir.is_null() {
+            return None;
+        }
+
+        let directory = unsafe { CStr::from_ptr(entry.pw_dir) };
+        return Some(PathBuf::from(std::ffi::OsStr::from_bytes(
+            directory.to_bytes(),
+        )));
     }
+}
+
+// profiles sit next to each other, in `C:\\Users` by default
+#[cfg(windows)]
+fn user_home(user: &str) -> Option<PathBuf> {
+    let home = dirs::home_dir()?;
+    Some(home.parent()?.join(user)).filter(|v| v.is_dir())
+}
+
+fn substitute(path: &str, pattern: &str) -> String {
+    Regex::new(pattern)
+        .unwrap()
+        .replace_all(path, |captures: &regex::Captures| {
+            let name = &captures[1];
+            let name = name
+                .strip_prefix('{')
+                .and_then(|v| v.strip_suffix('}'))
+                .unwrap_or(name);
+
+            std::env::var(name).unwrap_or_else(|_| captures[0].to_string())
+        })
+        .into_owned()
+}
+
+// `\\server\share` keeps its leading pair; everywhere else forward slashes
+// become backslashes and runs of them collapse
+fn normalize_separators(path: &str) -> String {
+    let (mut normalized, rest) = match path.get(..2) {
+        Some(r"\\" | "//") => (String::from(r"\\"), &path[2..]),
+        _ => (String::new(), path),
+    };
 
-    return PathBuf::from(path);
+    let prefix = normalized.len();
+    for character in rest.chars().map(|v| if v == '/' { '\\' } else { v }) {
+        if character == '\\' && normalized.len() > prefix && normalized.ends_with('\\') {
+            continue;
+        }
+
+        normalized.push(character);
+    }
+
+    normalized
 }
 
+// `$VAR` and `${VAR}` on unix, `%VAR%` on windows; unset variables are left
+// as written
 fn expand_vars(path: &str) -> String {
+    if cfg!(windows) {
+        return normalize_separators(&substitute(path, r"%([^%]+)%"));
+    }
+
     if !path.contains('$') {
         return path.to_string();
     }
 
-    let mut result = path.to_string();
+    substitute(path, r"\$(\w+|\{[^}]*\})")
+}
+
+// what every command does with a path it is given, before anything else
+pub(crate) fn resolve_path(path: &str) -> PathBuf {
+    expand_tilde(expand_vars(path))
+}
+
+// tridactyl documents `~/.config/tridactyl` on every unix, but on macOS
+// `dirs::config_dir` is `~/Library/Application Support`
+#[cfg(target_os = "macos")]
+pub(crate) fn config_directory() -> PathBuf {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| dirs::home_dir().unwrap().join(".config"))
+        .join(NAME)
+}
+
+#[cfg(not(target_os = "macos"))]
+pub(crate) fn config_directory() -> PathBuf {
+    dirs::config_dir().unwrap().join(NAME)
+}
+
+// where the tridactylrc may be, in order, with what put each candidate there
+fn config_file_candidates() -> Vec<(&'static str, PathBuf)> {
+    let mut candidates = Vec::new();
+    if let Some(path) = std::env::var_os(CONFIG_VARIABLE).filter(|v| !v.is_empty()) {
+        candidates.push(("env", PathBuf::from(path)));
+    }
+
+    for path in config::get_strings("rc.paths") {
+        candidates.push(("config", resolve_path(&path)));
+    }
 
-    if cfg!(unix) {
-        let re = Regex::new(r"\$(\w+|\{[^}]*\})").unwrap();
-        let mut last_end = 0;
-        let mut expanded = String::new();
+    // `dirs::config_dir` ignores it on windows and macos
+    if let Some(path) = std::env::var_os("XDG_CONFIG_HOME").filter(|v| !v.is_empty()) {
+        candidates.push(("xdg", PathBuf::from(path).join(NAME).join(CONFIG)));
+    }
 
-        for cap in re.captures_iter(path) {
-            let whole_match = cap.get(0).unwrap();
-            let var_name = cap.get(1).unwrap().as_str();
+    candidates.push(("default", config_directory().join(CONFIG)));
+    // where earlier releases looked on macOS
+    #[cfg(target_os = "macos")]
+    candidates.push((
+        "legacy",
+        dirs::config_dir().unwrap().join(NAME).join(CONFIG),
+    ));
+    candidates.push((
+        "home",
+        dirs::home_dir().unwrap().join(format!(".{}", CONFIG)),
+    ));
 
-            expanded.push_str(&path[last_end..whole_match.start()]);
+    candidates
+}
 
-            let clean_name = if var_name.starts_with('{') && var_name.ends_with('}') {
-                &var_name[1..var_name.len() - 1]
-            } else {
-                var_name
-            };
+// the profile the rc is for: `profile` when given, else the calling
+// browser's, found like for `getpref`
+fn config_profile(explicit: Option<&str>) -> Result<Option<String>, Value> {
+    let name = match explicit {
+        Some(name) if !name.is_empty() && name != "auto" => name.to_string(),
+        _ => match profiles::active_profile().and_then(|v| profiles::profile_name(&v)) {
+            Some(name) => name,
+            None => return Ok(None),
+        },
+    };
+
+    // the name ends up in a file name
+    if name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(json!({ "code": 1, "error": "Invalid profile name" }));
+    }
+
+    Ok(Some(name))
+}
+
+// `tridactylrc.<profile>` wins over the `tridactylrc` next to it; the profile
+// is returned when it did
+fn get_config_file(profile: Option<&str>) -> Option<(&'static str, PathBuf, Option<String>)> {
+    config_file_candidates()
+        .into_iter()
+        .find_map(|(source, path)| {
+            let specific = profile.map(|profile| {
+                let mut name = path.clone().into_os_string();
+                name.push(".");
+                name.push(profile);
+                (PathBuf::from(name), profile.to_string())
+            });
+
+            match specific.filter(|(v, _)| v.is_file()) {
+                Some((path, profile)) => Some((source, path, Some(profile))),
+                None => path.is_file().then_some((source, path, None)),
+            }
+        })
+}
+
+// replaces a file in one step by renaming a synced sibling over it
+pub(crate) fn write_atomic(path: &Path, content: &[u8], mode: Option<u32>) -> std::io::Result<()> {
+    let directory = path
+        .parent()
+        .filter(|v| !v.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    let mut file = tempfile::NamedTempFile::new_in(directory)?;
+    file.write_all(content)?;
+    file.as_file().sync_all()?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.as_file()
+            .set_permissions(std::fs::Permissions::from_mode(mode.unwrap_or(0o644)))?;
+    }
+
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    file.persist(path).map_err(|e| e.error)?;
+    sync_directory(directory)
+}
+
+// makes renames and removals in a directory durable, not only the contents
+// of the files in it
+pub(crate) fn sync_directory(directory: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    File::open(directory)?.sync_all()?;
+
+    #[cfg(not(unix))]
+    let _ = directory;
 
-            if let Ok(value) = std::env::var(clean_name) {
-                expanded.push_str(&value);
+    Ok(())
+}
+
+pub(crate) fn find_program(program: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .flat_map(|directory| {
+            let candidate = directory.join(program);
+            if cfg!(windows) {
+                vec![candidate.with_extension("exe"), candidate]
             } else {
-                expanded.push_str(whole_match.as_str());
+                vec![candidate]
             }
+        })
+        .find(|candidate| is_executable(candidate))
+}
 
-            last_end = whole_match.end();
-        }
+pub(crate) fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::metadata(path).is_ok_and(|v| v.is_file() && v.permissions().mode() & 0o111 != 0)
+    }
+
+    #[cfg(not(unix))]
+    path.is_file()
+}
 
-        expanded.push_str(&path[last_end..]);
-        result = expanded;
+// the file a path names even if it does not exist yet, so two spellings of
+// one new file still compare equal
+fn resolve_target(path: &Path) -> PathBuf {
+    let parent = path
+        .parent()
+        .filter(|v| !v.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    match (parent.canonicalize(), path.file_name()) {
+        (Ok(parent), Some(name)) => parent.join(name),
+        _ => path.to_path_buf(),
     }
+}
 
-    result
+// same device and inode (file id on windows), which also catches hard links
+// and symlinks
+pub(crate) fn same_file(a: &Path, b: &Path) -> bool {
+    same_file::is_same_file(a, b).unwrap_or(false) || resolve_target(a) == resolve_target(b)
 }
 
-fn get_config_file() -> Option<PathBuf> {
-    let candidates = [
-        dirs::config_dir().unwrap().join(NAME).join(CONFIG),
-        dirs::home_dir().unwrap().join(format!(".{}", CONFIG)),
-    ];
+// where `from` ends up when moved or copied to `to`
+fn destination(from: &Path, to: &Path) -> PathBuf {
+    match from.file_name() {
+        Some(name) if to.is_dir() => to.join(name),
+        _ => to.to_path_buf(),
+    }
+}
+
+// refuses operations that would clobber their own source
+fn check_distinct(from: &Path, destination: &Path) -> Result<(), &'static str> {
+    if same_file(from, destination) {
+        return Err("Source and destination are the same file");
+    }
 
-    for candidate in &candidates {
-        if candidate.exists() {
-            return Some(candidate.clone());
+    if from.is_dir() {
+        let from = from.canonicalize().unwrap_or(from.to_path_buf());
+        if resolve_target(destination).starts_with(from) {
+            return Err("Cannot move or copy a directory into itself");
         }
     }
 
-    None
+    Ok(())
 }
 
 pub fn version() -> Value {
     json!({
         "cmd": "version",
         "code": SUCCESS_CODE,
-        "version": VERSION
+        "version": VERSION,
+        "config_version": migrations::CONFIG_VERSION,
+        "migrations": migrations::results()
     })
 }
 
-pub fn get_config() -> Value {
-    let path = get_config_file();
-    if let Some(path) = path {
-        let content = std::fs::read_to_string(path);
-        if content.is_err() {
-            json!({
-                "cmd": "getconfig",
-                "code": 2
-            })
-        } else {
-            json!({
+pub fn get_config(profile: Option<&str>) -> Value {
+    let profile = match config_profile(profile) {
+        Ok(profile) => profile,
+        Err(mut error) => {
+            error["cmd"] = "getconfig".into();
+            return error;
+        }
+    };
+
+    let path = get_config_file(profile.as_deref());
+    if let Some((_, path, profile)) = path {
+        let preprocessed = rc::preprocess(&path);
+
+        // the rc is wherever the user keeps it, but what it sources is held
+        // to the roots like any other file
+        let outside = preprocessed
+            .as_ref()
+            .ok()
+            .and_then(|(_, includes)| includes.iter().find_map(|v| policy::check_path(v).err()));
+        if let Some(refusal) = outside {
+            return policy::denied("getconfig", refusal);
+        }
+
+        match preprocessed {
+            Ok((content, includes)) => json!({
                 "cmd": "getconfig",
                 "code": SUCCESS_CODE,
-                "content": content.unwrap()
-            })
+                "content": match config::get_bool("rc.templating") {
+                    Some(true) => rc::template(&content),
+                    _ => content,
+                },
+                "includes": includes,
+                "profile": profile
+            }),
+            Err((code, err)) => {
+                error!(
+                    "(commands::get_config) Failed to read the rc, error: {}",
+                    err
+                );
+                json!({
+                    "cmd": "getconfig",
+                    "code": code,
+                    "error": err
+                })
+            }
         }
     } else {
-        json!({
-            "cmd": "getconfig",
-            "code": 1
-        })
+        io_failure(
+            "getconfig",
+            1,
+            &std::io::Error::new(std::io::ErrorKind::NotFound, "No tridactylrc found"),
+        )
     }
 }
 
-pub fn get_config_path() -> Value {
-    let path = get_config_file();
-    if let Some(path) = path {
-        let binding = path.canonicalize().unwrap();
-        let path = binding.to_str().unwrap();
+// the path as the user knows it, made absolute; `resolve` follows symlinks to
+// where the file really is, like a dotfiles checkout
+pub fn get_config_path(profile: Option<&str>, resolve: bool) -> Value {
+    let profile = match config_profile(profile) {
+        Ok(profile) => profile,
+        Err(mut error) => {
+            error["cmd"] = "getconfigpath".into();
+            return error;
+        }
+    };
+
+    let path = get_config_file(profile.as_deref());
+    if let Some((source, path, profile)) = path {
+        let path = if resolve {
+            path.canonicalize()
+        } else {
+            std::path::absolute(&path)
+        }
+        .unwrap_or(path);
 
         json!({
             "cmd": "getconfigpath",
             "code": SUCCESS_CODE,
-            "content": path
+            "content": path,
+            "source": source,
+            "profile": profile
         })
     } else {
-        json!({
-            "cmd": "getconfigpath",
-            "code": 1
-        })
+        io_failure(
+            "getconfigpath",
+            1,
+            &std::io::Error::new(std::io::ErrorKind::NotFound, "No tridactylrc found"),
+        )
     }
 }
 
-pub(crate) fn read(path: &str) -> Value {
-    let path = expand_tilde(expand_vars(path));
+// text that is not valid utf-8 comes back with its invalid bytes replaced,
+// or with `encoding: "base64"` as base64; `encoding` in the reply says which
+fn decode(bytes: Vec<u8>, encoding: Option<&str>) -> (String, &'static str) {
+    match String::from_utf8(bytes) {
+        Ok(text) => (text, "utf8"),
+        Err(err) if encoding == Some("base64") => {
+            (BASE64_STANDARD.encode(err.as_bytes()), "base64")
+        }
+        Err(err) => (
+            String::from_utf8_lossy(err.as_bytes()).into_owned(),
+            "lossy",
+        ),
+    }
+}
 
-    let mut code = SUCCESS_CODE;
-    let result = match std::fs::read_to_string(&path) {
-        Ok(value) => value,
-        Err(_) => {
-            code = 2;
-            "".into()
+pub(crate) fn read(path: &str, encoding: Option<&str>) -> Value {
+    let path = resolve_path(path);
+
+    let result = match std::fs::read(&path) {
+        Ok(value) => decode(value, encoding),
+        Err(err) => {
+            error!(
+                "(commands::read) path: {}, error: {}",
+                path.to_string_lossy(),
+                err
+            );
+
+            let mut response = io_failure("read", 2, &err);
+            response["content"] = "".into();
+            return response;
         }
     };
 
     info!(
-        "(commands::write) path: {}, code: {}",
-        path.to_string_lossy().to_string(),
-        code
+        "(commands::read) path: {}, encoding: {}",
+        path.to_string_lossy(),
+        result.1
     );
 
     json!({
         "cmd": "read",
-        "code": code,
-        "content": result
+        "code": SUCCESS_CODE,
+        "content": result.0,
+        "encoding": result.1
     })
 }
 
-pub(crate) fn write(path: &str, content: &str) -> Value {
-    let re = Regex::new(r"^data:((.*?)(;charset=.*?)?)(;base64)?,").unwrap();
-
-    let mut content = String::from(content);
-    if re.is_match(&content) {
-        let binding = re.replace(&content, "").to_string();
-        let binding =
-            String::from_utf8(BASE64_STANDARD.decode(&binding.as_str()).unwrap()).unwrap();
-        content = binding;
-    }
+pub(crate) fn read_many(paths: &[String], pattern: Option<&str>, encoding: Option<&str>) -> Value {
+    let mut paths = paths
+        .iter()
+        .map(|path| (path.clone(), resolve_path(path)))
+        .collect::<Vec<_>>();
+
+    if let Some(pattern) = pattern {
+        let expanded = resolve_path(pattern);
+        match glob::glob(&expanded.to_string_lossy()) {
+            Ok(entries) => {
+                for entry in entries.flatten() {
+                    if entry.is_file() {
+                        paths.push((entry.to_string_lossy().to_string(), entry));
+                    }
+                }
+            }
 
-    let mut code = 2;
-    match File::create(path) {
-        Ok(mut file) => match file.write_all(content.as_bytes()) {
-            Ok(_) => {
-                code = SUCCESS_CODE;
+            Err(err) => {
+                error!(
+                    "(commands::read_many) Invalid pattern: '{}', error: {}",
+                    pattern, err
+                );
+
+                return json!({
+                    "cmd": "read_many",
+                    "code": 1,
+                    "error": err.to_string()
+                });
             }
-            _ => {}
-        },
-        _ => {}
+        }
     }
 
-    info!("(commands::write) path: {}, code: {}", path, code);
+    let mut content = serde_json::Map::new();
+    let mut failed = 0;
+    for (key, path) in paths {
+        let result = match std::fs::read(&path) {
+            Ok(value) => {
+                let (content, encoding) = decode(value, encoding);
+                json!({
+                    "code": SUCCESS_CODE,
+                    "content": content,
+                    "encoding": encoding
+                })
+            }
 
-    json!({
-        "cmd": "write",
-        "code": code
-    })
-}
+            Err(err) => {
+                failed += 1;
+                let mut result = io_failure("read", 2, &err);
+                result.as_object_mut().unwrap().remove("cmd");
+                result
+            }
+        };
 
-pub(crate) fn write_rc(path: &str, content: &str, force: bool) -> Value {
-    let path = expand_tilde(expand_vars(path));
-
-    let mut code = 1;
-    if !std::fs::exists(&path).unwrap_or(false) || force {
-        let file = File::create(&path);
-        if let Ok(mut file) = file {
-            let result = file.write_all(content.as_bytes());
-            code = if result.is_ok() { SUCCESS_CODE } else { 2 };
-        } else {
-            code = 2;
-        }
+        content.insert(key, result);
     }
 
     info!(
-        "(commands::write_rc) path: {}, force: {}, code: {}",
-        path.to_string_lossy().to_string(),
-        force,
-        code
+        "(commands::read_many) files: {}, failed: {}",
+        content.len(),
+        failed
     );
 
     json!({
-        "cmd": "writerc",
-        "code": code
+        "cmd": "read_many",
+        "code": SUCCESS_CODE,
+        "content": content
     })
 }
 
-pub(crate) fn create_directory(path: &str) -> Value {
-    let path = expand_tilde(expand_vars(path));
+pub(crate) fn read_table(
+    path: &str,
+    delimiter: Option<&str>,
+    header: bool,
+    limit: Option<usize>,
+) -> Value {
+    let path = resolve_path(path);
+
+    let delimiter = match delimiter {
+        Some("\\t") | Some("\t") => b'\t',
+        Some(value) if value.len() == 1 => value.as_bytes()[0],
+        _ => match path.extension().and_then(|v| v.to_str()) {
+            Some("tsv") | Some("tab") => b'\t',
+            _ => b',',
+        },
+    };
 
-    if std::fs::create_dir_all(&path).is_err() {
-        return json!({
-            "cmd": "mkdir",
-            "code": 2
-        });
+    let reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(header)
+        .flexible(true)
+        .from_path(&path);
+
+    let mut reader = match reader {
+        Ok(reader) => reader,
+        Err(err) => {
+            error!(
+                "(commands::read_table) path: {}, error: {}",
+                path.to_string_lossy(),
+                err
+            );
+
+            return io_failure("read_table", 2, &err.into());
+        }
     };
 
-    info!("(commands::mkdir) path: {}", path.to_str().unwrap());
+    let headers = if header {
+        match reader.headers() {
+            Ok(headers) => Some(headers.iter().map(String::from).collect::<Vec<_>>()),
+            Err(err) => {
+                error!(
+                    "(commands::read_table) path: {}, error: {}",
+                    path.to_string_lossy(),
+                    err
+                );
+
+                return io_failure("read_table", 2, &err.into());
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut rows = Vec::new();
+    let mut truncated = false;
+    for record in reader.records() {
+        if limit.is_some_and(|limit| rows.len() >= limit) {
+            truncated = true;
+            break;
+        }
+
+        let Ok(record) = record else {
+            continue;
+        };
+
+        let row = match &headers {
+            Some(headers) => {
+                let mut object = serde_json::Map::new();
+                for (index, field) in record.iter().enumerate() {
+                    let key = headers
+                        .get(index)
+                        .cloned()
+                        .unwrap_or_else(|| index.to_string());
+
+                    object.insert(key, Value::String(field.into()));
+                }
+
+                Value::Object(object)
+            }
+
+            None => Value::Array(record.iter().map(|v| Value::String(v.into())).collect()),
+        };
+
+        rows.push(row);
+    }
+
+    info!(
+        "(commands::read_table) path: {}, rows: {}",
+        path.to_string_lossy(),
+        rows.len()
+    );
+
     json!({
-        "cmd": "mkdir",
+        "cmd": "read_table",
         "code": SUCCESS_CODE,
+        "headers": headers,
+        "rows": rows,
+        "truncated": truncated
     })
 }
 
-pub(crate) fn read_directory(path: &str) -> Value {
-    let mut path = expand_tilde(path.into());
-
-    let is_directory = path.is_dir();
-    if !path.is_dir() {
-        path = path.parent().unwrap_or(&PathBuf::from(".")).into();
-    }
+pub(crate) fn write(path: &str, content: &str) -> Value {
+    let re = Regex::new(r"^data:((.*?)(;charset=.*?)?)(;base64)?,").unwrap();
 
-    let mut files = Vec::new();
-    if let Ok(entries) = path.read_dir() {
-        for entry in entries {
-            if let Ok(entry) = entry {
-                if let Some(file_name) = entry.path().file_name() {
-                    files.push(file_name.to_string_lossy().to_string());
+    // a base64 data: URL is written as the bytes it carries, binary or not
+    let bytes = match re.captures(content) {
+        Some(captures) if captures.get(4).is_some() => {
+            let payload = &content[captures.get(0).unwrap().end()..];
+            match BASE64_STANDARD.decode(payload) {
+                Ok(bytes) => bytes,
+                Err(err) => {
+                    error!("(commands::write) path: {}, error: {}", path, err);
+                    return io_failure(
+                        "write",
+                        1,
+                        &std::io::Error::new(std::io::ErrorKind::InvalidData, err),
+                    );
                 }
             }
         }
+
+        Some(captures) => content.as_bytes()[captures.get(0).unwrap().end()..].to_vec(),
+        None => content.as_bytes().to_vec(),
+    };
+
+    let result = File::create(resolve_path(path)).and_then(|mut v| v.write_all(&bytes));
+    if let Err(err) = result {
+        error!("(commands::write) path: {}, error: {}", path, err);
+        return io_failure("write", 2, &err);
     }
 
-    info!("(commands::temp) path: {}", path.to_string_lossy());
+    info!("(commands::write) path: {}", path);
+
     json!({
-        "cmd": "list_dir",
-        "isDir": is_directory,
-        "files": files,
-        "sep": std::path::MAIN_SEPARATOR.to_string()
+        "cmd": "write",
+        "code": SUCCESS_CODE
     })
 }
 
-pub(crate) fn temp(prefix: &str, content: &str) -> Option<Value> {
-    let prefix = format!("tmp_{}_", sanitize_file_name(prefix));
-
-    let file = tempfile::Builder::new()
-        .prefix(&prefix)
-        .suffix(".txt")
-        .tempfile()
-        .ok();
+struct StagedFile {
+    target: PathBuf,
+    staged: tempfile::TempPath,
+    backup: Option<PathBuf>,
+    // whether the target was there before this write, and whether the staged
+    // file has replaced it; a rollback only removes what the write created
+    existed: bool,
+    written: bool,
+}
 
-    let Some(mut file) = file else { return None };
+fn stage_file(path: &str, spec: &Value) -> Result<StagedFile, String> {
+    let (content, encoding, mode) = match spec {
+        Value::String(content) => (content.as_str(), None, None),
+        Value::Object(spec) => (
+            spec.get("content")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default(),
+            spec.get("encoding").and_then(|v| v.as_str()),
+            spec.get("mode"),
+        ),
+
+        _ => return Err("content must be a string or an object".into()),
+    };
 
-    file.write(content.as_bytes()).ok();
-    let file_path = file.path().to_str().unwrap_or("");
+    let bytes = match encoding {
+        None | Some("utf8") | Some("utf-8") => content.as_bytes().to_vec(),
+        Some("base64") => BASE64_STANDARD.decode(content).map_err(|e| e.to_string())?,
 
-    info!("(commands::temp) path: {}", file_path);
-    Some(json!({
-        "cmd": "temp",
-        "code": SUCCESS_CODE,
-        "content": file_path
-    }))
-}
+        Some(encoding) => return Err(format!("unknown encoding: {}", encoding)),
+    };
 
-pub(crate) fn move_file(from: &str, to: &str, overwrite: bool, cleanup: bool) -> Value {
-    let from = expand_tilde(expand_vars(from));
-    let to = expand_tilde(expand_vars(to));
+    let mode = match mode {
+        None => None,
+        Some(Value::Number(mode)) => mode.as_u64().map(|v| v as u32),
+        Some(Value::String(mode)) => {
+            Some(u32::from_str_radix(mode, 8).map_err(|_| format!("invalid mode: {}", mode))?)
+        }
 
-    let can_move = overwrite
-        || !std::fs::exists(&to).unwrap_or(false)
-        || std::fs::exists(to.join(from.file_name().unwrap())).unwrap_or(false);
+        Some(_) => return Err("mode must be an octal string or a number".into()),
+    };
 
-    let mut code = 1;
-    if can_move {
-        let result = if std::fs::exists(&to).unwrap_or(false) {
-            std::fs::rename(&from, to.join(from.file_name().unwrap()))
-        } else {
-            std::fs::rename(&from, &to)
+    let target = resolve_path(path);
+    let directory = target
+        .parent()
+        .filter(|v| !v.as_os_str().is_empty())
+        .unwrap_or(std::path::Path::new("."));
+
+    // staging next to the target keeps the final rename on one filesystem
+    let mut file = tempfile::NamedTempFile::new_in(directory).map_err(|e| e.to_string())?;
+    file.write_all(&bytes).map_err(|e| e.to_string())?;
+    file.as_file().sync_all().map_err(|e| e.to_string())?;
+
+    // temp files start out private, so fall back to what a plain write would give
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let permissions = match mode {
+            Some(mode) => std::fs::Permissions::from_mode(mode),
+            None => std::fs::metadata(&target)
+                .map(|v| v.permissions())
+                .unwrap_or(std::fs::Permissions::from_mode(0o644)),
         };
 
-        code = if result.is_ok() { SUCCESS_CODE } else { 2 };
+        file.as_file()
+            .set_permissions(permissions)
+            .map_err(|e| e.to_string())?;
     }
 
-    if cleanup {
-        std::fs::remove_file(from).unwrap();
-    }
+    #[cfg(not(unix))]
+    let _ = mode;
 
-    json!({
-        "cmd": "move",
-        "code": code
+    Ok(StagedFile {
+        target,
+        staged: file.into_temp_path(),
+        backup: None,
+        existed: false,
+        written: false,
     })
 }
 
-pub(crate) fn env(key: &str) -> Value {
-    match std::env::var(key) {
-        Ok(value) => {
-            info!("(commands::env) Retrived environment key: {}", key);
-            json!({
-                "cmd": "env",
-                "content": value
-            })
-        }
-        Err(_) => {
-            error!("(commands::env) Failed to retrive environment key: {}", key);
-            json!({
-                "cmd": "env"
-            })
-        }
-    }
+// named after the staged file, so the journal knows it before it exists
+fn backup_path(file: &StagedFile) -> PathBuf {
+    let mut name = file.staged.as_os_str().to_os_string();
+    name.push(".bak");
+    PathBuf::from(name)
 }
 
-pub(crate) fn get_process_id() -> Value {
-    let pid = std::process::id();
-    info!("(commands::get_process_id) Process id: {}", pid);
+fn commit_file(file: &mut StagedFile) -> std::io::Result<()> {
+    file.existed = file.target.symlink_metadata().is_ok();
+    if file.existed {
+        let backup = backup_path(file);
+        std::fs::rename(&file.target, &backup)?;
+        file.backup = Some(backup);
+    }
 
-    json!({
-        "cmd": "ppid",
-        "content": pid
-    })
+    std::fs::rename(&file.staged, &file.target)?;
+    file.written = true;
+    Ok(())
 }
 
-pub(crate) fn run(command: &str, content: Option<&str>) -> Value {
-    let mut code = SUCCESS_CODE;
-    let mut response = String::new();
-
-    let result = std::process::Command::new("sh")
-        .arg("-c")
-        .arg(command)
-        .spawn();
-
-    if result.is_ok() {
-        info!("(commands::run) Ran process: '{}', successfully", command)
-    } else {
-        error!(
-            "(commands::run) Failed to run process: '{}', error: {}",
-            command,
-            result.as_ref().err().unwrap().to_string()
-        )
+fn rollback_file(file: &StagedFile) {
+    if let Some(backup) = &file.backup {
+        let _ = std::fs::rename(backup, &file.target);
+    } else if file.written && !file.existed {
+        let _ = std::fs::remove_file(&file.target);
     }
+}
 
-    if let Ok(mut child) = result {
-        if let Some(content) = content {
-            if let Some(mut stdin) = child.stdin.take() {
-                let _ = stdin.write(content.as_bytes());
-                let _ = stdin.flush();
+pub(crate) fn write_many(files: &serde_json::Map<String, Value>) -> Value {
+    let mut staged = Vec::new();
+    let mut errors = serde_json::Map::new();
+
+    for (path, spec) in files {
+        match stage_file(path, spec) {
+            Ok(file) => staged.push((path, file)),
+            Err(err) => {
+                errors.insert(path.clone(), Value::String(err));
             }
         }
+    }
 
-        if let Some(mut stdout) = child.stdout.take() {
-            let mut buffer = vec![];
-            let _ = stdout.read_to_end(&mut buffer);
-
-            for line in buffer.lines() {
-                response.push_str(format!("{}\n", line.unwrap()).as_str());
-            }
+    // two names for one file would have the second write undo the first
+    for (index, (path, file)) in staged.iter().enumerate() {
+        if let Some((other, _)) = staged[..index]
+            .iter()
+            .find(|(_, v)| same_file(&v.target, &file.target))
+        {
+            errors.insert(
+                path.to_string(),
+                Value::String(format!("same file as {}", other)),
+            );
         }
+    }
+
+    // nothing is touched unless every file could be staged
+    if !errors.is_empty() {
+        error!("(commands::write_many) Failed to stage: {:?}", errors);
+        return json!({
+            "cmd": "write_many",
+            "code": 2,
+            "errors": errors
+        });
+    }
 
-        if let Ok(status) = child.wait() {
-            code = status.code().unwrap_or(code as i32) as u8;
+    let operation = json!({
+        "files": staged
+            .iter()
+            .map(|(_, file)| json!({
+                "target": file.target,
+                "staged": file.staged.to_path_buf(),
+                "backup": backup_path(file)
+            }))
+            .collect::<Vec<_>>()
+    });
+
+    let record = match journal::begin("write_many", operation) {
+        Ok(record) => record,
+        Err(err) => {
+            error!("(commands::write_many) Failed to journal, error: {}", err);
+            return json!({
+                "cmd": "write_many",
+                "code": 2,
+                "error": err.to_string()
+            });
         }
     };
 
-    json!({
-        "cmd": "run",
-        "code": code,
-        "result": response
-    })
-}
+    for index in 0..staged.len() {
+        let (path, file) = &mut staged[index];
+        if let Err(err) = commit_file(file) {
+            error!(
+                "(commands::write_many) Failed to write: {}, error: {}, rolling back",
+                path, err
+            );
+
+            errors.insert(path.to_string(), Value::String(err.to_string()));
+            for (_, file) in staged[..=index].iter().rev() {
+                rollback_file(file);
+            }
 
-pub(crate) fn run_async(command: &str) -> Value {
-    let mut arguments = command.split_whitespace();
+            record.commit();
 
-    let result = std::process::Command::new(arguments.next().unwrap())
-        .args(arguments)
-        .spawn();
+            return json!({
+                "cmd": "write_many",
+                "code": 2,
+                "errors": errors
+            });
+        }
+    }
+
+    for (_, file) in &staged {
+        if let Some(backup) = &file.backup {
+            let _ = std::fs::remove_file(backup);
+        }
+    }
+
+    record.commit();
+
+    info!("(commands::write_many) Wrote {} files", staged.len());
+    json!({
+        "cmd": "write_many",
+        "code": SUCCESS_CODE,
+        "files": staged.iter().map(|(path, _)| path.as_str()).collect::<Vec<_>>()
+    })
+}
+
+pub(crate) fn write_rc(path: &str, content: &str, force: bool) -> Value {
+    let path = resolve_path(path);
 
-    if result.is_ok() {
+    if std::fs::exists(&path).unwrap_or(false) && !force {
         info!(
-            "(commands::run_async) Ran process: '{}', successfully",
-            command
-        )
+            "(commands::write_rc) Kept the existing {}",
+            path.to_string_lossy()
+        );
+
+        return io_failure(
+            "writerc",
+            1,
+            &std::io::Error::new(std::io::ErrorKind::AlreadyExists, "File exists"),
+        );
+    }
+
+    let result = File::create(&path).and_then(|mut v| v.write_all(content.as_bytes()));
+    if let Err(err) = result {
+        error!(
+            "(commands::write_rc) path: {}, error: {}",
+            path.to_string_lossy(),
+            err
+        );
+        return io_failure("writerc", 2, &err);
+    }
+
+    info!(
+        "(commands::write_rc) path: {}, force: {}",
+        path.to_string_lossy(),
+        force
+    );
+
+    json!({
+        "cmd": "writerc",
+        "code": SUCCESS_CODE
+    })
+}
+
+pub(crate) fn create_directory(path: &str) -> Value {
+    let path = resolve_path(path);
+
+    if let Err(err) = std::fs::create_dir_all(&path) {
+        error!(
+            "(commands::mkdir) path: {}, error: {}",
+            path.to_string_lossy(),
+            err
+        );
+        return io_failure("mkdir", 2, &err);
+    };
+
+    info!("(commands::mkdir) path: {}", path.to_str().unwrap());
+    json!({
+        "cmd": "mkdir",
+        "code": SUCCESS_CODE,
+    })
+}
+
+fn is_hidden(name: &str, _metadata: &std::fs::Metadata) -> bool {
+    #[cfg(windows)]
+    {
+        // FILE_ATTRIBUTE_HIDDEN
+        use std::os::windows::fs::MetadataExt;
+        if _metadata.file_attributes() & 0x2 != 0 {
+            return true;
+        }
+    }
+
+    name.starts_with('.')
+}
+
+// what file completion needs to show an entry; a symlink is described by its
+// own metadata and the size of the file it points to
+fn describe_entry(entry: &std::fs::DirEntry) -> Option<Value> {
+    let name = entry.file_name().to_string_lossy().to_string();
+    let metadata = entry.path().symlink_metadata().ok()?;
+    let file_type = metadata.file_type();
+
+    let (kind, target) = if file_type.is_symlink() {
+        let target = std::fs::read_link(entry.path()).ok();
+        ("symlink", target)
+    } else if file_type.is_dir() {
+        ("dir", None)
+    } else if file_type.is_file() {
+        ("file", None)
     } else {
+        ("other", None)
+    };
+
+    // the size of a directory says nothing about what is in it
+    let size = std::fs::metadata(entry.path())
+        .ok()
+        .filter(|v| !v.is_dir())
+        .map(|v| v.len());
+
+    let mtime = metadata
+        .modified()
+        .ok()
+        .and_then(|v| v.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|v| v.as_secs());
+
+    Some(json!({
+        "name": name,
+        "kind": kind,
+        "size": size,
+        "mtime": mtime,
+        "hidden": is_hidden(&name, &metadata),
+        "target": target
+    }))
+}
+
+// `sort` is `name`, `size`, `mtime` or `kind` (directories first); without it
+// the order is the file system's
+fn sort_entries(entries: &mut [Value], sort: &str) {
+    let key = |v: &Value, field: &str| v.get(field).and_then(|v| v.as_u64()).unwrap_or(0);
+    let name = |v: &Value| {
+        v.get("name")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_lowercase()
+    };
+
+    match sort {
+        "name" => entries.sort_by_key(name),
+        "size" => entries.sort_by_key(|v| (key(v, "size"), name(v))),
+        "mtime" => entries.sort_by_key(|v| (key(v, "mtime"), name(v))),
+        "kind" => entries.sort_by_key(|v| (v.get("kind") != Some(&json!("dir")), name(v))),
+        _ => {}
+    }
+}
+
+pub(crate) fn read_directory(path: &str, sort: Option<&str>, show_hidden: bool) -> Value {
+    let mut path = resolve_path(path);
+
+    let is_directory = path.is_dir();
+    if !path.is_dir() {
+        path = path.parent().unwrap_or(&PathBuf::from(".")).into();
+    }
+
+    let mut entries = Vec::new();
+    match path.read_dir() {
+        Ok(read) => {
+            for entry in read.flatten() {
+                if let Some(entry) = describe_entry(&entry) {
+                    entries.push(entry);
+                }
+            }
+        }
+
+        Err(err) => {
+            error!(
+                "(commands::list_dir) path: {}, error: {}",
+                path.to_string_lossy(),
+                err
+            );
+            return io_failure("list_dir", 2, &err);
+        }
+    }
+
+    if !show_hidden {
+        entries.retain(|v| v.get("hidden") != Some(&json!(true)));
+    }
+
+    if let Some(sort) = sort {
+        sort_entries(&mut entries, sort);
+    }
+
+    // `files` keeps the bare names older extensions expect
+    let files = entries
+        .iter()
+        .map(|v| v["name"].clone())
+        .collect::<Vec<_>>();
+
+    info!("(commands::list_dir) path: {}", path.to_string_lossy());
+    json!({
+        "cmd": "list_dir",
+        "isDir": is_directory,
+        "files": files,
+        "entries": entries,
+        "sep": std::path::MAIN_SEPARATOR.to_string()
+    })
+}
+
+// `temp.dir`, for systems where /tmp is small or mounted noexec, or else
+// TMPDIR and the platform default
+pub(crate) fn temp_directory() -> PathBuf {
+    config::get_string("temp.dir")
+        .map(|v| resolve_path(&v))
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+// the file is kept for the editor tridactyl opens it in; `dir` puts it
+// somewhere else than `temp_directory`
+pub(crate) fn temp(prefix: &str, content: &str, dir: Option<&str>) -> Value {
+    let prefix = format!("tmp_{}_", sanitize_file_name(prefix));
+    let directory = dir.map_or_else(temp_directory, resolve_path);
+
+    let file = std::fs::create_dir_all(&directory).and_then(|_| {
+        let mut file = tempfile::Builder::new()
+            .prefix(&prefix)
+            .suffix(".txt")
+            .tempfile_in(&directory)?;
+
+        file.write_all(content.as_bytes())?;
+        file.keep().map_err(|e| e.error)
+    });
+
+    match file {
+        Ok((_, path)) => {
+            info!("(commands::temp) path: {}", path.to_string_lossy());
+            maintenance::track_temp(&path);
+            json!({
+                "cmd": "temp",
+                "code": SUCCESS_CODE,
+                "content": path
+            })
+        }
+
+        Err(err) => {
+            error!(
+                "(commands::temp) dir: {}, error: {}",
+                directory.to_string_lossy(),
+                err
+            );
+            io_failure("temp", 2, &err)
+        }
+    }
+}
+
+pub(crate) fn move_file(
+    from: &str,
+    to: &str,
+    overwrite: bool,
+    cleanup: bool,
+    scan: Option<bool>,
+) -> Value {
+    let from = resolve_path(from);
+    let to = resolve_path(to);
+
+    // a detection quarantines the file instead of moving it
+    let verdict = scan
+        .unwrap_or_else(scan::is_enabled)
+        .then(|| scan::scan(&from));
+
+    if let Some(verdict) = verdict.as_ref().filter(|v| !v.is_clean()) {
+        return json!({
+            "cmd": "move",
+            "code": 1,
+            "scan": verdict.to_json()
+        });
+    }
+
+    let target = destination(&from, &to);
+    if let Err((code, err)) = check_move(&from, &target, overwrite) {
+        error!("(commands::move_file) {}: {}", err, from.to_string_lossy());
+        return io_failure("move", code, &err);
+    }
+
+    if let Err(err) = transfer(&from, &target) {
         error!(
-            "(commands::run_async) Failed to run process: '{}', error: {}",
-            command,
-            result.err().unwrap().to_string()
-        )
+            "(commands::move_file) Failed to move {} to {}, error: {}",
+            from.to_string_lossy(),
+            target.to_string_lossy(),
+            err
+        );
+
+        return io_failure("move", 2, &err);
+    }
+
+    info!(
+        "(commands::move_file) Moved {} to {}",
+        from.to_string_lossy(),
+        target.to_string_lossy()
+    );
+
+    // the source is gone by now; what is left to clean up is the temporary
+    // directory it was downloaded into, once empty, and never a directory
+    // the user keeps, like the downloads folder itself
+    if cleanup {
+        let temporary = [temp_directory(), std::env::temp_dir()]
+            .iter()
+            .filter_map(|v| v.canonicalize().ok())
+            .collect::<Vec<_>>();
+
+        let directory = from
+            .parent()
+            .and_then(|v| v.canonicalize().ok())
+            .filter(|v| temporary.iter().any(|t| v.starts_with(t) && v != t));
+
+        if let Some(directory) = directory {
+            if let Err(err) = std::fs::remove_dir(&directory) {
+                info!(
+                    "(commands::move_file) Kept {}: {}",
+                    directory.to_string_lossy(),
+                    err
+                );
+            }
+        }
+    }
+
+    let mut response = json!({
+        "cmd": "move",
+        "code": SUCCESS_CODE
+    });
+
+    if let Some(verdict) = verdict {
+        response["scan"] = verdict.to_json();
+    }
+
+    response
+}
+
+// everything that can be checked before anything is touched
+fn check_move(from: &Path, target: &Path, overwrite: bool) -> Result<(), (u8, std::io::Error)> {
+    use std::io::{Error, ErrorKind};
+
+    std::fs::symlink_metadata(from).map_err(|e| (2, e))?;
+    check_distinct(from, target).map_err(|e| (1, Error::new(ErrorKind::InvalidInput, e)))?;
+
+    if std::fs::symlink_metadata(target).is_ok() && !overwrite {
+        return Err((
+            1,
+            Error::new(ErrorKind::AlreadyExists, "Destination exists"),
+        ));
+    }
+
+    if target.is_dir() && !from.is_dir() {
+        return Err((
+            1,
+            Error::new(ErrorKind::IsADirectory, "Destination is a directory"),
+        ));
+    }
+
+    Ok(())
+}
+
+// either the whole source ends up at the target or nothing does; a failure
+// leaves the source where it was
+fn transfer(from: &Path, target: &Path) -> std::io::Result<()> {
+    match std::fs::rename(from, target) {
+        Err(err) if err.kind() == std::io::ErrorKind::CrossesDevices => {
+            info!(
+                "(commands::transfer) {} is on another filesystem, copying",
+                target.to_string_lossy()
+            );
+            transfer_copy(from, target)
+        }
+
+        result => result,
+    }
+}
+
+// a rename cannot cross filesystems, like from a tmpfs /tmp to home, so the
+// file is copied and synced before the source goes
+fn transfer_copy(from: &Path, target: &Path) -> std::io::Result<()> {
+    if std::fs::symlink_metadata(from)?.is_dir() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::CrossesDevices,
+            "Cannot move a directory across filesystems",
+        ));
+    }
+
+    // a target being overwritten is moved aside rather than replaced, so it
+    // can be put back if the move fails; it is deleted once the move is done
+    let aside = match std::fs::symlink_metadata(target) {
+        Ok(_) => {
+            let directory = target
+                .parent()
+                .filter(|v| !v.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            let aside = tempfile::Builder::new()
+                .suffix(".bak")
+                .tempfile_in(directory)?
+                .into_temp_path();
+            std::fs::rename(target, &aside)?;
+            Some(aside)
+        }
+
+        Err(_) => None,
+    };
+
+    let Err(err) = copy_then_remove(from, target) else {
+        return Ok(());
+    };
+
+    // a move that leaves the source behind did not happen, so the copy is
+    // taken back and whatever was there before restored
+    match aside {
+        Some(aside) => {
+            if let Err(restore) = std::fs::rename(&aside, target) {
+                error!(
+                    "(commands::transfer_copy) Failed to restore {}, it is kept as {}: {}",
+                    target.to_string_lossy(),
+                    aside.to_string_lossy(),
+                    restore
+                );
+                let _ = aside.keep();
+            }
+        }
+
+        None => {
+            let _ = std::fs::remove_file(target);
+        }
+    }
+
+    Err(err)
+}
+
+fn copy_then_remove(from: &Path, target: &Path) -> std::io::Result<()> {
+    let (_, _, unpreserved) = copy_staged(from, target, false, true)?;
+    if !unpreserved.is_empty() {
+        info!("(commands::transfer_copy) Not preserved: {:?}", unpreserved);
+    }
+
+    if let Some(directory) = target.parent().filter(|v| !v.as_os_str().is_empty()) {
+        sync_directory(directory)?;
+    }
+
+    std::fs::remove_file(from)
+}
+
+const COPY_BLOCK: usize = 1 << 20;
+
+// copies `from` into `to` from `offset` on; the bytes before it are taken to
+// be there already. blocks of zeroes are skipped instead of written, so
+// sparse files stay sparse on filesystems that support holes
+fn copy_range(from: &Path, to: &Path, offset: u64) -> std::io::Result<u64> {
+    use std::io::{Seek, SeekFrom};
+
+    let mut source = File::open(from)?;
+    let length = source.metadata()?.len();
+
+    let mut destination = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(to)?;
+
+    // drops a torn block an interrupted copy may have left behind
+    destination.set_len(offset)?;
+    source.seek(SeekFrom::Start(offset))?;
+    destination.seek(SeekFrom::Start(offset))?;
+
+    let mut buffer = vec![0; COPY_BLOCK];
+    loop {
+        let count = source.read(&mut buffer)?;
+        if count == 0 {
+            break;
+        }
+
+        if buffer[..count].iter().all(|v| *v == 0) {
+            destination.seek(SeekFrom::Current(count as i64))?;
+        } else {
+            destination.write_all(&buffer[..count])?;
+        }
+    }
+
+    destination.set_len(length)?;
+    destination.sync_all()?;
+
+    Ok(length - offset)
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+// with `resume` an interrupted copy leaves `<to>.part` behind and the next
+// one continues where it stopped
+fn copy_staged(
+    from: &Path,
+    to: &Path,
+    resume: bool,
+    xattrs: bool,
+) -> std::io::Result<(u64, u64, Vec<String>)> {
+    if resume {
+        let partial = partial_path(to);
+        let length = std::fs::metadata(from)?.len();
+        let offset = std::fs::metadata(&partial)
+            .map(|v| v.len())
+            .ok()
+            .filter(|v| *v <= length)
+            .unwrap_or(0);
+
+        let bytes = copy_range(from, &partial, offset)?;
+        let unpreserved = metadata::preserve(from, &partial, xattrs);
+        std::fs::rename(&partial, to)?;
+        return Ok((bytes, offset, unpreserved));
+    }
+
+    // copy next to the destination first, so a failure never leaves a
+    // truncated file in its place
+    let directory = to
+        .parent()
+        .filter(|v| !v.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    let staged = tempfile::NamedTempFile::new_in(directory)?.into_temp_path();
+    let bytes = copy_range(from, &staged, 0)?;
+    let unpreserved = metadata::preserve(from, &staged, xattrs);
+    staged.persist(to).map_err(|e| e.error)?;
+
+    Ok((bytes, 0, unpreserved))
+}
+
+pub(crate) fn copy_file(
+    from: &str,
+    to: &str,
+    overwrite: bool,
+    resume: bool,
+    xattrs: Option<bool>,
+) -> Value {
+    let from = resolve_path(from);
+    let to = destination(&from, &resolve_path(to));
+
+    if let Err(err) = check_distinct(&from, &to) {
+        error!("(commands::copy_file) {}: {}", err, from.to_string_lossy());
+        return io_failure(
+            "copy",
+            1,
+            &std::io::Error::new(std::io::ErrorKind::InvalidInput, err),
+        );
+    }
+
+    if !from.is_file() || (to.exists() && !overwrite) {
+        error!(
+            "(commands::copy_file) Refusing to copy {} to {}",
+            from.to_string_lossy(),
+            to.to_string_lossy()
+        );
+
+        let err = match std::fs::metadata(&from) {
+            Err(err) => err,
+            Ok(_) if from.is_file() => {
+                std::io::Error::new(std::io::ErrorKind::AlreadyExists, "Destination exists")
+            }
+            Ok(_) => std::io::Error::new(std::io::ErrorKind::InvalidInput, "Source is not a file"),
+        };
+
+        return io_failure("copy", 1, &err);
+    }
+
+    match copy_staged(&from, &to, resume, metadata::xattrs_enabled(xattrs)) {
+        Ok((bytes, offset, unpreserved)) => {
+            for item in &unpreserved {
+                error!("(commands::copy_file) Not preserved, {}", item);
+            }
+
+            info!(
+                "(commands::copy_file) {} -> {}, resumed at {}",
+                from.to_string_lossy(),
+                to.to_string_lossy(),
+                offset
+            );
+
+            json!({
+                "cmd": "copy",
+                "code": SUCCESS_CODE,
+                "file": to,
+                "bytes": bytes,
+                "resumed": offset,
+                "unpreserved": unpreserved
+            })
+        }
+
+        Err(err) => {
+            error!("(commands::copy_file) Failed to copy, error: {}", err);
+            io_failure("copy", 2, &err)
+        }
+    }
+}
+
+pub(crate) fn env(key: &str) -> Value {
+    match std::env::var(key) {
+        Ok(value) => {
+            info!("(commands::env) Retrived environment key: {}", key);
+            json!({
+                "cmd": "env",
+                "content": value
+            })
+        }
+        Err(_) => {
+            error!("(commands::env) Failed to retrive environment key: {}", key);
+            json!({
+                "cmd": "env"
+            })
+        }
+    }
+}
+
+// a name with a path separator is taken as a path, like a shell would
+pub(crate) fn which(program: &str) -> Value {
+    let path = if program.contains(std::path::is_separator) {
+        let path = resolve_path(program);
+        is_executable(&path).then_some(path)
+    } else if program.is_empty() {
+        None
+    } else {
+        find_program(program)
+    };
+
+    match path {
+        Some(path) => {
+            info!(
+                "(commands::which) {} is {}",
+                program,
+                path.to_string_lossy()
+            );
+            json!({
+                "cmd": "which",
+                "code": SUCCESS_CODE,
+                "content": path
+            })
+        }
+
+        None => {
+            info!("(commands::which) {} not found", program);
+            io_failure(
+                "which",
+                1,
+                &std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("{} not found", program),
+                ),
+            )
+        }
+    }
+}
+
+pub(crate) fn get_process_id() -> Value {
+    let pid = std::process::id();
+    info!("(commands::get_process_id) Process id: {}", pid);
+
+    json!({
+        "cmd": "ppid",
+        "content": pid
+    })
+}
+
+// the shell `run` and friends hand their command line to
+pub(crate) fn shell() -> String {
+    config::get_string("shell").unwrap_or("sh".into())
+}
+
+pub(crate) fn termination_signal(status: &ExitStatus) -> Option<i32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        status.signal()
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        None
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Limits {
+    nice: Option<i32>,
+    max_output: Option<usize>,
+    cpu: Option<u64>,
+    memory: Option<u64>,
+}
+
+impl Limits {
+    // per request values win over the `run` section of the native config
+    pub(crate) fn from_message(map: &serde_json::Map<String, Value>) -> Self {
+        let get = |key: &str| {
+            map.get(key)
+                .and_then(|v| v.as_i64())
+                .or_else(|| config::get(&format!("run.{}", key)).and_then(|v| v.as_i64()))
+        };
+
+        // a negative or out of range size is ignored rather than wrapped
+        // around into a huge one
+        Limits {
+            nice: get("nice").and_then(|v| i32::try_from(v).ok()),
+            max_output: get("max_output").and_then(|v| usize::try_from(v).ok()),
+            cpu: get("cpu_limit").and_then(|v| u64::try_from(v).ok()),
+            memory: get("memory_limit").and_then(|v| u64::try_from(v).ok()),
+        }
+    }
+
+    fn apply(&self, process: &mut Command) {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+
+            let limits = *self;
+            if limits.nice.is_none() && limits.cpu.is_none() && limits.memory.is_none() {
+                return;
+            }
+
+            unsafe {
+                process.pre_exec(move || {
+                    if let Some(nice) = limits.nice {
+                        if libc::setpriority(libc::PRIO_PROCESS, 0, nice) == -1 {
+                            return Err(std::io::Error::last_os_error());
+                        }
+                    }
+
+                    let limit = |resource, value: u64| {
+                        let limit = libc::rlimit {
+                            rlim_cur: value as libc::rlim_t,
+                            rlim_max: value as libc::rlim_t,
+                        };
+
+                        if libc::setrlimit(resource, &limit) == -1 {
+                            return Err(std::io::Error::last_os_error());
+                        }
+
+                        Ok(())
+                    };
+
+                    if let Some(cpu) = limits.cpu {
+                        limit(libc::RLIMIT_CPU, cpu)?;
+                    }
+
+                    if let Some(memory) = limits.memory {
+                        limit(libc::RLIMIT_AS, memory)?;
+                    }
+
+                    Ok(())
+                });
+            }
+        }
+
+        #[cfg(not(unix))]
+        let _ = process;
+    }
+}
+
+// reads a pipe to the end, killing `pid` once more than `limit` bytes showed up
+fn read_pipe<R: Read + Send + 'static>(
+    pipe: Option<R>,
+    pid: u32,
+    limit: Option<usize>,
+) -> JoinHandle<(Vec<u8>, bool)> {
+    std::thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let Some(pipe) = pipe else {
+            return (buffer, false);
+        };
+
+        let Some(limit) = limit else {
+            let mut pipe = pipe;
+            let _ = pipe.read_to_end(&mut buffer);
+            return (buffer, false);
+        };
+
+        let _ = pipe.take(limit as u64 + 1).read_to_end(&mut buffer);
+        if buffer.len() <= limit {
+            return (buffer, false);
+        }
+
+        buffer.truncate(limit);
+
+        if let Some(pid) = Pid::new(pid.into()) {
+            #[cfg(unix)]
+            let _ = send_signal(pid, libc::SIGKILL);
+
+            #[cfg(not(unix))]
+            let _ = send_signal(pid, 9);
+        }
+
+        (buffer, true)
+    })
+}
+
+// runs a command to completion, killing it once `timeout` has passed
+pub(crate) fn run_with_timeout(
+    mut process: Command,
+    input: Option<&[u8]>,
+    timeout: Duration,
+) -> std::io::Result<Output> {
+    let mut child = process
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let stdin = child.stdin.take();
+    let input = input.unwrap_or_default().to_vec();
+    std::thread::spawn(move || {
+        if let Some(mut stdin) = stdin {
+            let _ = stdin.write_all(&input);
+        }
+    });
+
+    let stdout = read_pipe(child.stdout.take(), child.id(), None);
+    let stderr = read_pipe(child.stderr.take(), child.id(), None);
+
+    let start = Instant::now();
+    let status = loop {
+        match child.try_wait()? {
+            Some(status) => break status,
+            None if start.elapsed() < timeout => std::thread::sleep(Duration::from_millis(20)),
+            None => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    "process timed out",
+                ));
+            }
+        }
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default().0,
+        stderr: stderr.join().unwrap_or_default().0,
+    })
+}
+
+// spawns a prepared command, feeding it `content` and collecting its output
+// into a reply shaped like the one `run` has always given
+fn run_process(
+    name: &'static str,
+    mut process: Command,
+    command: &str,
+    content: Option<&str>,
+    cwd: Option<&str>,
+    limits: Limits,
+) -> Value {
+    limits.apply(&mut process);
+
+    process
+        .stdin(if content.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    if let Some(cwd) = cwd {
+        process.current_dir(resolve_path(cwd));
+    }
+
+    let mut child = match process.spawn() {
+        Ok(child) => {
+            info!(
+                "(commands::{}) Ran process: '{}', successfully",
+                name, command
+            );
+            child
+        }
+
+        Err(err) => {
+            error!(
+                "(commands::{}) Failed to run process: '{}', error: {}",
+                name, command, err
+            );
+
+            let mut reply = io_failure(name, 2, &err);
+            reply["result"] = json!("");
+            return reply;
+        }
+    };
+
+    // stdin is fed from its own thread while both output pipes are drained
+    // concurrently, otherwise a child filling one pipe would block forever
+    let stdin = child.stdin.take();
+    let content = content.unwrap_or_default().to_string();
+    let writer = std::thread::spawn(move || {
+        if let Some(mut stdin) = stdin {
+            let _ = stdin.write_all(content.as_bytes());
+        }
+    });
+
+    children::register(child.id(), name, command);
+
+    let stdout = read_pipe(child.stdout.take(), child.id(), limits.max_output);
+    let stderr = read_pipe(child.stderr.take(), child.id(), limits.max_output);
+
+    let mut code = SUCCESS_CODE;
+    let mut exit_code = None;
+    let mut signal = None;
+    let status = child.wait();
+    children::unregister(child.id());
+
+    if let Ok(status) = status {
+        exit_code = status.code();
+        signal = termination_signal(&status);
+        code = exit_code.unwrap_or(code as i32) as u8;
+
+        // mirror the shell convention so a killed process never reads as success
+        if let Some(signal) = signal {
+            code = (128 + signal) as u8;
+        }
+    }
+
+    let _ = writer.join();
+    let (stdout, stdout_truncated) = stdout.join().unwrap_or_default();
+    let (stderr, stderr_truncated) = stderr.join().unwrap_or_default();
+
+    if stdout_truncated || stderr_truncated {
+        error!(
+            "(commands::{}) Process: '{}', exceeded the output limit and was killed",
+            name, command
+        );
+    }
+
+    if let Some(signal) = signal {
+        error!(
+            "(commands::{}) Process: '{}', was terminated by signal {}",
+            name, command, signal
+        );
+    }
+
+    json!({
+        "cmd": name,
+        "code": code,
+        "result": String::from_utf8_lossy(&stdout),
+        "stderr": String::from_utf8_lossy(&stderr),
+        "exit_code": exit_code,
+        "signaled": signal.is_some(),
+        "signal": signal,
+        "truncated": stdout_truncated || stderr_truncated
+    })
+}
+
+pub(crate) fn run(
+    command: &str,
+    content: Option<&str>,
+    cwd: Option<&str>,
+    limits: Limits,
+) -> Value {
+    if let Err(refusal) = policy::check_shell(command) {
+        return policy::denied("run", refusal);
     }
 
+    let mut process = Command::new(shell());
+    process.arg("-c").arg(command);
+
+    run_process("run", process, command, content, cwd, limits)
+}
+
+pub(crate) fn exec(
+    program: &str,
+    arguments: &[String],
+    content: Option<&str>,
+    cwd: Option<&str>,
+    limits: Limits,
+) -> Value {
+    if program.is_empty() {
+        return io_failure(
+            "exec",
+            1,
+            &std::io::Error::new(std::io::ErrorKind::InvalidInput, "Empty program"),
+        );
+    }
+
+    if let Err(refusal) = policy::check_exec(program, arguments) {
+        return policy::denied("exec", refusal);
+    }
+
+    let mut process = Command::new(resolve_path(program));
+    process.args(arguments);
+
+    let command = format!("{} {:?}", program, arguments);
+    run_process("exec", process, &command, content, cwd, limits)
+}
+
+fn stream_pipe<R: Read + Send + 'static>(
+    pipe: Option<R>,
+    stream: &'static str,
+    id: Value,
+    reply: u64,
+) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        let Some(pipe) = pipe else { return };
+
+        events::wait_for_reply(reply);
+        for line in BufReader::new(pipe).split(b'\n').map_while(Result::ok) {
+            events::emit(&json!({
+                "cmd": "run_stream",
+                "id": id,
+                "event": stream,
+                "line": String::from_utf8_lossy(&line)
+            }));
+        }
+    })
+}
+
+pub(crate) fn run_stream(
+    command: &str,
+    content: Option<&str>,
+    cwd: Option<&str>,
+    id: Value,
+) -> Value {
+    if let Err(refusal) = policy::check_shell(command) {
+        return policy::denied("run_stream", refusal);
+    }
+
+    let mut process = Command::new(shell());
+    process
+        .arg("-c")
+        .arg(command)
+        .stdin(if content.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    if let Some(cwd) = cwd {
+        process.current_dir(resolve_path(cwd));
+    }
+
+    let mut child = match process.spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            error!(
+                "(commands::run_stream) Failed to run process: '{}', error: {}",
+                command, err
+            );
+
+            let mut reply = io_failure("run_stream", 2, &err);
+            reply["id"] = json!(id);
+            return reply;
+        }
+    };
+
+    let pid = child.id();
+    info!(
+        "(commands::run_stream) Ran process: '{}', successfully, pid: {}",
+        command, pid
+    );
+    children::register(pid, "run_stream", command);
+
+    let stdin = child.stdin.take();
+    let content = content.unwrap_or_default().to_string();
+    std::thread::spawn(move || {
+        if let Some(mut stdin) = stdin {
+            let _ = stdin.write_all(content.as_bytes());
+        }
+    });
+
+    // events must not overtake the reply acknowledging the stream
+    let reply = events::pending_reply();
+    let stdout = stream_pipe(child.stdout.take(), "stdout", id.clone(), reply);
+    let stderr = stream_pipe(child.stderr.take(), "stderr", id.clone(), reply);
+
+    let event_id = id.clone();
+    std::thread::spawn(move || {
+        let status = child.wait();
+        children::unregister(pid);
+
+        let _ = stdout.join();
+        let _ = stderr.join();
+
+        let exit_code = status.as_ref().ok().and_then(|v| v.code());
+        let signal = status.as_ref().ok().and_then(termination_signal);
+
+        let code = match (exit_code, signal) {
+            (Some(code), _) => code as u8,
+            (None, Some(signal)) => (128 + signal) as u8,
+            (None, None) => 2,
+        };
+
+        events::wait_for_reply(reply);
+        events::emit(&json!({
+            "cmd": "run_stream",
+            "id": event_id,
+            "event": "exit",
+            "code": code,
+            "exit_code": exit_code,
+            "signaled": signal.is_some(),
+            "signal": signal
+        }));
+    });
+
     json!({
-        "cmd": "run_async"
+        "cmd": "run_stream",
+        "code": SUCCESS_CODE,
+        "id": id,
+        "pid": pid
     })
 }
+
+// starts a process in its own session with no ties to the host's stdio, so
+// it outlives the host when the browser shuts it down
+pub(crate) fn spawn_detached(mut process: Command) -> std::io::Result<u32> {
+    process
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    #[cfg(unix)]
+    unsafe {
+        use std::os::unix::process::CommandExt;
+        process.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+
+            Ok(())
+        });
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+
+        const DETACHED_PROCESS: u32 = 0x00000008;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x00000200;
+        process.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+    }
+
+    let mut child = process.spawn()?;
+    let pid = child.id();
+    children::register_detached(pid);
+
+    // reap it for as long as the host lives, init takes over after that
+    std::thread::spawn(move || {
+        let _ = child.wait();
+        children::unregister_detached(pid);
+    });
+
+    Ok(pid)
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn opener(target: &str) -> Option<Command> {
+    let mut process = Command::new("open");
+    process.arg(target);
+    Some(process)
+}
+
+// explorer hands the target to its default application like `start` would,
+// without cmd parsing a target that holds quotes or `&`
+#[cfg(windows)]
+pub(crate) fn opener(target: &str) -> Option<Command> {
+    let mut process = Command::new("explorer.exe");
+    process.arg(target);
+    Some(process)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+pub(crate) fn opener(target: &str) -> Option<Command> {
+    if find_program("xdg-open").is_some() {
+        let mut process = Command::new("xdg-open");
+        process.arg(target);
+        Some(process)
+    } else if find_program("gio").is_some() {
+        let mut process = Command::new("gio");
+        process.args(["open", target]);
+        Some(process)
+    } else {
+        None
+    }
+}
+
+// hands a url or path to the desktop's default application; `open.command`
+// replaces the platform opener, with `%f` standing for the target
+pub(crate) fn open(target: &str) -> Value {
+    let is_url = Regex::new(r"^[A-Za-z][A-Za-z0-9+.-]*:[^\\]")
+        .unwrap()
+        .is_match(target)
+        && !Path::new(target).exists();
+
+    let target = if is_url {
+        target.to_string()
+    } else {
+        let path = resolve_path(target);
+        if !path.exists() {
+            error!("(commands::open) No such file: {}", path.to_string_lossy());
+            return json!({
+                "cmd": "open",
+                "code": 1,
+                "error": "No such file"
+            });
+        }
+
+        path.to_string_lossy().to_string()
+    };
+
+    let process = match config::get_string("open.command") {
+        Some(command) => shell_words::split(&command)
+            .ok()
+            .filter(|v| !v.is_empty())
+            .map(|mut arguments| {
+                if arguments.iter().any(|v| v.contains("%f")) {
+                    arguments = arguments.iter().map(|v| v.replace("%f", &target)).collect();
+                } else {
+                    arguments.push(target.clone());
+                }
+
+                let mut process = Command::new(&arguments[0]);
+                process.args(&arguments[1..]);
+                process
+            }),
+
+        None => opener(&target),
+    };
+
+    let Some(process) = process else {
+        error!("(commands::open) No opener found");
+        return json!({
+            "cmd": "open",
+            "code": 2,
+            "error": "No opener found"
+        });
+    };
+
+    if let Err(refusal) = policy::check_command(&process) {
+        return policy::denied("open", refusal);
+    }
+
+    match spawn_detached(process) {
+        Ok(pid) => {
+            info!("(commands::open) Opened {}", target);
+            json!({
+                "cmd": "open",
+                "code": SUCCESS_CODE,
+                "pid": pid
+            })
+        }
+
+        Err(err) => {
+            error!("(commands::open) Failed to open {}, error: {}", target, err);
+            json!({
+                "cmd": "open",
+                "code": 2,
+                "error": err.to_string()
+            })
+        }
+    }
+}
+
+pub(crate) fn run_async(
+    command: &str,
+    cwd: Option<&str>,
+    mode: Option<&str>,
+    detach: bool,
+) -> Value {
+    let mut process = match mode {
+        Some("shell") => {
+            if let Err(refusal) = policy::check_shell(command) {
+                return policy::denied("run_async", refusal);
+            }
+
+            let mut process = Command::new(shell());
+            process.arg("-c").arg(command);
+            process
+        }
+
+        Some("words") | None => {
+            let arguments = match shell_words::split(command) {
+                Ok(arguments) => arguments,
+                Err(err) => {
+                    error!(
+                        "(commands::run_async) Failed to parse: '{}', error: {}",
+                        command, err
+                    );
+
+                    return json!({
+                        "cmd": "run_async",
+                        "code": 1,
+                        "error": err.to_string()
+                    });
+                }
+            };
+
+            let Some((program, arguments)) = arguments.split_first() else {
+                return io_failure(
+                    "run_async",
+                    1,
+                    &std::io::Error::new(std::io::ErrorKind::InvalidInput, "Empty command"),
+                );
+            };
+
+            if let Err(refusal) = policy::check_exec(program, arguments) {
+                return policy::denied("run_async", refusal);
+            }
+
+            let mut process = Command::new(program);
+            process.args(arguments);
+            process
+        }
+
+        Some(mode) => {
+            error!("(commands::run_async) Unknown mode: {}", mode);
+            return io_failure(
+                "run_async",
+                1,
+                &std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("Unknown mode {}", mode),
+                ),
+            );
+        }
+    };
+
+    if let Some(cwd) = cwd {
+        process.current_dir(resolve_path(cwd));
+    }
+
+    if detach {
+        return match spawn_detached(process) {
+            Ok(pid) => {
+                info!(
+                    "(commands::run_async) Ran detached process: '{}', successfully, pid: {}",
+                    command, pid
+                );
+
+                json!({
+                    "cmd": "run_async",
+                    "code": SUCCESS_CODE,
+                    "pid": pid,
+                    "job": null
+                })
+            }
+
+            Err(err) => {
+                error!(
+                    "(commands::run_async) Failed to run detached process: '{}', error: {}",
+                    command, err
+                );
+
+                io_failure("run_async", 2, &err)
+            }
+        };
+    }
+
+    match jobs::spawn(process, command) {
+        Ok((job, pid)) => {
+            info!(
+                "(commands::run_async) Ran process: '{}', successfully, pid: {}, job: {}",
+                command, pid, job
+            );
+
+            json!({
+                "cmd": "run_async",
+                "code": SUCCESS_CODE,
+                "pid": pid,
+                "job": job
+            })
+        }
+
+        Err(err) => {
+            error!(
+                "(commands::run_async) Failed to run process: '{}', error: {}",
+                command, err
+            );
+
+            io_failure("run_async", 2, &err)
+        }
+    }
+}
+
+pub(crate) fn parse_signal(signal: &str) -> Option<i32> {
+    if let Ok(number) = signal.parse() {
+        return Some(number);
+    }
+
+    let name = signal.to_uppercase();
+    let name = name.strip_prefix("SIG").unwrap_or(&name);
+
+    #[cfg(unix)]
+    let signal = match name {
+        "HUP" => libc::SIGHUP,
+        "INT" => libc::SIGINT,
+        "QUIT" => libc::SIGQUIT,
+        "KILL" => libc::SIGKILL,
+        "USR1" => libc::SIGUSR1,
+        "USR2" => libc::SIGUSR2,
+        "TERM" => libc::SIGTERM,
+        "CONT" => libc::SIGCONT,
+        "STOP" => libc::SIGSTOP,
+        _ => return None,
+    };
+
+    #[cfg(not(unix))]
+    let signal = match name {
+        "KILL" => 9,
+        "TERM" => 15,
+        _ => return None,
+    };
+
+    Some(signal)
+}
+
+// a process that may be signalled; kill(2) takes 0 and negative pids for
+// process groups, -1 for every process the user owns, and 1 is init
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Pid(i32);
+
+impl Pid {
+    pub(crate) fn new(pid: u64) -> Option<Pid> {
+        i32::try_from(pid).ok().filter(|v| *v > 1).map(Pid)
+    }
+
+    pub(crate) fn id(self) -> u32 {
+        self.0 as u32
+    }
+}
+
+impl std::fmt::Display for Pid {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn send_signal(pid: Pid, signal: i32) -> std::io::Result<()> {
+    if unsafe { libc::kill(pid.0 as libc::pid_t, signal) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(windows)]
+pub(crate) fn send_signal(pid: Pid, signal: i32) -> std::io::Result<()> {
+    let mut process = Command::new("taskkill");
+    process.arg("/PID").arg(pid.to_string());
+
+    // windows has no signals, anything but a polite TERM is a forced kill
+    if signal != 15 {
+        process.arg("/F");
+    }
+
+    let status = process
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other("taskkill failed"))
+    }
+}
+
+pub(crate) fn kill(pid: u64, signal: &str) -> Value {
+    let Some(signal) = parse_signal(signal) else {
+        error!("(commands::kill) Unknown signal: {}", signal);
+        return io_failure(
+            "kill",
+            1,
+            &std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Unknown signal {}", signal),
+            ),
+        );
+    };
+
+    let Some(pid) = Pid::new(pid).filter(|v| v.id() != std::process::id()) else {
+        error!("(commands::kill) Refusing to signal pid: {}", pid);
+        return io_failure(
+            "kill",
+            1,
+            &std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Refusing to signal pid {}", pid),
+            ),
+        );
+    };
+
+    // only helpers the host started, never the browser or the user's session
+    if !children::is_tracked(pid.id()) {
+        return policy::denied(
+            "kill",
+            policy::Refusal::Policy(format!("pid {} was not started by the host", pid)),
+        );
+    }
+
+    match send_signal(pid, signal) {
+        Ok(_) => {
+            info!("(commands::kill) pid: {}, signal: {}", pid, signal);
+            json!({
+                "cmd": "kill",
+                "code": SUCCESS_CODE
+            })
+        }
+
+        Err(err) => {
+            error!(
+                "(commands::kill) pid: {}, signal: {}, error: {}",
+                pid, signal, err
+            );
+
+            io_failure("kill", 2, &err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{
+        backup_path, commit_file, rollback_file, sanitize_file_name, stage_file, transfer_copy,
+    };
+
+    #[test]
+    fn keeps_useful_names() {
+        assert_eq!(sanitize_file_name("github.com"), "github.com");
+        assert_eq!(sanitize_file_name("my-notes_2024"), "my-notes_2024");
+        assert_eq!(sanitize_file_name("Issue Tracker"), "issue_tracker");
+        assert_eq!(sanitize_file_name("Ünïcödé-日本語"), "ünïcödé-日本語");
+        assert_eq!(sanitize_file_name("archive.tar.gz"), "archive.tar.gz");
+    }
+
+    #[test]
+    fn blocks_traversal() {
+        assert_eq!(sanitize_file_name("../../etc/passwd"), "etcpasswd");
+        assert_eq!(sanitize_file_name("..\\..\\windows"), "windows");
+        assert_eq!(sanitize_file_name("a/../b"), "a.b");
+        assert_eq!(sanitize_file_name("...."), "");
+        assert_eq!(sanitize_file_name("a....b"), "a.b");
+        assert_eq!(sanitize_file_name(".bashrc"), "bashrc");
+        assert_eq!(sanitize_file_name("name."), "name");
+    }
+
+    #[test]
+    fn drops_control_and_invisible_characters() {
+        assert_eq!(sanitize_file_name("a\0b"), "ab");
+        assert_eq!(sanitize_file_name("a\nb\tc"), "a_b_c");
+        assert_eq!(sanitize_file_name("evil\u{202e}txt.exe"), "eviltxt.exe");
+        assert_eq!(sanitize_file_name("zero\u{200b}width"), "zerowidth");
+        assert_eq!(sanitize_file_name("C:con|<>*?\"'"), "ccon");
+        assert_eq!(sanitize_file_name("   "), "");
+    }
+
+    #[test]
+    fn rollback_keeps_a_target_it_could_not_back_up() {
+        let directory = tempfile::tempdir().unwrap();
+        let target = directory.path().join("kept.txt");
+        std::fs::write(&target, "original").unwrap();
+
+        let mut file = stage_file(&target.to_string_lossy(), &json!("replacement")).unwrap();
+
+        // a directory where the backup goes makes moving the target aside fail
+        let backup = backup_path(&file);
+        std::fs::create_dir_all(backup.join("occupied")).unwrap();
+
+        assert!(commit_file(&mut file).is_err());
+        rollback_file(&file);
+
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "original");
+    }
+
+    // procfs files can be copied but not removed, even by root
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn failed_transfer_restores_the_overwritten_target() {
+        let directory = tempfile::tempdir().unwrap();
+        let target = directory.path().join("kept.txt");
+        std::fs::write(&target, "original").unwrap();
+
+        let source = std::path::Path::new("/proc/self/status");
+        assert!(transfer_copy(source, &target).is_err());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "original");
+        assert_eq!(std::fs::read_dir(directory.path()).unwrap().count(), 1);
+    }
+}
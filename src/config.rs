@@ -0,0 +1,112 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use serde_json::{Map, Value};
+
+use crate::commands::config_directory;
+
+// settings for the native host itself, kept apart from the tridactylrc
+const FILE: &str = "native.json";
+
+// the file as last parsed, with the path, mtime and length it had then; it
+// is read again whenever one of them changes
+struct Cached {
+    path: PathBuf,
+    stamp: Option<(SystemTime, u64)>,
+    value: Value,
+}
+
+static CACHE: Mutex<Option<Cached>> = Mutex::new(None);
+
+pub(crate) fn path() -> PathBuf {
+    config_directory().join(FILE)
+}
+
+pub(crate) fn load() -> Value {
+    let path = path();
+    let stamp = std::fs::metadata(&path)
+        .ok()
+        .and_then(|v| Some((v.modified().ok()?, v.len())));
+
+    let mut cache = CACHE.lock().unwrap();
+    if let Some(cached) = cache
+        .as_ref()
+        .filter(|v| v.path == path && v.stamp.is_some() && v.stamp == stamp)
+    {
+        return cached.value.clone();
+    }
+
+    let value = parse(&path);
+    *cache = Some(Cached {
+        path,
+        stamp,
+        value: value.clone(),
+    });
+
+    value
+}
+
+fn parse(path: &Path) -> Value {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return Value::Object(Map::new());
+    };
+
+    match serde_json::from_str::<Value>(&content) {
+        Ok(value) if value.is_object() => value,
+        Ok(_) => {
+            error!(
+                "(config::load) {} is not a json object, ignoring it",
+                path.to_string_lossy()
+            );
+
+            Value::Object(Map::new())
+        }
+
+        Err(err) => {
+            error!(
+                "(config::load) Failed to parse {}, error: {}",
+                path.to_string_lossy(),
+                err
+            );
+
+            Value::Object(Map::new())
+        }
+    }
+}
+
+// looks up a dotted key such as `sqlite.databases`
+pub(crate) fn get(key: &str) -> Option<Value> {
+    let mut value = load();
+    for part in key.split('.') {
+        value = value.get_mut(part)?.take();
+    }
+
+    Some(value)
+}
+
+pub(crate) fn get_bool(key: &str) -> Option<bool> {
+    get(key).and_then(|v| v.as_bool())
+}
+
+pub(crate) fn get_u64(key: &str) -> Option<u64> {
+    get(key).and_then(|v| v.as_u64())
+}
+
+pub(crate) fn get_string(key: &str) -> Option<String> {
+    get(key).and_then(|v| v.as_str().map(String::from))
+}
+
+pub(crate) fn get_strings(key: &str) -> Vec<String> {
+    match get(key) {
+        Some(Value::Array(values)) => values
+            .iter()
+            .filter_map(|v| v.as_str().map(String::from))
+            .collect(),
+
+        Some(Value::String(value)) => vec![value],
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,109 @@
+use std::{process::Command, time::Duration};
+
+use crate::{commands::run_with_timeout, config};
+
+// unanswered dialogs count as a no
+const DEFAULT_TIMEOUT: u64 = 60_000;
+
+// in order of preference; they all exit zero when the question is accepted
+#[cfg(all(unix, not(target_os = "macos")))]
+const DIALOGS: [&str; 2] = ["zenity", "kdialog"];
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn known(dialog: &str, question: &str) -> Command {
+    let mut process = Command::new(dialog);
+    match dialog {
+        "zenity" => process.args(["--question", "--title", "Tridactyl", "--text", question]),
+        _ => process.args(["--title", "Tridactyl", "--yesno", question]),
+    };
+
+    process
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn detect(question: &str) -> Option<Command> {
+    DIALOGS
+        .iter()
+        .find(|v| crate::commands::find_program(v).is_some())
+        .map(|v| known(v, question))
+}
+
+#[cfg(target_os = "macos")]
+fn detect(question: &str) -> Option<Command> {
+    let quote = |v: &str| format!("\"{}\"", v.replace('\\', "\\\\").replace('"', "\\\""));
+
+    // the cancel button makes osascript exit non-zero
+    let mut process = Command::new("osascript");
+    process.args([
+        "-e",
+        &format!(
+            "display dialog {} with title \"Tridactyl\" buttons {{\"Deny\", \"Allow\"}} \
+             default button \"Deny\" cancel button \"Deny\"",
+            quote(question)
+        ),
+    ]);
+
+    Some(process)
+}
+
+#[cfg(windows)]
+fn detect(question: &str) -> Option<Command> {
+    let quote = |v: &str| format!("'{}'", v.replace('\'', "''"));
+    let script = format!(
+        "Add-Type -AssemblyName System.Windows.Forms; \
+         $r = [System.Windows.Forms.MessageBox]::Show({}, 'Tridactyl', 'YesNo', 'Warning', 'Button2'); \
+         if ($r -ne 'Yes') {{ exit 1 }}",
+        quote(question)
+    );
+
+    let mut process = Command::new("powershell");
+    process.args(["-NoProfile", "-WindowStyle", "Hidden", "-Command", &script]);
+    Some(process)
+}
+
+// `security.confirm.dialog` is a whole command line, with `%m` standing for
+// the question, that exits zero to accept
+fn configured(command: &str, question: &str) -> Option<Command> {
+    let arguments = shell_words::split(command)
+        .ok()
+        .filter(|v| !v.is_empty())?
+        .into_iter()
+        .map(|v| v.replace("%m", question))
+        .collect::<Vec<_>>();
+
+    let mut process = Command::new(&arguments[0]);
+    process.args(&arguments[1..]);
+    Some(process)
+}
+
+pub(crate) fn enabled() -> bool {
+    config::get_bool("security.confirm.enabled").unwrap_or(false)
+}
+
+// asks the user, blocking until they answer; anything but a yes, including no
+// dialog being found, is a no
+pub(crate) fn ask(question: &str) -> bool {
+    let process = match config::get_string("security.confirm.dialog") {
+        Some(command) => configured(&command, question),
+        None => detect(question),
+    };
+
+    let Some(process) = process else {
+        error!("(confirm::ask) No dialog found");
+        return false;
+    };
+
+    let timeout = config::get_u64("security.confirm.timeout").unwrap_or(DEFAULT_TIMEOUT);
+    match run_with_timeout(process, None, Duration::from_millis(timeout)) {
+        Ok(output) => {
+            let accepted = output.status.success();
+            info!("(confirm::ask) {:?}: {}", question, accepted);
+            accepted
+        }
+
+        Err(err) => {
+            error!("(confirm::ask) Failed to run the dialog, error: {}", err);
+            false
+        }
+    }
+}
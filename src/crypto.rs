@@ -0,0 +1,175 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use age::{x25519, Decryptor, Encryptor, Recipient};
+use serde_json::{json, Value};
+
+use crate::{
+    commands::{resolve_path, SUCCESS_CODE},
+    keys,
+};
+
+const DEFAULT_KEY: &str = "default";
+
+// streams into a temporary file next to `path` and only replaces it once
+// everything went through
+fn write_through<F>(path: &Path, mode: u32, write: F) -> Result<(), String>
+where
+    F: FnOnce(&mut File) -> Result<(), String>,
+{
+    let directory = path
+        .parent()
+        .filter(|v| !v.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    let mut file = tempfile::NamedTempFile::new_in(directory).map_err(|e| e.to_string())?;
+    write(file.as_file_mut())?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.as_file()
+            .set_permissions(std::fs::Permissions::from_mode(mode))
+            .map_err(|e| e.to_string())?;
+    }
+
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    file.persist(path).map_err(|e| e.error.to_string())?;
+    Ok(())
+}
+
+fn encrypt(
+    source: &Path,
+    destination: &Path,
+    key: &str,
+    recipients: &[String],
+) -> Result<String, String> {
+    let identity = keys::identity(key)?;
+    let public = identity.to_public();
+
+    let extra = recipients
+        .iter()
+        .map(|v| x25519::Recipient::from_str(v).map_err(|e| format!("{}: {}", v, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let recipients = std::iter::once(&public)
+        .chain(extra.iter())
+        .map(|v| v as &dyn Recipient);
+
+    let encryptor = Encryptor::with_recipients(recipients).map_err(|e| e.to_string())?;
+    let mut input = File::open(source).map_err(|e| e.to_string())?;
+
+    write_through(destination, 0o644, |file| {
+        let mut output = encryptor.wrap_output(file).map_err(|e| e.to_string())?;
+        std::io::copy(&mut input, &mut output).map_err(|e| e.to_string())?;
+        output.finish().map_err(|e| e.to_string())?;
+        Ok(())
+    })?;
+
+    Ok(public.to_string())
+}
+
+fn decryptor(source: &Path) -> Result<impl Read, String> {
+    let input = BufReader::new(File::open(source).map_err(|e| e.to_string())?);
+    let decryptor = Decryptor::new_buffered(input).map_err(|e| e.to_string())?;
+
+    let identities = keys::identities();
+    decryptor
+        .decrypt(identities.iter().map(|v| v as &dyn age::Identity))
+        .map_err(|e| e.to_string())
+}
+
+pub(crate) fn encrypt_file(
+    file: &str,
+    output: Option<&str>,
+    key: Option<&str>,
+    recipients: &[String],
+) -> Value {
+    let source = resolve_path(file);
+    let destination = match output {
+        Some(output) => resolve_path(output),
+        None => PathBuf::from(format!("{}.age", source.to_string_lossy())),
+    };
+
+    let key = key.unwrap_or(DEFAULT_KEY);
+    match encrypt(&source, &destination, key, recipients) {
+        Ok(recipient) => {
+            info!(
+                "(crypto::encrypt_file) {} -> {}",
+                source.to_string_lossy(),
+                destination.to_string_lossy()
+            );
+
+            json!({
+                "cmd": "encrypt_file",
+                "code": SUCCESS_CODE,
+                "file": destination,
+                "key": key,
+                "recipient": recipient
+            })
+        }
+
+        Err(err) => {
+            error!("(crypto::encrypt_file) Failed to encrypt, error: {}", err);
+            json!({
+                "cmd": "encrypt_file",
+                "code": 2,
+                "error": err
+            })
+        }
+    }
+}
+
+// without `output` the plaintext is returned in the reply, like `read`
+pub(crate) fn decrypt_file(file: &str, output: Option<&str>) -> Value {
+    let source = resolve_path(file);
+
+    let result = decryptor(&source).and_then(|mut reader| match output {
+        Some(output) => {
+            let destination = resolve_path(output);
+            write_through(&destination, 0o600, |file| {
+                std::io::copy(&mut reader, file).map_err(|e| e.to_string())?;
+                file.flush().map_err(|e| e.to_string())
+            })?;
+
+            Ok(json!({ "file": destination }))
+        }
+
+        None => {
+            let mut content = String::new();
+            reader
+                .read_to_string(&mut content)
+                .map_err(|e| e.to_string())?;
+
+            Ok(json!({ "content": content }))
+        }
+    });
+
+    match result {
+        Ok(mut response) => {
+            info!(
+                "(crypto::decrypt_file) Decrypted {}",
+                source.to_string_lossy()
+            );
+
+            response["cmd"] = json!("decrypt_file");
+            response["code"] = json!(SUCCESS_CODE);
+            response
+        }
+
+        Err(err) => {
+            error!("(crypto::decrypt_file) Failed to decrypt, error: {}", err);
+            json!({
+                "cmd": "decrypt_file",
+                "code": 2,
+                "error": err
+            })
+        }
+    }
+}
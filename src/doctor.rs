@@ -0,0 +1,195 @@
+use std::{
+    io::{Read, Write},
+    path::Path,
+    process::{Command, Stdio},
+    sync::mpsc,
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+
+use crate::{commands::is_executable, logging, manifest};
+
+// the release id, which firefox passes when it starts the host
+const EXTENSION_ID: &str = "tridactyl.vim@cmcaine.co.uk";
+const SELF_TEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+// a check that passed, or what is wrong and how to fix it
+type Finding = Result<String, (String, String)>;
+
+// starts the host the way the browser would and asks it for its version over
+// the same framing
+fn self_test(executable: &Path, manifest: &Path) -> Result<String, String> {
+    let mut child = Command::new(executable)
+        .args([manifest.as_os_str(), EXTENSION_ID.as_ref()])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("failed to start: {}", e))?;
+
+    let message = json!({ "cmd": "version" }).to_string();
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let reply = (|| -> std::io::Result<Vec<u8>> {
+            let mut length = [0u8; 4];
+            stdout.read_exact(&mut length)?;
+            let mut reply = vec![0u8; u32::from_ne_bytes(length) as usize];
+            stdout.read_exact(&mut reply)?;
+            Ok(reply)
+        })();
+
+        let _ = sender.send(reply);
+    });
+
+    let written = stdin
+        .write_all(&(message.len() as u32).to_ne_bytes())
+        .and_then(|_| stdin.write_all(message.as_bytes()))
+        .and_then(|_| stdin.flush());
+
+    let result = match written {
+        Err(err) => Err(format!("failed to send a message: {}", err)),
+        Ok(()) => match receiver.recv_timeout(SELF_TEST_TIMEOUT) {
+            Err(_) => Err(format!(
+                "no reply within {} seconds",
+                SELF_TEST_TIMEOUT.as_secs()
+            )),
+
+            Ok(Err(err)) => Err(format!("the reply was cut short: {}", err)),
+            Ok(Ok(reply)) => match serde_json::from_slice::<Value>(&reply) {
+                Ok(reply) if reply["cmd"] == "version" && reply["code"] == 0 => Ok(format!(
+                    "answered as version {}",
+                    reply["version"].as_str().unwrap_or("unknown")
+                )),
+
+                Ok(reply) => Err(format!("unexpected reply: {}", reply)),
+                Err(err) => Err(format!("the reply is not json: {}", err)),
+            },
+        },
+    };
+
+    drop(stdin);
+    if result.is_err() {
+        let _ = child.kill();
+    }
+
+    let _ = child.wait();
+    result
+}
+
+fn diagnose(path: &Path, expected: &Value) -> Vec<Finding> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) => {
+            return vec![Err((
+                format!("cannot read the manifest: {}", err),
+                "run tridactyl --setup".into(),
+            ))]
+        }
+    };
+
+    let manifest = match serde_json::from_str::<Value>(&content) {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            return vec![Err((
+                format!("the manifest is not valid json: {}", err),
+                "run tridactyl --repair".into(),
+            ))]
+        }
+    };
+
+    let mut findings = vec![Ok("the manifest parses".to_string())];
+
+    if manifest["name"] != expected["name"] || manifest["type"] != expected["type"] {
+        findings.push(Err((
+            format!(
+                "expected name {} and type {}, found {} and {}",
+                expected["name"], expected["type"], manifest["name"], manifest["type"]
+            ),
+            "run tridactyl --repair".into(),
+        )));
+    }
+
+    let missing = expected["allowed_extensions"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|id| {
+            !manifest["allowed_extensions"]
+                .as_array()
+                .is_some_and(|v| v.contains(id))
+        })
+        .filter_map(|v| v.as_str())
+        .collect::<Vec<_>>();
+
+    findings.push(match missing.is_empty() {
+        true => Ok("tridactyl's extension ids are allowed".into()),
+        false => Err((
+            format!("allowed_extensions lacks {}", missing.join(", ")),
+            "run tridactyl --repair".into(),
+        )),
+    });
+
+    let executable = Path::new(manifest["path"].as_str().unwrap_or_default());
+    if !executable.is_absolute() || !is_executable(executable) {
+        findings.push(Err((
+            format!(
+                "path {:?} is not an executable file",
+                executable.to_string_lossy()
+            ),
+            "run tridactyl --repair, or --setup --bin-path <executable>".into(),
+        )));
+
+        return findings;
+    }
+
+    findings.push(Ok(format!(
+        "{} is executable",
+        executable.to_string_lossy()
+    )));
+
+    findings.push(self_test(executable, path).map_err(|err| {
+        (
+            format!("self-test failed, {}", err),
+            format!(
+                "check {}, or reinstall the executable",
+                logging::log_file().to_string_lossy()
+            ),
+        )
+    }));
+
+    findings
+}
+
+// checks every installed manifest and exits non-zero if anything is wrong
+pub(crate) fn doctor() {
+    let manifests = manifest::manifest_paths();
+    if manifests.is_empty() {
+        println!("no supported browser found, nothing to check");
+        std::process::exit(1);
+    }
+
+    let expected: Value = serde_json::from_str(&manifest::manifest_content()).unwrap();
+    let mut healthy = true;
+
+    for (browser, path) in manifests {
+        println!("{} ({})", browser, path.to_string_lossy());
+
+        for finding in diagnose(&path, &expected) {
+            match finding {
+                Ok(message) => println!("  ok   {}", message),
+                Err((problem, fix)) => {
+                    healthy = false;
+                    println!("  FAIL {}\n       fix: {}", problem, fix);
+                }
+            }
+        }
+    }
+
+    if !healthy {
+        std::process::exit(1);
+    }
+}
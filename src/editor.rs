@@ -0,0 +1,287 @@
+use std::{io::Write, path::Path, process::Command};
+
+use serde_json::{json, Value};
+
+use crate::{
+    children,
+    commands::{find_program, sanitize_file_name, temp_directory, SUCCESS_CODE},
+    config,
+};
+
+// terminal emulators tried in order, with the arguments that make them run a
+// command and stay in the foreground until it exits
+const TERMINALS: &[(&str, &[&str])] = &[
+    ("x-terminal-emulator", &["-e"]),
+    ("alacritty", &["-e"]),
+    ("kitty", &[]),
+    ("foot", &[]),
+    ("wezterm", &["start", "--always-new-process", "--"]),
+    ("gnome-terminal", &["--wait", "--"]),
+    ("konsole", &["-e"]),
+    ("xfce4-terminal", &["--disable-server", "-x"]),
+    ("urxvt", &["-e"]),
+    ("st", &["-e"]),
+    ("xterm", &["-e"]),
+];
+
+pub(crate) fn editor() -> Vec<String> {
+    config::get_string("editor.command")
+        .or_else(|| std::env::var("VISUAL").ok())
+        .or_else(|| std::env::var("EDITOR").ok())
+        .and_then(|v| shell_words::split(&v).ok())
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| vec!["vi".into()])
+}
+
+// arguments opening `file` at `line` and `column` (both 1-based) for the
+// editors that are known to support it; others just get the file
+fn file_arguments(editor: &str, file: &str, line: Option<u64>, column: Option<u64>) -> Vec<String> {
+    let program = Path::new(editor)
+        .file_stem()
+        .map(|v| v.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let Some(line) = line else {
+        return vec![file.into()];
+    };
+
+    let column = column.unwrap_or(1);
+    match program.as_str() {
+        "vi" | "vim" | "nvim" | "gvim" | "mvim" => {
+            vec![format!("+call cursor({}, {})", line, column), file.into()]
+        }
+
+        "emacs" | "emacsclient" | "kak" | "micro" => {
+            vec![format!("+{}:{}", line, column), file.into()]
+        }
+
+        "nano" => vec![format!("+{},{}", line, column), file.into()],
+        "code" | "codium" | "code-oss" | "vscodium" => {
+            vec!["-g".into(), format!("{}:{}:{}", file, line, column)]
+        }
+
+        "hx" | "helix" | "subl" | "zed" => vec![format!("{}:{}:{}", file, line, column)],
+        "kate" => vec![
+            "-l".into(),
+            line.to_string(),
+            "-c".into(),
+            column.to_string(),
+            file.into(),
+        ],
+
+        "gedit" => vec![format!("+{}:{}", line, column), file.into()],
+        "vis" | "mcedit" => vec![format!("+{}", line), file.into()],
+
+        _ => vec![file.into()],
+    }
+}
+
+// `editor.terminal: false` runs the editor directly, for graphical editors
+pub(crate) fn terminal() -> Option<Vec<String>> {
+    if config::get_bool("editor.terminal") == Some(false) {
+        return Some(Vec::new());
+    }
+
+    if let Some(terminal) = config::get_string("editor.terminal")
+        .or_else(|| std::env::var("TERMINAL").ok())
+        .and_then(|v| shell_words::split(&v).ok())
+        .filter(|v| !v.is_empty())
+    {
+        let known = TERMINALS
+            .iter()
+            .find(|(name, _)| terminal.len() == 1 && *name == terminal[0]);
+
+        return Some(match known {
+            Some((_, arguments)) => terminal
+                .into_iter()
+                .chain(arguments.iter().map(|v| v.to_string()))
+                .collect(),
+            None => terminal,
+        });
+    }
+
+    TERMINALS.iter().find_map(|(name, arguments)| {
+        find_program(name)?;
+        Some(
+            std::iter::once(name.to_string())
+                .chain(arguments.iter().map(|v| v.to_string()))
+                .collect(),
+        )
+    })
+}
+
+// `editor.template` (or `editor` itself as a string) is the whole command
+// line, e.g. `alacritty -e nvim %f +%l`; placeholders are substituted per
+// argument so paths with spaces survive
+fn template() -> Option<String> {
+    config::get_string("editor.template").or_else(|| config::get_string("editor"))
+}
+
+fn expand_template(
+    template: &str,
+    file: &str,
+    line: Option<u64>,
+    column: Option<u64>,
+) -> Option<Vec<String>> {
+    let mut has_file = false;
+    let mut arguments = shell_words::split(template)
+        .ok()?
+        .into_iter()
+        .map(|argument| {
+            let mut result = String::new();
+            let mut characters = argument.chars();
+            while let Some(c) = characters.next() {
+                if c != '%' {
+                    result.push(c);
+                    continue;
+                }
+
+                match characters.next() {
+                    Some('f') => {
+                        has_file = true;
+                        result.push_str(file);
+                    }
+                    Some('l') => result.push_str(&line.unwrap_or(1).to_string()),
+                    Some('c') => result.push_str(&column.unwrap_or(1).to_string()),
+                    Some('%') => result.push('%'),
+                    Some(c) => {
+                        result.push('%');
+                        result.push(c);
+                    }
+                    None => result.push('%'),
+                }
+            }
+
+            result
+        })
+        .collect::<Vec<_>>();
+
+    if arguments.is_empty() {
+        return None;
+    }
+
+    if !has_file {
+        arguments.push(file.into());
+    }
+
+    Some(arguments)
+}
+
+fn command_line(
+    file: &str,
+    line: Option<u64>,
+    column: Option<u64>,
+) -> Result<Vec<String>, &'static str> {
+    if let Some(template) = template() {
+        return expand_template(&template, file, line, column).ok_or("Invalid editor template");
+    }
+
+    let mut arguments = terminal().ok_or("No terminal emulator found")?;
+    let editor = editor();
+    arguments.extend(editor.iter().cloned());
+    arguments.extend(file_arguments(&editor[0], file, line, column));
+
+    Ok(arguments)
+}
+
+pub(crate) fn editor_command(
+    content: &str,
+    prefix: &str,
+    extension: Option<&str>,
+    line: Option<u64>,
+    column: Option<u64>,
+) -> Value {
+    let extension = extension.map(sanitize_file_name).unwrap_or("txt".into());
+    let directory = temp_directory();
+    let file = std::fs::create_dir_all(&directory)
+        .and_then(|_| {
+            tempfile::Builder::new()
+                .prefix(&format!("tmp_{}_", sanitize_file_name(prefix)))
+                .suffix(&format!(".{}", extension))
+                .tempfile_in(&directory)
+        })
+        .and_then(|mut file| file.write_all(content.as_bytes()).map(|_| file));
+
+    let file = match file {
+        Ok(file) => file,
+        Err(err) => {
+            error!(
+                "(editor::editor_command) Failed to create file, error: {}",
+                err
+            );
+            return json!({
+                "cmd": "editor",
+                "code": 2,
+                "error": err.to_string()
+            });
+        }
+    };
+
+    let arguments = match command_line(&file.path().to_string_lossy(), line, column) {
+        Ok(arguments) => arguments,
+        Err(err) => {
+            error!("(editor::editor_command) {}", err);
+            return json!({
+                "cmd": "editor",
+                "code": 1,
+                "error": err
+            });
+        }
+    };
+
+    let mut process = Command::new(&arguments[0]);
+    process.args(&arguments[1..]);
+
+    let status = process.spawn().and_then(|mut child| {
+        children::register(child.id(), "editor", &arguments.join(" "));
+        let status = child.wait();
+        children::unregister(child.id());
+        status
+    });
+
+    let status = match status {
+        Ok(status) => status,
+        Err(err) => {
+            error!(
+                "(editor::editor_command) Failed to launch '{}', error: {}",
+                arguments[0], err
+            );
+
+            return json!({
+                "cmd": "editor",
+                "code": 2,
+                "error": err.to_string()
+            });
+        }
+    };
+
+    // some editors replace the file instead of writing to it, so read it back
+    // by path rather than through the open handle
+    match std::fs::read_to_string(file.path()) {
+        Ok(content) => {
+            info!(
+                "(editor::editor_command) Edited {}",
+                file.path().to_string_lossy()
+            );
+
+            json!({
+                "cmd": "editor",
+                "code": if status.success() { SUCCESS_CODE } else { 1 },
+                "content": content,
+                "exit_code": status.code()
+            })
+        }
+
+        Err(err) => {
+            error!(
+                "(editor::editor_command) Failed to read back, error: {}",
+                err
+            );
+            json!({
+                "cmd": "editor",
+                "code": 2,
+                "error": err.to_string()
+            })
+        }
+    }
+}
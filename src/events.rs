@@ -0,0 +1,33 @@
+use std::sync::{Condvar, Mutex};
+
+use serde_json::Value;
+
+use crate::protocol::write_frame;
+
+// number of replies written so far, used to hold events back until the
+// reply to the request that started them has gone out
+static REPLIES: Mutex<u64> = Mutex::new(0);
+static REPLIED: Condvar = Condvar::new();
+
+// the sequence number the reply to the request being handled will get
+pub(crate) fn pending_reply() -> u64 {
+    *REPLIES.lock().unwrap() + 1
+}
+
+pub(crate) fn mark_replied() {
+    *REPLIES.lock().unwrap() += 1;
+    REPLIED.notify_all();
+}
+
+pub(crate) fn wait_for_reply(reply: u64) {
+    let mut replies = REPLIES.lock().unwrap();
+    while *replies < reply {
+        replies = REPLIED.wait(replies).unwrap();
+    }
+}
+
+pub(crate) fn emit(event: &Value) {
+    if let Err(err) = write_frame(event) {
+        error!("(events::emit) Failed to write event, error: {}", err);
+    }
+}
@@ -0,0 +1,216 @@
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    commands::{url_host, write_atomic, SUCCESS_CODE},
+    config, logging,
+};
+
+// the session lives on disk rather than in the host, so every browser window
+// (each with its own host process) sees the same one and a reconnect does
+// not end it
+fn state_file() -> PathBuf {
+    logging::data_directory().join("focus.json")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+// the running session, if any; an expired one counts as none
+fn session() -> Option<Value> {
+    let content = std::fs::read_to_string(state_file()).ok()?;
+    let session: Value = serde_json::from_str(&content).ok()?;
+
+    let ends = session.get("ends")?.as_u64()?;
+    (ends > now()).then_some(session)
+}
+
+fn to_strings(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(|v| v.as_array())
+        .map(|v| {
+            v.iter()
+                .filter_map(|v| v.as_str())
+                .map(|v| v.to_lowercase())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// `example.com` covers its subdomains too
+fn matches(domain: &str, rule: &str) -> bool {
+    let rule = rule.trim_start_matches("*.");
+    domain == rule || domain.ends_with(&format!(".{}", rule))
+}
+
+fn describe(session: &Value) -> Value {
+    let ends = session
+        .get("ends")
+        .and_then(|v| v.as_u64())
+        .unwrap_or_default();
+
+    json!({
+        "label": session.get("label"),
+        "started": session.get("started"),
+        "ends": ends,
+        "remaining": ends.saturating_sub(now()),
+        "block": session.get("block")
+    })
+}
+
+// a week, longer than anyone means to stay locked out of a site
+const MAX_DURATION: u64 = 7 * 24 * 60 * 60;
+
+// `duration` is in seconds; `block` adds domains to `focus.block` for this
+// session only
+pub(crate) fn focus_start(duration: u64, label: Option<&str>, block: &[String]) -> Value {
+    if let Some(session) = session() {
+        return json!({
+            "cmd": "focus_start",
+            "code": 1,
+            "error": "A focus session is already running",
+            "session": describe(&session)
+        });
+    }
+
+    let started = now();
+    let Some(ends) = started
+        .checked_add(duration)
+        .filter(|_| duration <= MAX_DURATION)
+    else {
+        return json!({
+            "cmd": "focus_start",
+            "code": 1,
+            "error": format!("Duration must be at most {}s", MAX_DURATION)
+        });
+    };
+
+    let session = json!({
+        "label": label,
+        "started": started,
+        "ends": ends,
+        "block": block.iter().map(|v| v.to_lowercase()).collect::<Vec<_>>()
+    });
+
+    let result = std::fs::create_dir_all(logging::data_directory())
+        .and_then(|_| write_atomic(&state_file(), session.to_string().as_bytes(), None));
+
+    match result {
+        Ok(()) => {
+            info!("(focus::focus_start) Focus session for {}s", duration);
+            json!({
+                "cmd": "focus_start",
+                "code": SUCCESS_CODE,
+                "session": describe(&session)
+            })
+        }
+
+        Err(err) => {
+            error!(
+                "(focus::focus_start) Failed to save session, error: {}",
+                err
+            );
+
+            json!({
+                "cmd": "focus_start",
+                "code": 2,
+                "error": err.to_string()
+            })
+        }
+    }
+}
+
+// with `focus.locked` a session can only run out, not be stopped
+pub(crate) fn focus_stop() -> Value {
+    if session().is_none() {
+        return json!({
+            "cmd": "focus_stop",
+            "code": 1,
+            "error": "No focus session is running"
+        });
+    }
+
+    if config::get_bool("focus.locked").unwrap_or(false) {
+        error!("(focus::focus_stop) Refused to stop a locked session");
+        return json!({
+            "cmd": "focus_stop",
+            "code": 1,
+            "error": "Focus sessions are locked"
+        });
+    }
+
+    match std::fs::remove_file(state_file()) {
+        Ok(()) => {
+            info!("(focus::focus_stop) Focus session stopped");
+            json!({
+                "cmd": "focus_stop",
+                "code": SUCCESS_CODE
+            })
+        }
+
+        Err(err) => {
+            error!("(focus::focus_stop) Failed to stop session, error: {}", err);
+            json!({
+                "cmd": "focus_stop",
+                "code": 2,
+                "error": err.to_string()
+            })
+        }
+    }
+}
+
+pub(crate) fn focus_status() -> Value {
+    let session = session();
+    json!({
+        "cmd": "focus_status",
+        "code": SUCCESS_CODE,
+        "active": session.is_some(),
+        "session": session.as_ref().map(describe)
+    })
+}
+
+// answers for each domain (or url) whether it is blocked right now: only
+// during a session, when it matches `focus.block` or the session's own list
+// and not `focus.allow`
+pub(crate) fn should_block(domains: &[String]) -> Value {
+    let session = session();
+
+    let mut block = config::get_strings("focus.block")
+        .iter()
+        .map(|v| v.to_lowercase())
+        .collect::<Vec<_>>();
+
+    block.extend(to_strings(session.as_ref().and_then(|v| v.get("block"))));
+
+    let allow = config::get_strings("focus.allow")
+        .iter()
+        .map(|v| v.to_lowercase())
+        .collect::<Vec<_>>();
+
+    let content = domains
+        .iter()
+        .map(|domain| {
+            let host = url_host(domain).unwrap_or(domain.to_lowercase());
+            let blocked = session.is_some()
+                && block.iter().any(|rule| matches(&host, rule))
+                && !allow.iter().any(|rule| matches(&host, rule));
+
+            (domain.clone(), Value::Bool(blocked))
+        })
+        .collect::<serde_json::Map<_, _>>();
+
+    json!({
+        "cmd": "should_block",
+        "code": SUCCESS_CODE,
+        "active": session.is_some(),
+        "content": content
+    })
+}
@@ -0,0 +1,141 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use rayon::prelude::*;
+use serde_json::{json, Value};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::commands::{resolve_path, SUCCESS_CODE};
+
+const BUFFER_SIZE: usize = 64 * 1024;
+const ALGORITHMS: [&str; 3] = ["sha256", "sha512", "sha1"];
+
+fn digest<D: Digest>(path: &Path) -> std::io::Result<(String, u64)> {
+    let mut file = File::open(path)?;
+    let mut hasher = D::new();
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+    let mut size = 0;
+
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+
+    let digest = hasher
+        .finalize()
+        .iter()
+        .map(|v| format!("{:02x}", v))
+        .collect();
+
+    Ok((digest, size))
+}
+
+// hex digest and size of a file, `algorithm` being one of `ALGORITHMS`
+pub(crate) fn hash_file(path: &Path, algorithm: &str) -> std::io::Result<(String, u64)> {
+    match algorithm {
+        "sha512" => digest::<Sha512>(path),
+        "sha1" => digest::<Sha1>(path),
+        _ => digest::<Sha256>(path),
+    }
+}
+
+fn collect_files(directory: &Path, recursive: bool, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = directory.read_dir() else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+
+        if kind.is_file() {
+            files.push(entry.path());
+        } else if kind.is_dir() && recursive {
+            collect_files(&entry.path(), recursive, files);
+        }
+    }
+}
+
+pub(crate) fn hash_many(
+    paths: &[String],
+    directory: Option<&str>,
+    recursive: bool,
+    algorithm: Option<&str>,
+) -> Value {
+    let algorithm = algorithm.unwrap_or("sha256");
+    if !ALGORITHMS.contains(&algorithm) {
+        error!("(hash::hash_many) Unknown algorithm: '{}'", algorithm);
+        return json!({
+            "cmd": "hash_many",
+            "code": 1,
+            "error": format!("Unknown algorithm '{}'", algorithm)
+        });
+    }
+
+    let mut files = paths.iter().map(|v| resolve_path(v)).collect::<Vec<_>>();
+
+    if let Some(directory) = directory {
+        let mut found = Vec::new();
+        collect_files(&resolve_path(directory), recursive, &mut found);
+        found.sort();
+        files.extend(found);
+    }
+
+    let results = files
+        .par_iter()
+        .map(|path| match hash_file(path, algorithm) {
+            Ok((digest, size)) => json!({
+                "file": path,
+                "digest": digest,
+                "size": size
+            }),
+
+            Err(err) => json!({
+                "file": path,
+                "error": err.to_string()
+            }),
+        })
+        .collect::<Vec<_>>();
+
+    // identical content, for deduplicating download folders
+    let mut groups: HashMap<&str, Vec<&Value>> = HashMap::new();
+    for result in &results {
+        if let Some(digest) = result["digest"].as_str() {
+            groups.entry(digest).or_default().push(&result["file"]);
+        }
+    }
+
+    let mut duplicates = groups
+        .into_values()
+        .filter(|v| v.len() > 1)
+        .collect::<Vec<_>>();
+
+    duplicates.sort_by_key(|v| v[0].as_str());
+
+    let failed = results.iter().filter(|v| v.get("error").is_some()).count();
+    info!(
+        "(hash::hash_many) Hashed {} files with {}, {} failed",
+        results.len() - failed,
+        algorithm,
+        failed
+    );
+
+    json!({
+        "cmd": "hash_many",
+        "code": if failed == 0 { SUCCESS_CODE } else { 2 },
+        "algorithm": algorithm,
+        "content": results,
+        "duplicates": duplicates
+    })
+}
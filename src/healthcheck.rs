@@ -0,0 +1,280 @@
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::Path,
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+
+use crate::{commands::is_executable, config, logging, manifest};
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:8517";
+// a request line and a few headers is all a monitor sends
+const MAX_REQUEST: usize = 8192;
+
+fn check(name: &str, result: Result<String, String>) -> Value {
+    match result {
+        Ok(detail) => json!({ "check": name, "ok": true, "detail": detail }),
+        Err(detail) => json!({ "check": name, "ok": false, "detail": detail }),
+    }
+}
+
+fn check_binary() -> Result<String, String> {
+    let executable = std::env::current_exe().map_err(|e| e.to_string())?;
+    if is_executable(&executable) {
+        Ok(executable.to_string_lossy().to_string())
+    } else {
+        Err(format!(
+            "{} is not executable",
+            executable.to_string_lossy()
+        ))
+    }
+}
+
+// a manifest has to be the one `--setup` would install, and the executable
+// it points at has to run
+fn check_manifest(path: &Path) -> Result<String, String> {
+    let content =
+        std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.to_string_lossy(), e))?;
+
+    let current = serde_json::from_str::<Value>(&content)
+        .map_err(|e| format!("{}: invalid json, {}", path.to_string_lossy(), e))?;
+
+    let executable = current.get("path").and_then(|v| v.as_str()).unwrap_or("");
+    if !is_executable(Path::new(executable)) {
+        return Err(format!("points at a missing executable {:?}", executable));
+    }
+
+    let expected: Value =
+        serde_json::from_str(&manifest::expected_content(Some(&current))).unwrap();
+    if current != expected {
+        return Err(format!(
+            "{} differs from what --setup installs, run --repair",
+            path.to_string_lossy()
+        ));
+    }
+
+    Ok(path.to_string_lossy().to_string())
+}
+
+fn check_config() -> Result<String, String> {
+    let path = config::path();
+    let Ok(content) = std::fs::read_to_string(&path) else {
+        return Ok("not present, using defaults".into());
+    };
+
+    match serde_json::from_str::<Value>(&content) {
+        Ok(value) if value.is_object() => Ok(path.to_string_lossy().to_string()),
+        Ok(_) => Err(format!("{} is not a json object", path.to_string_lossy())),
+        Err(err) => Err(format!("{}: {}", path.to_string_lossy(), err)),
+    }
+}
+
+// anyone who can write these can make the browser run anything
+#[cfg(unix)]
+fn check_writable_by_others(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.permissions().mode() & 0o022 != 0 => Err(format!(
+            "{} is writable by group or others",
+            path.to_string_lossy()
+        )),
+
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn check_writable_by_others(_path: &Path) -> Result<(), String> {
+    Ok(())
+}
+
+fn check_permissions() -> Result<String, String> {
+    let mut paths = vec![config::path()];
+    paths.extend(manifest::manifest_paths().into_iter().map(|(_, v)| v));
+    if let Ok(executable) = std::env::current_exe() {
+        paths.push(executable);
+    }
+
+    for path in &paths {
+        check_writable_by_others(path)?;
+    }
+
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(logging::log_file())
+        .map_err(|e| format!("{}: {}", logging::log_file().to_string_lossy(), e))?;
+
+    Ok("ok".into())
+}
+
+pub(crate) fn run_checks() -> Vec<Value> {
+    let mut checks = vec![check("binary", check_binary())];
+
+    let manifests = manifest::manifest_paths();
+    if manifests.is_empty() {
+        checks.push(check("manifest", Err("no supported browser found".into())));
+    }
+
+    let executable = manifest::executable();
+    for (browser, path) in manifests {
+        let result = check_manifest(&path)
+            .and_then(|v| manifest::snap_warning(browser, &executable).map_or(Ok(v), Err));
+
+        #[cfg(windows)]
+        let result = result.and_then(|v| match manifest::is_registered(browser, &path) {
+            true => Ok(v),
+            false => Err("not in the registry, run --setup".into()),
+        });
+
+        checks.push(check(&format!("manifest ({})", browser), result));
+    }
+
+    checks.push(check("config", check_config()));
+    checks.push(check("permissions", check_permissions()));
+    checks
+}
+
+fn is_healthy(checks: &[Value]) -> bool {
+    checks.iter().all(|v| v["ok"] == true)
+}
+
+fn respond(stream: &mut TcpStream, status: &str, body: &Value) -> std::io::Result<()> {
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+// the token may come as a bearer token or a `token` query parameter, since
+// some monitors can only be given a url
+fn is_authorized(request: &str, token: &str) -> bool {
+    let mut lines = request.lines();
+    let target = lines
+        .next()
+        .and_then(|v| v.split_whitespace().nth(1))
+        .unwrap_or("");
+
+    let from_query = target
+        .split_once('?')
+        .map(|(_, query)| query.split('&').any(|v| v == format!("token={}", token)))
+        .unwrap_or(false);
+
+    let from_header = lines.any(|line| {
+        line.split_once(':').is_some_and(|(name, value)| {
+            name.eq_ignore_ascii_case("authorization")
+                && value.trim() == format!("Bearer {}", token)
+        })
+    });
+
+    from_query || from_header
+}
+
+fn serve_one(mut stream: TcpStream, token: &str) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !request.windows(4).any(|v| v == b"\r\n\r\n") && request.len() < MAX_REQUEST {
+        let read = stream.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+
+        request.extend_from_slice(&buffer[..read]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    if !is_authorized(&request, token) {
+        return respond(
+            &mut stream,
+            "401 Unauthorized",
+            &json!({ "error": "bad token" }),
+        );
+    }
+
+    let checks = run_checks();
+    let healthy = is_healthy(&checks);
+    let status = if healthy {
+        "200 OK"
+    } else {
+        "503 Service Unavailable"
+    };
+    respond(
+        &mut stream,
+        status,
+        &json!({ "healthy": healthy, "checks": checks }),
+    )
+}
+
+// answers every request with the checks; it only listens on loopback and
+// refuses to start without `healthcheck.token`
+fn listen(address: Option<&str>) -> i32 {
+    let Some(token) = config::get_string("healthcheck.token").filter(|v| !v.is_empty()) else {
+        eprintln!(
+            "healthcheck.token has to be set in {}",
+            config::path().to_string_lossy()
+        );
+        return 1;
+    };
+
+    let address = address
+        .map(String::from)
+        .or_else(|| config::get_string("healthcheck.address"))
+        .unwrap_or(DEFAULT_ADDRESS.into());
+
+    let address = match address.parse::<SocketAddr>() {
+        Ok(address) if address.ip().is_loopback() => address,
+        _ => {
+            eprintln!("{} is not a loopback address", address);
+            return 1;
+        }
+    };
+
+    let listener = match TcpListener::bind(address) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("failed to listen on {}: {}", address, err);
+            return 1;
+        }
+    };
+
+    println!("listening on http://{}", address);
+    info!("(healthcheck::listen) Listening on {}", address);
+    for stream in listener.incoming().flatten() {
+        if let Err(err) = serve_one(stream, &token) {
+            error!("(healthcheck::listen) Request failed, error: {}", err);
+        }
+    }
+
+    0
+}
+
+// prints every check and exits non-zero when one of them failed; with
+// `--listen [address]` the same checks are served over http instead
+pub(crate) fn healthcheck(arguments: &[String]) {
+    if arguments.first().map(String::as_str) == Some("--listen") {
+        std::process::exit(listen(arguments.get(1).map(String::as_str)));
+    }
+
+    let checks = run_checks();
+    for check in &checks {
+        println!(
+            "{} {}: {}",
+            if check["ok"] == true { "ok  " } else { "FAIL" },
+            check["check"].as_str().unwrap_or_default(),
+            check["detail"].as_str().unwrap_or_default()
+        );
+    }
+
+    if !is_healthy(&checks) {
+        std::process::exit(1);
+    }
+}
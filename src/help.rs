@@ -0,0 +1,142 @@
+use std::{process::Command, time::Duration};
+
+use serde_json::{json, Value};
+
+use crate::{
+    commands::{run_with_timeout, SUCCESS_CODE},
+    policy::{self, Refusal},
+};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// documentation commands get a hard timeout, since anything that ignores
+// `--help` might otherwise sit there forever
+fn capture(command: Command) -> Option<(bool, String)> {
+    let output = run_with_timeout(command, None, TIMEOUT).ok()?;
+
+    let mut bytes = output.stdout;
+    if bytes.is_empty() {
+        bytes = output.stderr;
+    }
+
+    Some((output.status.success(), strip_overstrike(&bytes)))
+}
+
+// man pages rendered for a terminal encode bold and underline as `c\bc`
+fn strip_overstrike(bytes: &[u8]) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    let mut result: Vec<char> = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        if c == '\u{8}' {
+            result.pop();
+        } else {
+            result.push(c);
+        }
+    }
+
+    result.into_iter().collect()
+}
+
+fn help_output(program: &str) -> Option<String> {
+    let mut command = Command::new(program);
+    command.arg("--help");
+
+    capture(command)
+        .filter(|(_, output)| !output.trim().is_empty())
+        .map(|(_, output)| output)
+}
+
+fn man_page(program: &str, section: Option<&str>) -> Option<String> {
+    let mut command = Command::new("man");
+    command
+        .env("MANPAGER", "cat")
+        .env("PAGER", "cat")
+        .env("MANWIDTH", "80")
+        .env_remove("MAN_KEEP_FORMATTING");
+
+    if let Some(section) = section {
+        command.arg(section);
+    }
+
+    command.arg(program);
+
+    capture(command)
+        .filter(|(success, output)| *success && !output.trim().is_empty())
+        .map(|(_, output)| output)
+}
+
+// `--help` runs the program itself, so it is up to the same policy as `exec`;
+// man pages are always there
+fn may_run(program: &str) -> Result<(), Refusal> {
+    if !policy::shell_enabled() {
+        let reason = if cfg!(feature = "shell") {
+            "is disabled by security.run.enabled"
+        } else {
+            "is not built in"
+        };
+
+        return Err(Refusal::Policy(format!(
+            "running {} --help {}",
+            program, reason
+        )));
+    }
+
+    policy::check_exec(program, &["--help".to_string()])
+}
+
+pub(crate) fn help_lookup(program: &str, source: Option<&str>, section: Option<&str>) -> Value {
+    // only bare program names, so this can never turn into running a path or
+    // a shell snippet handed over by the extension
+    let valid = !program.is_empty()
+        && program
+            .chars()
+            .all(|c| c.is_alphanumeric() || "-_.+".contains(c))
+        && !program.starts_with('-')
+        && section.is_none_or(|v| v.chars().all(|c| c.is_alphanumeric()));
+
+    if !valid {
+        error!("(help::help_lookup) Invalid program name: '{}'", program);
+        return json!({
+            "cmd": "help_lookup",
+            "code": 1
+        });
+    }
+
+    let result = match source {
+        Some("help") => match may_run(program) {
+            Ok(()) => help_output(program).map(|v| ("help", v)),
+            Err(refusal) => return policy::denied("help_lookup", refusal),
+        },
+        Some("man") => man_page(program, section).map(|v| ("man", v)),
+        _ => man_page(program, section).map(|v| ("man", v)).or_else(|| {
+            may_run(program)
+                .ok()
+                .and_then(|_| help_output(program))
+                .map(|v| ("help", v))
+        }),
+    };
+
+    match result {
+        Some((source, content)) => {
+            info!(
+                "(help::help_lookup) program: {}, source: {}",
+                program, source
+            );
+
+            json!({
+                "cmd": "help_lookup",
+                "code": SUCCESS_CODE,
+                "source": source,
+                "content": content
+            })
+        }
+
+        None => {
+            error!("(help::help_lookup) No documentation for: {}", program);
+            json!({
+                "cmd": "help_lookup",
+                "code": 2
+            })
+        }
+    }
+}
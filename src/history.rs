@@ -0,0 +1,129 @@
+use std::path::Path;
+
+use rusqlite::OpenFlags;
+use serde_json::{json, Value};
+
+use crate::{commands::SUCCESS_CODE, profiles};
+
+const DEFAULT_LIMIT: usize = 50;
+
+// the browser holds places.sqlite locked, so queries run against a copy
+// taken together with its write-ahead log
+const PLACES_FILES: [&str; 2] = ["places.sqlite", "places.sqlite-wal"];
+
+fn order(sort: &str) -> Option<&'static str> {
+    match sort {
+        "recent" => Some("last_visit_date"),
+        "visits" => Some("visit_count"),
+        "frecency" => Some("frecency"),
+        _ => None,
+    }
+}
+
+fn like_pattern(search: &str) -> String {
+    let escaped = search
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+
+    format!("%{}%", escaped)
+}
+
+fn query(profile: &Path, search: &str, order: &str, limit: usize) -> Result<Vec<Value>, String> {
+    let directory = tempfile::tempdir().map_err(|e| e.to_string())?;
+    for file in PLACES_FILES {
+        let source = profile.join(file);
+        if source.exists() {
+            std::fs::copy(&source, directory.path().join(file)).map_err(|e| e.to_string())?;
+        }
+    }
+
+    // opened writable so sqlite can fold the copied log back in; the
+    // profile's own database is never touched
+    let path = directory.path().join(PLACES_FILES[0]);
+    if !path.exists() {
+        return Err("places.sqlite not found".into());
+    }
+
+    let connection =
+        rusqlite::Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_WRITE)
+            .map_err(|e| e.to_string())?;
+
+    let sql = format!(
+        "SELECT url, title, visit_count, last_visit_date FROM moz_places \
+         WHERE hidden = 0 AND last_visit_date IS NOT NULL \
+         AND (url LIKE ?1 ESCAPE '\\' OR title LIKE ?1 ESCAPE '\\') \
+         ORDER BY {} DESC LIMIT ?2",
+        order
+    );
+
+    let mut statement = connection.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = statement
+        .query_map(
+            rusqlite::params![like_pattern(search), limit as i64],
+            |row| {
+                let visited: Option<i64> = row.get(3)?;
+                Ok(json!({
+                    "url": row.get::<_, String>(0)?,
+                    "title": row.get::<_, Option<String>>(1)?,
+                    "visits": row.get::<_, i64>(2)?,
+                    // places keeps microseconds, the extension wants milliseconds
+                    "last_visit": visited.map(|v| v / 1000)
+                }))
+            },
+        )
+        .map_err(|e| e.to_string())?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+pub(crate) fn history_query(
+    search: &str,
+    sort: Option<&str>,
+    limit: Option<usize>,
+    profile: Option<&str>,
+) -> Value {
+    let Some(order) = order(sort.unwrap_or("recent")) else {
+        return json!({
+            "cmd": "history_query",
+            "code": 1,
+            "error": "Unknown sort, expected recent, visits or frecency"
+        });
+    };
+
+    let Some(profile) = profiles::resolve_profile(profile) else {
+        error!("(history::history_query) Could not find a profile directory");
+        return json!({
+            "cmd": "history_query",
+            "code": 2,
+            "error": "No profile found"
+        });
+    };
+
+    match query(&profile, search, order, limit.unwrap_or(DEFAULT_LIMIT)) {
+        Ok(content) => {
+            info!(
+                "(history::history_query) search: {}, results: {}",
+                search,
+                content.len()
+            );
+
+            json!({
+                "cmd": "history_query",
+                "code": SUCCESS_CODE,
+                "content": content,
+                "profile": profile
+            })
+        }
+
+        Err(err) => {
+            error!("(history::history_query) Failed to query, error: {}", err);
+            json!({
+                "cmd": "history_query",
+                "code": 2,
+                "error": err
+            })
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io::Read,
+    process::Stdio,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    children,
+    commands::{io_failure, parse_signal, send_signal, termination_signal, Pid, SUCCESS_CODE},
+    config,
+};
+
+const DEFAULT_OUTPUT_LIMIT: usize = 64 * 1024;
+const DEFAULT_RETENTION: u64 = 10 * 60;
+
+#[derive(Default)]
+struct Output {
+    data: VecDeque<u8>,
+    dropped: usize,
+}
+
+impl Output {
+    // keeps the newest bytes, like a terminal scrollback
+    fn push(&mut self, bytes: &[u8], limit: usize) {
+        self.data.extend(bytes);
+        while self.data.len() > limit {
+            self.data.pop_front();
+            self.dropped += 1;
+        }
+    }
+
+    fn text(&self) -> String {
+        let (front, back) = self.data.as_slices();
+        String::from_utf8_lossy(&[front, back].concat()).to_string()
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Status {
+    Running,
+    Exited(Option<i32>, Option<i32>),
+}
+
+struct Job {
+    pid: u32,
+    command: String,
+    started: u64,
+    status: Status,
+    finished: Option<Instant>,
+    stdout: Output,
+    stderr: Output,
+}
+
+type Jobs = BTreeMap<u64, Arc<Mutex<Job>>>;
+
+static JOBS: Mutex<Jobs> = Mutex::new(BTreeMap::new());
+static NEXT_ID: Mutex<u64> = Mutex::new(1);
+
+fn output_limit() -> usize {
+    config::get_u64("jobs.output_limit")
+        .map(|v| v as usize)
+        .unwrap_or(DEFAULT_OUTPUT_LIMIT)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+// finished jobs are kept around for a while so their output can be collected
+fn prune(jobs: &mut Jobs) {
+    let retention =
+        Duration::from_secs(config::get_u64("jobs.retention").unwrap_or(DEFAULT_RETENTION));
+
+    jobs.retain(|_, job| {
+        let job = job.lock().unwrap();
+        job.finished.is_none_or(|v| v.elapsed() < retention)
+    });
+}
+
+fn collect<R: Read + Send + 'static>(
+    pipe: Option<R>,
+    job: Arc<Mutex<Job>>,
+    select: fn(&mut Job) -> &mut Output,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let Some(mut pipe) = pipe else { return };
+
+        let limit = output_limit();
+        let mut buffer = [0u8; 4096];
+        while let Ok(length) = pipe.read(&mut buffer) {
+            if length == 0 {
+                break;
+            }
+
+            select(&mut job.lock().unwrap()).push(&buffer[..length], limit);
+        }
+    })
+}
+
+// spawns a configured command as a tracked job, wiring its output into the
+// job buffers; returns the job id and pid
+pub(crate) fn spawn(
+    mut process: std::process::Command,
+    command: &str,
+) -> std::io::Result<(u64, u32)> {
+    process
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let mut child = process.spawn()?;
+    let pid = child.id();
+    children::register(pid, "run_async", command);
+
+    let id = {
+        let mut next = NEXT_ID.lock().unwrap();
+        *next += 1;
+        *next - 1
+    };
+
+    let job = Arc::new(Mutex::new(Job {
+        pid,
+        command: command.to_string(),
+        started: now(),
+        status: Status::Running,
+        finished: None,
+        stdout: Output::default(),
+        stderr: Output::default(),
+    }));
+
+    let stdout = collect(child.stdout.take(), job.clone(), |job| &mut job.stdout);
+    let stderr = collect(child.stderr.take(), job.clone(), |job| &mut job.stderr);
+
+    {
+        let mut jobs = JOBS.lock().unwrap();
+        prune(&mut jobs);
+        jobs.insert(id, job.clone());
+    }
+
+    // waiting here also reaps the child, so finished jobs never linger as zombies
+    std::thread::spawn(move || {
+        let status = child.wait().ok();
+        children::unregister(pid);
+
+        let _ = stdout.join();
+        let _ = stderr.join();
+
+        let exit_code = status.and_then(|v| v.code());
+        let signal = status.as_ref().and_then(termination_signal);
+
+        let mut job = job.lock().unwrap();
+        job.status = Status::Exited(exit_code, signal);
+        job.finished = Some(Instant::now());
+
+        info!(
+            "(jobs::spawn) Job {} ('{}') finished, code: {:?}, signal: {:?}",
+            id, job.command, exit_code, signal
+        );
+    });
+
+    Ok((id, pid))
+}
+
+fn describe(id: u64, job: &Job) -> Value {
+    let (state, exit_code, signal) = match job.status {
+        Status::Running => ("running", None, None),
+        Status::Exited(code, signal) => ("exited", code, signal),
+    };
+
+    json!({
+        "job": id,
+        "pid": job.pid,
+        "command": job.command,
+        "started": job.started,
+        "state": state,
+        "exit_code": exit_code,
+        "signal": signal
+    })
+}
+
+fn get(id: u64) -> Option<Arc<Mutex<Job>>> {
+    let mut jobs = JOBS.lock().unwrap();
+    prune(&mut jobs);
+    jobs.get(&id).cloned()
+}
+
+fn not_found(name: &str, id: u64) -> Value {
+    error!("(jobs::{}) No such job: {}", name, id);
+    io_failure(
+        name,
+        1,
+        &std::io::Error::new(std::io::ErrorKind::NotFound, format!("No such job: {}", id)),
+    )
+}
+
+pub(crate) fn jobs_list() -> Value {
+    let mut jobs = JOBS.lock().unwrap();
+    prune(&mut jobs);
+
+    let content = jobs
+        .iter()
+        .map(|(id, job)| describe(*id, &job.lock().unwrap()))
+        .collect::<Vec<_>>();
+
+    json!({
+        "cmd": "jobs_list",
+        "code": SUCCESS_CODE,
+        "content": content
+    })
+}
+
+pub(crate) fn job_status(id: u64) -> Value {
+    let Some(job) = get(id) else {
+        return not_found("job_status", id);
+    };
+
+    let mut response = describe(id, &job.lock().unwrap());
+    response["cmd"] = json!("job_status");
+    response["code"] = json!(SUCCESS_CODE);
+    response
+}
+
+pub(crate) fn job_output(id: u64) -> Value {
+    let Some(job) = get(id) else {
+        return not_found("job_output", id);
+    };
+
+    let job = job.lock().unwrap();
+    json!({
+        "cmd": "job_output",
+        "code": SUCCESS_CODE,
+        "job": id,
+        "stdout": job.stdout.text(),
+        "stderr": job.stderr.text(),
+        "stdout_dropped": job.stdout.dropped,
+        "stderr_dropped": job.stderr.dropped
+    })
+}
+
+pub(crate) fn job_kill(id: u64, signal: &str) -> Value {
+    let Some(job) = get(id) else {
+        return not_found("job_kill", id);
+    };
+
+    let Some(signal) = parse_signal(signal) else {
+        error!("(jobs::job_kill) Unknown signal: {}", signal);
+        return io_failure(
+            "job_kill",
+            1,
+            &std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Unknown signal {}", signal),
+            ),
+        );
+    };
+
+    let job = job.lock().unwrap();
+    if !matches!(job.status, Status::Running) {
+        return json!({
+            "cmd": "job_kill",
+            "code": 1,
+            "error": "Job already finished"
+        });
+    }
+
+    let Some(pid) = Pid::new(job.pid.into()) else {
+        return io_failure(
+            "job_kill",
+            1,
+            &std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Refusing to signal pid {}", job.pid),
+            ),
+        );
+    };
+
+    let code = match send_signal(pid, signal) {
+        Ok(_) => {
+            info!("(jobs::job_kill) job: {}, signal: {}", id, signal);
+            SUCCESS_CODE
+        }
+
+        Err(err) => {
+            error!("(jobs::job_kill) job: {}, error: {}", id, err);
+            2
+        }
+    };
+
+    json!({
+        "cmd": "job_kill",
+        "code": code
+    })
+}
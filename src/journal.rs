@@ -0,0 +1,213 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    commands::{sync_directory, write_atomic},
+    logging,
+};
+
+// operations that touch more than one file write down what they are about to
+// do first; whatever is still here at startup was cut short by a crash or
+// power loss and is carried through to the end
+fn journal_directory() -> PathBuf {
+    logging::data_directory().join("journal")
+}
+
+static NEXT: AtomicU64 = AtomicU64::new(0);
+
+pub(crate) struct Record {
+    path: PathBuf,
+}
+
+impl Record {
+    // the operation went through (or was rolled back by hand), so there is
+    // nothing left to recover
+    pub(crate) fn commit(self) {
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            error!(
+                "(journal::commit) Failed to remove {}, error: {}",
+                self.path.to_string_lossy(),
+                err
+            );
+        }
+    }
+}
+
+// the record is on disk before this returns, so the operation may start
+pub(crate) fn begin(kind: &str, operation: Value) -> std::io::Result<Record> {
+    let directory = journal_directory();
+    std::fs::create_dir_all(&directory)?;
+
+    let name = format!(
+        "{}-{}-{}.json",
+        kind,
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    );
+
+    let path = directory.join(name);
+    let record = json!({
+        "kind": kind,
+        "pid": std::process::id(),
+        "operation": operation
+    });
+    write_atomic(&path, record.to_string().as_bytes(), Some(0o600))?;
+
+    Ok(Record { path })
+}
+
+// `files` is a list of `{ target, staged, backup }`; a staged file that is
+// still there had not been renamed over its target yet
+fn finish_write_many(operation: &Value) -> std::io::Result<usize> {
+    let files = operation
+        .get("files")
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default();
+
+    let mut finished = 0;
+    for file in &files {
+        let path = |key: &str| file.get(key).and_then(|v| v.as_str()).map(PathBuf::from);
+        let (Some(target), Some(staged)) = (path("target"), path("staged")) else {
+            continue;
+        };
+
+        if staged.exists() {
+            std::fs::rename(&staged, &target)?;
+            finished += 1;
+        }
+
+        if let Some(backup) = path("backup") {
+            match std::fs::remove_file(backup) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+    }
+
+    Ok(finished)
+}
+
+// appends `content` to `file` unless it already ends with it, then removes
+// `remove`, if given
+fn finish_append(operation: &Value) -> std::io::Result<usize> {
+    let text = |key: &str| operation.get(key).and_then(|v| v.as_str());
+    let (Some(file), Some(content)) = (text("file"), text("content")) else {
+        return Ok(0);
+    };
+
+    let existing = std::fs::read(file).unwrap_or_default();
+    let mut finished = 0;
+    if !existing.ends_with(content.as_bytes()) {
+        let mut handle = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(file)?;
+
+        // a torn line from the crash is ended first so it does not swallow
+        // the entry
+        if !existing.is_empty() && !existing.ends_with(b"\n") {
+            handle.write_all(b"\n")?;
+        }
+
+        handle.write_all(content.as_bytes())?;
+        handle.sync_all()?;
+        finished += 1;
+    }
+
+    if let Some(remove) = text("remove") {
+        match std::fs::remove_file(remove) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+    }
+
+    Ok(finished)
+}
+
+// another browser window has a host of its own, whose records are not ours to
+// finish while it runs
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    let Some(pid) = crate::commands::Pid::new(pid.into()) else {
+        return false;
+    };
+
+    match crate::commands::send_signal(pid, 0) {
+        Ok(()) => true,
+        Err(err) => err.raw_os_error() == Some(libc::EPERM),
+    }
+}
+
+#[cfg(windows)]
+fn is_running(pid: u32) -> bool {
+    std::process::Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+        .output()
+        .is_ok_and(|v| String::from_utf8_lossy(&v.stdout).contains(&pid.to_string()))
+}
+
+// None when the record belongs to a host that is still running
+fn replay(path: &Path) -> Result<Option<usize>, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let record: Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+    let operation = record.get("operation").cloned().unwrap_or_default();
+
+    let pid = record.get("pid").and_then(|v| v.as_u64()).map(|v| v as u32);
+    if pid.is_some_and(|v| v != std::process::id() && is_running(v)) {
+        return Ok(None);
+    }
+
+    let finished = match record.get("kind").and_then(|v| v.as_str()) {
+        Some("write_many") => finish_write_many(&operation),
+        Some("append") => finish_append(&operation),
+        kind => return Err(format!("unknown kind: {:?}", kind)),
+    };
+
+    finished.map(Some).map_err(|e| e.to_string())
+}
+
+// runs at startup, before anything could start new operations
+pub(crate) fn recover() {
+    let Ok(entries) = std::fs::read_dir(journal_directory()) else {
+        return;
+    };
+
+    let records = entries
+        .flatten()
+        .map(|v| v.path())
+        .filter(|v| v.extension().is_some_and(|v| v == "json"));
+
+    for path in records {
+        match replay(&path) {
+            Ok(None) => {}
+            Ok(Some(finished)) => {
+                info!(
+                    "(journal::recover) Recovered {}, {} steps finished",
+                    path.to_string_lossy(),
+                    finished
+                );
+
+                let _ = std::fs::remove_file(&path);
+            }
+
+            // kept for a look by hand rather than dropped
+            Err(err) => {
+                error!(
+                    "(journal::recover) Failed to recover {}, error: {}",
+                    path.to_string_lossy(),
+                    err
+                );
+
+                let _ = std::fs::rename(&path, path.with_extension("failed"));
+            }
+        }
+    }
+
+    let _ = sync_directory(&journal_directory());
+}
@@ -0,0 +1,176 @@
+use std::{path::PathBuf, str::FromStr};
+
+use age::{secrecy::ExposeSecret, x25519::Identity};
+
+use crate::{
+    commands::write_atomic,
+    config, logging,
+    secret::{read_secret, store_secret, Attributes},
+};
+
+const EXTENSION: &str = "key";
+
+// the names of the keys in the keyring, which cannot be listed everywhere
+const INDEX: &str = "keys";
+
+const SERVICE: &str = "tridactyl-native";
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn key_path(name: &str) -> PathBuf {
+    logging::data_directory().join(format!("{}.{}", name, EXTENSION))
+}
+
+// keys live in the platform keyring unless `keys.store` is "file", which
+// keeps them as plaintext files for machines without one
+fn in_files() -> bool {
+    config::get_string("keys.store").as_deref() == Some("file")
+}
+
+#[cfg(not(windows))]
+fn attributes(name: &str) -> Attributes {
+    Attributes::from([
+        ("service".to_string(), SERVICE.to_string()),
+        ("account".to_string(), name.to_string()),
+    ])
+}
+
+// credential manager only finds entries by target name
+#[cfg(windows)]
+fn attributes(name: &str) -> Attributes {
+    Attributes::from([("target".to_string(), format!("{}:{}", SERVICE, name))])
+}
+
+fn names() -> Vec<String> {
+    std::fs::read_to_string(logging::data_directory().join(INDEX))
+        .unwrap_or_default()
+        .lines()
+        .filter(|v| is_valid_name(v))
+        .map(String::from)
+        .collect()
+}
+
+fn add_name(name: &str) -> Result<(), String> {
+    let mut names = names();
+    if names.iter().any(|v| v == name) {
+        return Ok(());
+    }
+
+    names.push(name.to_string());
+    std::fs::create_dir_all(logging::data_directory()).map_err(|e| e.to_string())?;
+    write_atomic(
+        &logging::data_directory().join(INDEX),
+        format!("{}\n", names.join("\n")).as_bytes(),
+        Some(0o600),
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn store(name: &str, identity: &Identity) -> Result<(), String> {
+    let secret = identity.to_string();
+    if in_files() {
+        std::fs::create_dir_all(logging::data_directory()).map_err(|e| e.to_string())?;
+        return write_atomic(
+            &key_path(name),
+            secret.expose_secret().as_bytes(),
+            Some(0o600),
+        )
+        .map_err(|e| e.to_string());
+    }
+
+    store_secret(
+        &attributes(name),
+        &format!("{} age key {}", SERVICE, name),
+        secret.expose_secret(),
+    )?;
+    add_name(name)
+}
+
+fn load(name: &str) -> Result<Option<Identity>, String> {
+    let content = if in_files() {
+        std::fs::read_to_string(key_path(name)).ok()
+    } else {
+        read_secret(&attributes(name))?
+    };
+
+    content
+        .map(|v| Identity::from_str(v.trim()).map_err(|e| e.to_string()))
+        .transpose()
+}
+
+// a key file from before keys went to the keyring is moved there
+fn migrate(name: &str) -> Result<Option<Identity>, String> {
+    let path = key_path(name);
+    let Ok(content) = std::fs::read_to_string(&path) else {
+        return Ok(None);
+    };
+
+    let identity = Identity::from_str(content.trim()).map_err(|e| e.to_string())?;
+    store(name, &identity)?;
+    std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+
+    info!(
+        "(keys::migrate) Moved {} into the keyring",
+        path.to_string_lossy()
+    );
+
+    Ok(Some(identity))
+}
+
+// the age identity called `name`, generated on first use
+pub(crate) fn identity(name: &str) -> Result<Identity, String> {
+    if !is_valid_name(name) {
+        return Err(format!("Invalid key name: '{}'", name));
+    }
+
+    if let Some(identity) = load(name)? {
+        return Ok(identity);
+    }
+
+    if !in_files() {
+        if let Some(identity) = migrate(name)? {
+            return Ok(identity);
+        }
+    }
+
+    let identity = Identity::generate();
+    store(name, &identity)?;
+
+    info!("(keys::identity) Generated a new key: {}", name);
+    Ok(identity)
+}
+
+// every identity the host has, so files can be decrypted without naming the
+// key they were made for
+pub(crate) fn identities() -> Vec<Identity> {
+    let mut names = names();
+    if let Ok(entries) = logging::data_directory().read_dir() {
+        names.extend(
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|v| v == EXTENSION))
+                .filter_map(|path| Some(path.file_stem()?.to_string_lossy().to_string())),
+        );
+    }
+
+    names.sort();
+    names.dedup();
+    names
+        .iter()
+        .filter_map(|name| identity_if_present(name))
+        .collect()
+}
+
+fn identity_if_present(name: &str) -> Option<Identity> {
+    match load(name) {
+        Ok(Some(identity)) => Some(identity),
+        _ if !in_files() => migrate(name).ok().flatten(),
+        _ => None,
+    }
+}
@@ -0,0 +1,118 @@
+use std::{sync::Mutex, time::Instant};
+
+use serde_json::{json, Value};
+
+use crate::config;
+
+// the `error` of a request refused for coming too fast, or a reply dropped
+// for being too large
+pub(crate) const THROTTLED: &str = "throttled";
+pub(crate) const REPLY_TOO_LARGE: &str = "reply_too_large";
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    // throttled requests since the last one let through, so a loop is
+    // logged once rather than for every request
+    refused: u64,
+}
+
+static BUCKET: Mutex<Option<Bucket>> = Mutex::new(None);
+
+// the invalid settings already logged, so a bad value is reported once
+// rather than on every request
+static REPORTED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+// a limit that is not a positive integer is left out rather than read as a
+// huge or zero one
+fn setting(key: &str) -> Option<u64> {
+    let value = config::get(key)?;
+    if let Some(value) = value.as_u64().filter(|v| *v > 0) {
+        return Some(value);
+    }
+
+    let setting = format!("{}: {}", key, value);
+    let mut reported = REPORTED.lock().unwrap();
+    if !reported.contains(&setting) {
+        error!(
+            "(limits::setting) {} must be a positive integer, ignoring {}",
+            key, value
+        );
+        reported.push(setting);
+    }
+
+    None
+}
+
+// a token bucket refilled at `limits.rate` requests per second, holding up to
+// `limits.burst` of them; without a rate nothing is limited
+pub(crate) fn admit(command: &Value) -> Option<Value> {
+    let rate = setting("limits.rate")? as f64;
+    let burst = setting("limits.burst").map_or(rate, |v| v as f64);
+
+    let now = Instant::now();
+    let mut bucket = BUCKET.lock().unwrap();
+    let bucket = bucket.get_or_insert(Bucket {
+        tokens: burst,
+        updated: now,
+        refused: 0,
+    });
+
+    let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+    bucket.tokens = (bucket.tokens + elapsed * rate).min(burst);
+    bucket.updated = now;
+
+    if bucket.tokens >= 1.0 {
+        bucket.tokens -= 1.0;
+        if bucket.refused > 0 {
+            info!("(limits::admit) Throttled {} requests", bucket.refused);
+            bucket.refused = 0;
+        }
+
+        return None;
+    }
+
+    if bucket.refused == 0 {
+        error!(
+            "(limits::admit) More than {} requests per second, throttling",
+            rate
+        );
+    }
+
+    bucket.refused += 1;
+    let retry_after = ((1.0 - bucket.tokens) / rate * 1000.0).ceil() as u64;
+
+    Some(json!({
+        "cmd": command.get("cmd").and_then(|v| v.as_str()).unwrap_or("error"),
+        "code": 1,
+        "error": THROTTLED,
+        "retry_after": retry_after
+    }))
+}
+
+// a reply over `limits.max_reply` bytes is replaced by an error telling how
+// large it would have been
+pub(crate) fn cap_reply(reply: Value) -> Value {
+    let Some(limit) = setting("limits.max_reply") else {
+        return reply;
+    };
+
+    let size = reply.to_string().len() as u64;
+    if size <= limit {
+        return reply;
+    }
+
+    let name = reply.get("cmd").and_then(|v| v.as_str()).unwrap_or("error");
+    error!(
+        "(limits::cap_reply) Dropped a {} byte reply to {}, over the {} byte limit",
+        size, name, limit
+    );
+
+    json!({
+        "cmd": name,
+        "code": 1,
+        "error": REPLY_TOO_LARGE,
+        "size": size,
+        "limit": limit
+    })
+}
@@ -0,0 +1,246 @@
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+use std::{fs::File, path::PathBuf};
+
+#[cfg(unix)]
+use log::{Level, Metadata, Record};
+use log::{LevelFilter, Log};
+use serde_json::{json, Value};
+use simplelog::{Config, WriteLogger};
+
+use crate::{
+    commands::{resolve_path, NAME, SUCCESS_CODE},
+    config,
+};
+
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
+const LEVEL_VARIABLE: &str = "TRIDACTYL_NATIVE_LOG";
+
+// `Err` when there is no home to find it in
+pub(crate) fn try_data_directory() -> std::io::Result<PathBuf> {
+    dirs::data_dir().map(|v| v.join(NAME)).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "cannot find the data directory",
+        )
+    })
+}
+
+// without a home the host still answers, keeping its files in the temporary
+// directory
+pub(crate) fn data_directory() -> PathBuf {
+    try_data_directory().unwrap_or_else(|_| std::env::temp_dir().join(NAME))
+}
+
+// the log is state rather than data, so it goes under `$XDG_STATE_HOME` where
+// the platform has one, unless `log.dir` says otherwise
+fn configured_log_directory() -> Option<PathBuf> {
+    config::get_string("log.dir")
+        .map(|v| resolve_path(&v))
+        .or_else(|| Some(dirs::state_dir()?.join(NAME)))
+}
+
+pub(crate) fn log_directory() -> PathBuf {
+    configured_log_directory().unwrap_or_else(data_directory)
+}
+
+fn log_file_name() -> String {
+    format!("{}.log", NAME)
+}
+
+pub(crate) fn log_file() -> PathBuf {
+    log_directory().join(log_file_name())
+}
+
+// `--log-level` wins over the environment, which wins over `log.level`
+fn configured_level(flag: Option<&str>) -> LevelFilter {
+    flag.map(String::from)
+        .or_else(|| std::env::var(LEVEL_VARIABLE).ok())
+        .or_else(|| config::get_string("log.level"))
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_LEVEL)
+}
+
+// syslog and the journal both take a datagram per line
+#[cfg(unix)]
+const SYSLOG_SOCKET: &str = "/dev/log";
+#[cfg(unix)]
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+#[cfg(unix)]
+struct SocketLogger {
+    socket: UnixDatagram,
+    journal: bool,
+}
+
+#[cfg(unix)]
+fn severity(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+// rfc 3164 with the user facility
+#[cfg(unix)]
+fn syslog_line(level: Level, message: &str) -> Vec<u8> {
+    format!(
+        "<{}>{}[{}]: {}",
+        8 + severity(level),
+        NAME,
+        std::process::id(),
+        message
+    )
+    .into_bytes()
+}
+
+// the journal's native protocol; the message may hold newlines, so it is
+// sent length-prefixed
+#[cfg(unix)]
+fn journal_entry(level: Level, message: &str) -> Vec<u8> {
+    let mut entry = format!(
+        "PRIORITY={}\nSYSLOG_IDENTIFIER={}\nSYSLOG_PID={}\nMESSAGE\n",
+        severity(level),
+        NAME,
+        std::process::id()
+    )
+    .into_bytes();
+
+    entry.extend((message.len() as u64).to_le_bytes());
+    entry.extend(message.as_bytes());
+    entry.push(b'\n');
+    entry
+}
+
+#[cfg(unix)]
+impl Log for SocketLogger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let message = record.args().to_string();
+        let datagram = if self.journal {
+            journal_entry(record.level(), &message)
+        } else {
+            syslog_line(record.level(), &message)
+        };
+
+        let _ = self.socket.send(&datagram);
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(unix)]
+fn socket_logger(journal: bool) -> std::io::Result<Box<dyn Log>> {
+    let socket = UnixDatagram::unbound()?;
+    socket.connect(if journal {
+        JOURNAL_SOCKET
+    } else {
+        SYSLOG_SOCKET
+    })?;
+
+    Ok(Box::new(SocketLogger { socket, journal }))
+}
+
+#[cfg(not(unix))]
+fn socket_logger(_journal: bool) -> std::io::Result<Box<dyn Log>> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+fn file_logger() -> std::io::Result<Box<dyn Log>> {
+    let directory = match configured_log_directory() {
+        Some(directory) => directory,
+        None => try_data_directory()?,
+    };
+
+    std::fs::create_dir_all(&directory)?;
+
+    let log_file = File::options()
+        .append(true)
+        .create(true)
+        .open(directory.join(log_file_name()))?;
+
+    Ok(WriteLogger::new(
+        LevelFilter::Trace,
+        Config::default(),
+        log_file,
+    ))
+}
+
+fn stderr_logger() -> Box<dyn Log> {
+    WriteLogger::new(LevelFilter::Trace, Config::default(), std::io::stderr())
+}
+
+// `log.sink` sends the log to stderr (stdout carries the protocol), syslog
+// or the journal instead of the file; a sink that cannot be opened falls
+// back to the file, and the file to stderr
+pub(crate) fn init(level: Option<&str>) {
+    let created = try_data_directory().and_then(std::fs::create_dir_all);
+
+    let sink = config::get_string("log.sink").unwrap_or("file".into());
+    let opened = match sink.as_str() {
+        "file" => file_logger(),
+        "stderr" => Ok(stderr_logger()),
+        "syslog" | "journald" => socket_logger(sink == "journald"),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "unknown sink",
+        )),
+    };
+
+    let (logger, problem) = match opened {
+        Ok(logger) => (logger, None),
+        Err(err) if sink == "file" => (stderr_logger(), Some(("stderr", err))),
+        Err(err) => match file_logger() {
+            Ok(logger) => (logger, Some(("the file", err))),
+            Err(_) => (stderr_logger(), Some(("stderr", err))),
+        },
+    };
+
+    // the loggers themselves let everything through, so that the max level
+    // alone decides and can be changed at runtime
+    log::set_logger(Box::leak(logger)).unwrap();
+    log::set_max_level(configured_level(level));
+
+    if let Some((fallback, problem)) = problem {
+        error!(
+            "(logging::init) Cannot log to {}, using {} instead: {}",
+            sink, fallback, problem
+        );
+    }
+
+    if let Err(err) = created {
+        error!(
+            "(logging::init) Cannot create the data directory, error: {}",
+            err
+        );
+    }
+}
+
+pub(crate) fn set_loglevel(level: &str) -> Value {
+    let Ok(level) = level.trim().parse::<LevelFilter>() else {
+        return json!({
+            "cmd": "set_loglevel",
+            "code": 1,
+            "error": "Expected one of off, error, warn, info, debug or trace"
+        });
+    };
+
+    let previous = log::max_level();
+    log::set_max_level(level);
+    info!(
+        "(logging::set_loglevel) Changed the log level from {} to {}",
+        previous, level
+    );
+
+    json!({
+        "cmd": "set_loglevel",
+        "code": SUCCESS_CODE,
+        "level": level.to_string().to_lowercase(),
+        "previous": previous.to_string().to_lowercase()
+    })
+}
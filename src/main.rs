@@ -2,27 +2,106 @@
 extern crate log;
 extern crate simplelog;
 
+pub mod audit;
+pub mod capabilities;
+pub mod children;
+pub mod clipboard;
+pub mod cliphist;
+pub mod color;
 pub mod commands;
-
-use dirs;
-use std::{
-    fs::File,
-    io::{Read, Stdin, Stdout, Write},
-};
+pub mod config;
+pub mod confirm;
+pub mod crypto;
+pub mod doctor;
+pub mod editor;
+pub mod events;
+pub mod focus;
+pub mod hash;
+pub mod healthcheck;
+pub mod help;
+pub mod history;
+pub mod jobs;
+pub mod journal;
+pub mod keys;
+pub mod limits;
+pub mod logging;
+pub mod maintenance;
+pub mod manifest;
+pub mod metadata;
+pub mod migrations;
+pub mod mimetype;
+pub mod net;
+pub mod notes;
+pub mod picker;
+pub mod pipelines;
+pub mod plugins;
+pub mod policy;
+pub mod prefs;
+pub mod profiles;
+pub mod protocol;
+pub mod pty;
+pub mod rc;
+pub mod registry;
+pub mod restart;
+pub mod rewrite;
+pub mod routes;
+pub mod scan;
+pub mod secret;
+pub mod session;
+pub mod shell_history;
+pub mod shims;
+pub mod sqlite;
+pub mod sysinfo;
+pub mod timers;
+pub mod timetrack;
+pub mod zoxide;
+
+use std::time::Instant;
 
 use serde_json::{json, Value};
-use simplelog::{Config, LevelFilter, WriteLogger};
-
-const NATIVE_MESSAGE_HOST: &str = "tridactyl.json";
-const BROWSERS: [&str; 2] = [".mozilla", ".librewolf"];
 
 fn handle_command(command: &Value) -> Value {
+    let start = Instant::now();
+    let mut response = dispatch(command);
+    audit::record(command, &response, start.elapsed());
+
+    // every failure says what went wrong, if only in general terms; the code
+    // of a finished program is its exit status, not a failure of ours
+    let failed = response.get("exit_code").is_none()
+        && response
+            .get("code")
+            .and_then(|v| v.as_u64())
+            .is_some_and(|v| v != u64::from(commands::SUCCESS_CODE));
+    if failed && response.get("error").is_none() {
+        response["error"] = "Failed".into();
+    }
+
+    response
+}
+
+fn dispatch(command: &Value) -> Value {
     let error = json!({
         "cmd": "error",
         "code": 1,
         "error": "Unhandled message"
     });
 
+    if let Value::Object(map) = command {
+        if registry::is_strict() {
+            if let Some(error) = registry::validate(map) {
+                return error;
+            }
+        }
+
+        if let Some(denied) = policy::check_shell_enabled(map)
+            .or_else(|| policy::check_readonly(map))
+            .or_else(|| policy::check_paths(map))
+            .or_else(|| policy::confirm_overwrite(map))
+        {
+            return denied;
+        }
+    }
+
     // TODO: kill this nest
     let response = match command {
         Value::Object(map) => match map.get("cmd") {
@@ -45,14 +124,96 @@ fn handle_command(command: &Value) -> Value {
                         commands::env(key)
                     }
 
+                    "which" => {
+                        let program = map.get("name").and_then(|v| v.as_str()).unwrap_or_default();
+                        commands::which(program)
+                    }
+
+                    "net_check" => {
+                        let host = map.get("host").and_then(|v| v.as_str());
+                        let port = map.get("port").and_then(|v| v.as_u64());
+                        let url = map.get("url").and_then(|v| v.as_str());
+                        let timeout = map.get("timeout").and_then(|v| v.as_u64());
+                        net::net_check(host, port, url, timeout)
+                    }
+
+                    "capabilities" => {
+                        let refresh = map
+                            .get("refresh")
+                            .and_then(|v| v.as_bool())
+                            .unwrap_or(false);
+                        capabilities::capabilities(refresh)
+                    }
+
+                    "hello" => {
+                        let strict = map.get("strict").and_then(|v| v.as_bool()).unwrap_or(false);
+                        let protocol = map.get("protocol").and_then(|v| v.as_u64());
+
+                        registry::hello(strict, protocol)
+                    }
+
                     "version" => commands::version(),
+                    "sysinfo" => sysinfo::sysinfo(),
+                    "set_loglevel" => {
+                        let level = map
+                            .get("level")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default();
+                        logging::set_loglevel(level)
+                    }
 
-                    "getconfig" => commands::get_config(),
-                    "getconfigpath" => commands::get_config_path(),
+                    "audit_tail" => {
+                        let lines = map.get("lines").and_then(|v| v.as_u64());
+                        audit::audit_tail(lines)
+                    }
+
+                    "getconfig" => {
+                        let profile = map.get("profile").and_then(|v| v.as_str());
+                        commands::get_config(profile)
+                    }
+
+                    "getconfigpath" => {
+                        let profile = map.get("profile").and_then(|v| v.as_str());
+                        let resolve = map
+                            .get("resolve")
+                            .and_then(|v| v.as_bool())
+                            .unwrap_or(false);
+                        commands::get_config_path(profile, resolve)
+                    }
 
                     "read" => {
                         let path = map.get("file").and_then(|v| v.as_str()).unwrap_or_default();
-                        commands::read(path)
+                        let encoding = map.get("encoding").and_then(|v| v.as_str());
+                        commands::read(path, encoding)
+                    }
+
+                    "read_many" => {
+                        let paths = map
+                            .get("files")
+                            .and_then(|v| v.as_array())
+                            .map(|v| {
+                                v.iter()
+                                    .filter_map(|v| v.as_str().map(String::from))
+                                    .collect::<Vec<_>>()
+                            })
+                            .unwrap_or_default();
+
+                        let pattern = map.get("glob").and_then(|v| v.as_str());
+                        let encoding = map.get("encoding").and_then(|v| v.as_str());
+
+                        commands::read_many(&paths, pattern, encoding)
+                    }
+
+                    "read_table" => {
+                        let path = map.get("file").and_then(|v| v.as_str()).unwrap_or_default();
+                        let delimiter = map.get("delimiter").and_then(|v| v.as_str());
+                        let header = map.get("header").and_then(|v| v.as_bool()).unwrap_or(true);
+                        let limit = map
+                            .get("limit")
+                            .and_then(|v| v.as_u64())
+                            .map(|v| v as usize);
+
+                        commands::read_table(path, delimiter, header, limit)
                     }
 
                     "write" => {
@@ -65,6 +226,16 @@ fn handle_command(command: &Value) -> Value {
                         commands::write(path, content)
                     }
 
+                    "write_many" => {
+                        let files = map
+                            .get("files")
+                            .and_then(|v| v.as_object())
+                            .cloned()
+                            .unwrap_or_default();
+
+                        commands::write_many(&files)
+                    }
+
                     "writerc" => {
                         let path = map.get("file").and_then(|v| v.as_str()).unwrap_or_default();
                         let force = map.get("force").and_then(|v| v.as_bool()).unwrap_or(false);
@@ -90,7 +261,50 @@ fn handle_command(command: &Value) -> Value {
                             .and_then(|v| v.as_bool())
                             .unwrap_or(false);
 
-                        commands::move_file(from, to, overwrite, cleanup)
+                        let scan = map.get("scan").and_then(|v| v.as_bool());
+
+                        commands::move_file(from, to, overwrite, cleanup, scan)
+                    }
+
+                    "copy" => {
+                        let from = map.get("from").and_then(|v| v.as_str()).unwrap_or_default();
+                        let to = map.get("to").and_then(|v| v.as_str()).unwrap_or_default();
+                        let overwrite = map
+                            .get("overwrite")
+                            .and_then(|v| v.as_bool())
+                            .unwrap_or(false);
+
+                        let resume = map.get("resume").and_then(|v| v.as_bool()).unwrap_or(false);
+                        let xattrs = map.get("xattrs").and_then(|v| v.as_bool());
+
+                        commands::copy_file(from, to, overwrite, resume, xattrs)
+                    }
+
+                    "mimetype" => {
+                        let file = map.get("file").and_then(|v| v.as_str()).unwrap_or_default();
+                        mimetype::mimetype(file)
+                    }
+
+                    "hash_many" => {
+                        let paths = map
+                            .get("files")
+                            .and_then(|v| v.as_array())
+                            .map(|v| {
+                                v.iter()
+                                    .filter_map(|v| v.as_str().map(String::from))
+                                    .collect::<Vec<_>>()
+                            })
+                            .unwrap_or_default();
+
+                        let directory = map.get("dir").and_then(|v| v.as_str());
+                        let recursive = map
+                            .get("recursive")
+                            .and_then(|v| v.as_bool())
+                            .unwrap_or(false);
+
+                        let algorithm = map.get("algorithm").and_then(|v| v.as_str());
+
+                        hash::hash_many(&paths, directory, recursive, algorithm)
                     }
 
                     "mkdir" => {
@@ -100,7 +314,13 @@ fn handle_command(command: &Value) -> Value {
 
                     "list_dir" => {
                         let path = map.get("path").and_then(|v| v.as_str()).unwrap_or_default();
-                        commands::read_directory(path)
+                        let sort = map.get("sort").and_then(|v| v.as_str());
+                        let show_hidden = map
+                            .get("show_hidden")
+                            .and_then(|v| v.as_bool())
+                            .unwrap_or(true);
+
+                        commands::read_directory(path, sort, show_hidden)
                     }
 
                     "temp" => {
@@ -114,11 +334,27 @@ fn handle_command(command: &Value) -> Value {
                             .and_then(|v| v.as_str())
                             .unwrap_or_default();
 
-                        if let Some(result) = commands::temp(prefix, content) {
-                            result
-                        } else {
-                            error
-                        }
+                        let dir = map.get("dir").and_then(|v| v.as_str());
+
+                        commands::temp(prefix, content, dir)
+                    }
+
+                    "editor" => {
+                        let content = map
+                            .get("content")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default();
+
+                        let prefix = map
+                            .get("prefix")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default();
+
+                        let extension = map.get("extension").and_then(|v| v.as_str());
+                        let line = map.get("line").and_then(|v| v.as_u64());
+                        let column = map.get("column").and_then(|v| v.as_u64());
+
+                        editor::editor_command(content, prefix, extension, line, column)
                     }
 
                     "run" => {
@@ -133,7 +369,10 @@ fn handle_command(command: &Value) -> Value {
                             None
                         };
 
-                        commands::run(command, content)
+                        let cwd = map.get("cwd").and_then(|v| v.as_str());
+                        let limits = commands::Limits::from_message(map);
+
+                        commands::run(command, content, cwd, limits)
                     }
 
                     "run_async" => {
@@ -142,10 +381,434 @@ fn handle_command(command: &Value) -> Value {
                             .and_then(|v| v.as_str())
                             .unwrap_or_default();
 
-                        commands::run_async(command)
+                        let cwd = map.get("cwd").and_then(|v| v.as_str());
+                        let mode = map.get("mode").and_then(|v| v.as_str());
+                        let detach = map.get("detach").and_then(|v| v.as_bool()).unwrap_or(false);
+
+                        commands::run_async(command, cwd, mode, detach)
+                    }
+
+                    "sqlite_query" => {
+                        let database = map.get("db").and_then(|v| v.as_str()).unwrap_or_default();
+                        let query = map
+                            .get("query")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default();
+
+                        let params = map
+                            .get("params")
+                            .and_then(|v| v.as_array())
+                            .cloned()
+                            .unwrap_or_default();
+
+                        let write = map.get("write").and_then(|v| v.as_bool()).unwrap_or(false);
+                        let limit = map
+                            .get("limit")
+                            .and_then(|v| v.as_u64())
+                            .map(|v| v as usize);
+
+                        sqlite::sqlite_query(database, query, &params, write, limit)
+                    }
+
+                    "zoxide_query" => {
+                        let query = map
+                            .get("query")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default();
+
+                        let limit = map
+                            .get("limit")
+                            .and_then(|v| v.as_u64())
+                            .map(|v| v as usize);
+
+                        zoxide::zoxide_query(query, limit)
+                    }
+
+                    "shell_history" => {
+                        let shell = map.get("shell").and_then(|v| v.as_str());
+                        let query = map
+                            .get("query")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default();
+
+                        let sort = map.get("sort").and_then(|v| v.as_str()).unwrap_or_default();
+                        let limit = map
+                            .get("limit")
+                            .and_then(|v| v.as_u64())
+                            .map(|v| v as usize);
+
+                        shell_history::shell_history(shell, query, sort, limit)
+                    }
+
+                    "exec" => {
+                        let program = map
+                            .get("program")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default();
+
+                        let arguments = map
+                            .get("args")
+                            .and_then(|v| v.as_array())
+                            .map(|v| {
+                                v.iter()
+                                    .map(|v| match v {
+                                        Value::String(v) => v.clone(),
+                                        v => v.to_string(),
+                                    })
+                                    .collect::<Vec<_>>()
+                            })
+                            .unwrap_or_default();
+
+                        let content = map.get("content").and_then(|v| v.as_str());
+                        let cwd = map.get("cwd").and_then(|v| v.as_str());
+
+                        let limits = commands::Limits::from_message(map);
+
+                        commands::exec(program, &arguments, content, cwd, limits)
+                    }
+
+                    "run_stream" => {
+                        let command = map
+                            .get("command")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default();
+
+                        let content = map.get("content").and_then(|v| v.as_str());
+                        let cwd = map.get("cwd").and_then(|v| v.as_str());
+                        let id = map.get("id").cloned().unwrap_or(Value::Null);
+
+                        commands::run_stream(command, content, cwd, id)
+                    }
+
+                    "run_pty" => {
+                        let command = map
+                            .get("command")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default();
+
+                        let cwd = map.get("cwd").and_then(|v| v.as_str());
+                        let rows = map.get("rows").and_then(|v| v.as_u64()).map(|v| v as u16);
+                        let cols = map.get("cols").and_then(|v| v.as_u64()).map(|v| v as u16);
+                        let id = map.get("id").cloned().unwrap_or(Value::Null);
+
+                        pty::run_pty(command, cwd, rows, cols, id)
+                    }
+
+                    "pty_input" => {
+                        let id = map.get("id").cloned().unwrap_or(Value::Null);
+                        let data = map.get("data").and_then(|v| v.as_str()).unwrap_or_default();
+
+                        pty::pty_input(&id, data)
+                    }
+
+                    "pty_resize" => {
+                        let id = map.get("id").cloned().unwrap_or(Value::Null);
+                        let rows = map.get("rows").and_then(|v| v.as_u64()).map(|v| v as u16);
+                        let cols = map.get("cols").and_then(|v| v.as_u64()).map(|v| v as u16);
+
+                        pty::pty_resize(&id, rows, cols)
+                    }
+
+                    "rewrite_url" => {
+                        let url = map.get("url").and_then(|v| v.as_str()).unwrap_or_default();
+                        rewrite::rewrite_url(url)
                     }
 
+                    "fix_manifest" => manifest::fix_manifest(),
+
+                    "profiles" => profiles::profiles(),
+
+                    "history_query" => {
+                        let search = map
+                            .get("query")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default();
+                        let sort = map.get("sort").and_then(|v| v.as_str());
+                        let limit = map
+                            .get("limit")
+                            .and_then(|v| v.as_u64())
+                            .map(|v| v as usize);
+
+                        let profile = map.get("profiledir").and_then(|v| v.as_str());
+                        history::history_query(search, sort, limit, profile)
+                    }
+
+                    "getpref" => {
+                        let name = map.get("name").and_then(|v| v.as_str()).unwrap_or_default();
+                        let profile = map.get("profiledir").and_then(|v| v.as_str());
+
+                        prefs::getpref(name, profile)
+                    }
+
+                    "setpref" => {
+                        let name = map.get("name").and_then(|v| v.as_str()).unwrap_or_default();
+                        let value = map.get("value").cloned().unwrap_or(Value::Null);
+                        let profile = map.get("profiledir").and_then(|v| v.as_str());
+
+                        prefs::setpref(name, &value, profile)
+                    }
+
+                    "restart" => {
+                        let profile = map.get("profiledir").and_then(|v| v.as_str());
+                        restart::restart(profile)
+                    }
+
+                    "session_backup" => {
+                        let directory = map.get("dir").and_then(|v| v.as_str());
+                        let profile = map.get("profiledir").and_then(|v| v.as_str());
+
+                        session::session_backup(directory, profile)
+                    }
+
+                    "secret" => {
+                        let attributes = map
+                            .get("attributes")
+                            .and_then(|v| v.as_object())
+                            .cloned()
+                            .unwrap_or_default();
+
+                        secret::secret(&attributes)
+                    }
+
+                    "encrypt_file" => {
+                        let file = map.get("file").and_then(|v| v.as_str()).unwrap_or_default();
+                        let output = map.get("output").and_then(|v| v.as_str());
+                        let key = map.get("key").and_then(|v| v.as_str());
+                        let recipients = map
+                            .get("recipients")
+                            .and_then(|v| v.as_array())
+                            .map(|v| {
+                                v.iter()
+                                    .filter_map(|v| v.as_str().map(String::from))
+                                    .collect::<Vec<_>>()
+                            })
+                            .unwrap_or_default();
+
+                        crypto::encrypt_file(file, output, key, &recipients)
+                    }
+
+                    "decrypt_file" => {
+                        let file = map.get("file").and_then(|v| v.as_str()).unwrap_or_default();
+                        let output = map.get("output").and_then(|v| v.as_str());
+
+                        crypto::decrypt_file(file, output)
+                    }
+
+                    "open" => {
+                        let target = map
+                            .get("target")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default();
+
+                        commands::open(target)
+                    }
+
+                    "route_open" => {
+                        let url = map.get("url").and_then(|v| v.as_str()).unwrap_or_default();
+                        routes::route_open(url)
+                    }
+
+                    "pipeline_run" => {
+                        let name = map.get("name").and_then(|v| v.as_str()).unwrap_or_default();
+                        let args = map.get("args").cloned().unwrap_or(json!({}));
+                        let id = map.get("id").cloned().unwrap_or(Value::Null);
+
+                        pipelines::pipeline_run(name, &args, id, handle_command)
+                    }
+
+                    "jobs_list" => jobs::jobs_list(),
+
+                    "job_status" => {
+                        let job = map.get("job").and_then(|v| v.as_u64()).unwrap_or_default();
+                        jobs::job_status(job)
+                    }
+
+                    "job_output" => {
+                        let job = map.get("job").and_then(|v| v.as_u64()).unwrap_or_default();
+                        jobs::job_output(job)
+                    }
+
+                    "job_kill" => {
+                        let job = map.get("job").and_then(|v| v.as_u64()).unwrap_or_default();
+                        let signal = map.get("signal").and_then(|v| v.as_str()).unwrap_or("TERM");
+
+                        jobs::job_kill(job, signal)
+                    }
+
+                    "focus_start" => {
+                        let duration = map
+                            .get("duration")
+                            .and_then(|v| v.as_u64())
+                            .unwrap_or_default();
+
+                        let label = map.get("label").and_then(|v| v.as_str());
+                        let block = map
+                            .get("block")
+                            .and_then(|v| v.as_array())
+                            .map(|v| {
+                                v.iter()
+                                    .filter_map(|v| v.as_str().map(String::from))
+                                    .collect::<Vec<_>>()
+                            })
+                            .unwrap_or_default();
+
+                        focus::focus_start(duration, label, &block)
+                    }
+
+                    "focus_stop" => focus::focus_stop(),
+                    "focus_status" => focus::focus_status(),
+
+                    "should_block" => {
+                        let domains = map
+                            .get("domains")
+                            .and_then(|v| v.as_array())
+                            .map(|v| {
+                                v.iter()
+                                    .filter_map(|v| v.as_str().map(String::from))
+                                    .collect::<Vec<_>>()
+                            })
+                            .unwrap_or_default();
+
+                        focus::should_block(&domains)
+                    }
+
+                    "tt_start" => {
+                        let label = map.get("label").and_then(|v| v.as_str());
+                        let url = map.get("url").and_then(|v| v.as_str());
+
+                        timetrack::tt_start(label, url)
+                    }
+
+                    "tt_stop" => timetrack::tt_stop(),
+
+                    "tt_report" => {
+                        let since = map.get("since").and_then(|v| v.as_str());
+                        timetrack::tt_report(since)
+                    }
+
+                    "timer_start" => {
+                        let duration = map
+                            .get("duration")
+                            .and_then(|v| v.as_u64())
+                            .unwrap_or_default();
+
+                        let label = map
+                            .get("label")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default();
+                        let notify = map.get("notify").and_then(|v| v.as_bool());
+                        let id = map.get("id").cloned().unwrap_or(Value::Null);
+
+                        timers::timer_start(duration, label, notify, id)
+                    }
+
+                    "timer_list" => timers::timer_list(),
+
+                    "timer_cancel" => {
+                        let timer = map
+                            .get("timer")
+                            .and_then(|v| v.as_u64())
+                            .unwrap_or_default();
+                        timers::timer_cancel(timer)
+                    }
+
+                    "kill" => {
+                        let pid = map.get("pid").and_then(|v| v.as_u64()).unwrap_or_default();
+                        let signal = map.get("signal").and_then(|v| v.as_str()).unwrap_or("TERM");
+
+                        commands::kill(pid, signal)
+                    }
+
+                    "help_lookup" => {
+                        let program = map
+                            .get("program")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default();
+
+                        let source = map.get("source").and_then(|v| v.as_str());
+                        let section = map.get("section").and_then(|v| v.as_str());
+
+                        help::help_lookup(program, source, section)
+                    }
+
+                    "note_get" => {
+                        let key = map.get("key").and_then(|v| v.as_str()).unwrap_or_default();
+                        notes::note_get(key)
+                    }
+
+                    "note_set" => {
+                        let key = map.get("key").and_then(|v| v.as_str()).unwrap_or_default();
+                        let content = map
+                            .get("content")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default();
+
+                        let append = map.get("append").and_then(|v| v.as_bool()).unwrap_or(false);
+                        notes::note_set(key, content, append)
+                    }
+
+                    "note_search" => {
+                        let query = map
+                            .get("query")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default();
+                        let limit = map
+                            .get("limit")
+                            .and_then(|v| v.as_u64())
+                            .map(|v| v as usize);
+
+                        notes::note_search(query, limit)
+                    }
+
+                    "clipboard" => {
+                        let action = map
+                            .get("action")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default();
+
+                        let content = map.get("content").and_then(|v| v.as_str());
+                        clipboard::clipboard(action, content)
+                    }
+
+                    "pick" => {
+                        let choices = map
+                            .get("choices")
+                            .and_then(|v| v.as_array())
+                            .cloned()
+                            .unwrap_or_default();
+
+                        let prompt = map.get("prompt").and_then(|v| v.as_str());
+                        picker::pick(&choices, prompt)
+                    }
+
+                    "cliphist_list" => {
+                        let query = map
+                            .get("query")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default();
+
+                        let limit = map
+                            .get("limit")
+                            .and_then(|v| v.as_u64())
+                            .map(|v| v as usize);
+
+                        cliphist::cliphist_list(query, limit)
+                    }
+
+                    "cliphist_get" => {
+                        let id = map
+                            .get("entry")
+                            .and_then(|v| v.as_u64())
+                            .unwrap_or_default();
+                        cliphist::cliphist_get(id)
+                    }
+
+                    "pick_pixel" => color::pick_pixel(),
+
                     "ppid" => commands::get_process_id(),
+                    "health" => maintenance::health(),
+
+                    name if name.starts_with(plugins::PREFIX) => plugins::run(name, command),
 
                     _ => error,
                 }
@@ -160,99 +823,100 @@ fn handle_command(command: &Value) -> Value {
     response
 }
 
-fn get_message(stream: &mut Stdin) -> Option<Value> {
-    let mut buffer = [0u8; 4];
-    stream.read_exact(&mut buffer).unwrap();
-
-    let length = u32::from_ne_bytes(buffer);
-    if length == 0 {
-        return None;
-    }
-
-    debug!("Received message from client with length of {}", length);
-
-    let mut buffer = vec![0u8; length as usize];
-    stream.read_exact(&mut buffer).unwrap();
-
-    let string = String::from_utf8(buffer).unwrap();
-    let json: Value = serde_json::from_str(string.as_str()).unwrap();
-
-    Some(json)
-}
-
-fn send_message(stream: &mut Stdout, json: &Value) {
-    let mut handle = stream.lock();
-    let response = &handle_command(&json).to_string();
+// fails only once the browser stopped reading our stdout
+fn send_message(json: &Value) -> std::io::Result<()> {
+    let response = match limits::admit(json) {
+        Some(throttled) => throttled,
+        None => limits::cap_reply(handle_command(json)),
+    };
 
     info!("Sending message to client");
 
-    handle
-        .write(&(response.len() as u32).to_ne_bytes())
-        .unwrap();
-    handle.write(&response.as_bytes()).unwrap();
-    handle.flush().unwrap();
+    protocol::write_frame(&response)?;
+    events::mark_replied();
+    Ok(())
 }
 
 fn main() {
-    let log_path = dirs::data_dir().unwrap().join("tridactyl");
-    std::fs::create_dir_all(&log_path).unwrap();
-
-    let log_file = File::options()
-        .append(true)
-        .create(true)
-        .open(log_path.join("tridactyl.log"))
-        .unwrap();
-
-    WriteLogger::init(LevelFilter::Info, Config::default(), log_file).unwrap();
+    let arguments = std::env::args().collect::<Vec<_>>();
+    let level = arguments
+        .windows(2)
+        .find(|v| v[0] == "--log-level")
+        .map(|v| v[1].as_str());
+    logging::init(level);
+
+    if arguments.iter().any(|v| v == "--readonly") {
+        policy::set_readonly();
+    }
 
     debug!("Ran the tridactyl native executable");
 
-    let arguments = std::env::args().collect::<Vec<_>>();
     if let Some(argument) = arguments.get(1) {
         match argument.as_str() {
             "-h" => return usage(),
             "--help" => return usage(),
-            "--setup" => return setup_tridactyl(),
+            "--setup" => return manifest::setup(&arguments[2..]),
+            "--repair" => return manifest::repair(),
+            "--healthcheck" => return healthcheck::healthcheck(&arguments[2..]),
+            "--doctor" => return doctor::doctor(),
+            "schema" => return print_schema(),
 
             _ => {}
         }
     }
 
-    let mut stream = std::io::stdin();
-    let mut stream_out = std::io::stdout();
+    children::handle_signals();
+    journal::recover();
+    migrations::run();
+    maintenance::start();
+    cliphist::start();
+    capabilities::start();
+
+    let mut stream = protocol::input();
 
     loop {
-        let json = get_message(&mut stream);
-        if let Some(json) = json {
-            send_message(&mut stream_out, &json);
+        let result = match protocol::read_frame(&mut stream) {
+            Ok(Some(json)) => send_message(&json),
+            Ok(None) => Ok(()),
+
+            // a message that is too large or not json is answered, and the
+            // next one read as usual
+            Err(err) if err.kind() == std::io::ErrorKind::InvalidData => {
+                error!("Invalid message from client, error: {}", err);
+                protocol::write_frame(&commands::io_failure("error", 1, &err))
+            }
+
+            Err(_) => break,
         };
+
+        if let Err(err) = result {
+            error!("Failed to write to client, error: {}", err);
+            break;
+        }
     }
+
+    info!("Client disconnected, shutting down");
+    children::shutdown();
 }
 
 fn usage() {
     println!(env!("CARGO_PKG_DESCRIPTION"));
-    println!("Usage: tridactyl-native [options]");
+    println!("Usage: tridactyl-native [options] [command]");
     println!("\nOptions:");
     println!("\t-h, --help\tDisplay this message");
-    println!("\t--setup   \tSetup tridactyl");
+    println!("\t--setup   \tSetup tridactyl; takes --browsers firefox,zen, --manifest-dir and --bin-path");
+    println!("\t--repair  \tFix manifests pointing at a stale executable");
+    println!("\t--healthcheck\tCheck the manifests, config and permissions; --listen serves it over http");
+    println!("\t--doctor  \tCheck each installed manifest and talk to the host it points at");
+    println!("\t--log-level\tLog at off, error, warn, info, debug or trace");
+    println!("\t--readonly\tRefuse the commands that write files or run programs");
+    println!("\nCommands:");
+    println!("\tschema    \tPrint a json schema of the messaging protocol");
 }
 
-fn setup_tridactyl() {
-    let home = dirs::home_dir().unwrap();
-    for browser in BROWSERS {
-        let path = home.join(browser);
-        if path.exists() {
-            let path = path.join("native-messaging-hosts");
-            std::fs::create_dir_all(&path).unwrap();
-
-            let path = path.join(NATIVE_MESSAGE_HOST);
-            let content = format!(
-                include_str!("../tridactyl.json"),
-                std::env::current_exe().unwrap().to_str().unwrap()
-            );
-
-            println!("installing manifest to: {}", path.to_str().unwrap());
-            std::fs::write(path, content).unwrap();
-        }
-    }
+fn print_schema() {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&registry::schema()).unwrap()
+    );
 }
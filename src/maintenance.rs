@@ -0,0 +1,228 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    commands::{self, SUCCESS_CODE},
+    config, logging,
+};
+
+const TICK: Duration = Duration::from_secs(30);
+
+const DEFAULT_INTERVAL: u64 = 60 * 60;
+const DEFAULT_LOG_MAX_SIZE: u64 = 1024 * 1024;
+const DEFAULT_LOG_KEEP: u64 = 3;
+const DEFAULT_TEMP_MAX_AGE: u64 = 24 * 60 * 60;
+
+struct Task {
+    name: &'static str,
+    run: fn() -> Result<String, String>,
+}
+
+const TASKS: [Task; 2] = [
+    Task {
+        name: "rotate_logs",
+        run: rotate_logs,
+    },
+    Task {
+        name: "sweep_temp",
+        run: sweep_temp,
+    },
+];
+
+#[derive(Default)]
+struct TaskState {
+    runs: u64,
+    last_run: Option<u64>,
+    last_result: Option<Result<String, String>>,
+}
+
+static STATE: Mutex<BTreeMap<&'static str, TaskState>> = Mutex::new(BTreeMap::new());
+static STARTED: Mutex<Option<Instant>> = Mutex::new(None);
+
+fn interval(task: &str) -> u64 {
+    config::get_u64(&format!("maintenance.{}", task)).unwrap_or(DEFAULT_INTERVAL)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+// copy-then-truncate, since the logger keeps its handle to the live file open
+fn rotate_logs() -> Result<String, String> {
+    let path = logging::log_file();
+    let max_size = config::get_u64("maintenance.log_max_size").unwrap_or(DEFAULT_LOG_MAX_SIZE);
+    let keep = config::get_u64("maintenance.log_keep").unwrap_or(DEFAULT_LOG_KEEP);
+
+    let size = std::fs::metadata(&path).map_err(|e| e.to_string())?.len();
+    if size <= max_size || keep == 0 {
+        return Ok(format!("{} bytes, nothing to do", size));
+    }
+
+    let rotated = |index: u64| path.with_extension(format!("log.{}", index));
+    for index in (1..keep).rev() {
+        if rotated(index).exists() {
+            std::fs::rename(rotated(index), rotated(index + 1)).map_err(|e| e.to_string())?;
+        }
+    }
+
+    std::fs::copy(&path, rotated(1)).map_err(|e| e.to_string())?;
+    File::options()
+        .write(true)
+        .open(&path)
+        .and_then(|file| file.set_len(0))
+        .map_err(|e| e.to_string())?;
+
+    Ok(format!("rotated {} bytes", size))
+}
+
+// the files `temp` made, one path a line; `temp.dir` can be any directory,
+// so only these are ever swept
+fn temp_list() -> PathBuf {
+    logging::data_directory().join("temp_files")
+}
+
+pub(crate) fn track_temp(path: &Path) {
+    let list = temp_list();
+    let result = list
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| File::options().create(true).append(true).open(&list))
+        .and_then(|mut file| writeln!(file, "{}", path.to_string_lossy()));
+
+    if let Err(err) = result {
+        error!(
+            "(maintenance::track_temp) Failed to record {}, error: {}",
+            path.to_string_lossy(),
+            err
+        );
+    }
+}
+
+// removes the files `temp` made once they are older than `temp_max_age`
+fn sweep_temp() -> Result<String, String> {
+    let max_age = config::get_u64("maintenance.temp_max_age").unwrap_or(DEFAULT_TEMP_MAX_AGE);
+    let max_age = Duration::from_secs(max_age);
+
+    let list = temp_list();
+    let content = match std::fs::read_to_string(&list) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok("removed 0 files".into())
+        }
+        Err(err) => return Err(err.to_string()),
+    };
+
+    let mut removed = 0;
+    let mut kept = String::new();
+    for path in content.lines().filter(|v| !v.is_empty()) {
+        // gone already, or replaced by something that is not ours
+        let Ok(metadata) = std::fs::symlink_metadata(path) else {
+            continue;
+        };
+
+        if !metadata.is_file() {
+            continue;
+        }
+
+        let expired = metadata
+            .modified()
+            .ok()
+            .and_then(|v| v.elapsed().ok())
+            .is_some_and(|age| age > max_age);
+
+        if expired && std::fs::remove_file(path).is_ok() {
+            removed += 1;
+        } else {
+            kept.push_str(path);
+            kept.push('\n');
+        }
+    }
+
+    commands::write_atomic(&list, kept.as_bytes(), Some(0o600)).map_err(|e| e.to_string())?;
+    Ok(format!("removed {} files", removed))
+}
+
+fn run_task(task: &Task) {
+    let result = (task.run)();
+    match &result {
+        Ok(message) => info!("(maintenance::{}) {}", task.name, message),
+        Err(message) => error!("(maintenance::{}) {}", task.name, message),
+    }
+
+    let mut state = STATE.lock().unwrap();
+    let state = state.entry(task.name).or_default();
+    state.runs += 1;
+    state.last_run = Some(now());
+    state.last_result = Some(result);
+}
+
+pub(crate) fn start() {
+    *STARTED.lock().unwrap() = Some(Instant::now());
+
+    std::thread::spawn(|| {
+        let mut due = [Instant::now(); TASKS.len()];
+
+        loop {
+            for (index, task) in TASKS.iter().enumerate() {
+                let interval = interval(task.name);
+                if interval == 0 || Instant::now() < due[index] {
+                    continue;
+                }
+
+                run_task(task);
+                due[index] = Instant::now() + Duration::from_secs(interval);
+            }
+
+            std::thread::sleep(TICK);
+        }
+    });
+}
+
+pub(crate) fn health() -> Value {
+    let uptime = STARTED
+        .lock()
+        .unwrap()
+        .map(|v| v.elapsed().as_secs())
+        .unwrap_or_default();
+
+    let state = STATE.lock().unwrap();
+    let tasks = TASKS
+        .iter()
+        .map(|task| {
+            let state = state.get(task.name);
+            let (result, message) = match state.and_then(|v| v.last_result.as_ref()) {
+                Some(Ok(message)) => ("ok", Some(message)),
+                Some(Err(message)) => ("error", Some(message)),
+                None => ("pending", None),
+            };
+
+            json!({
+                "name": task.name,
+                "interval": interval(task.name),
+                "runs": state.map(|v| v.runs).unwrap_or_default(),
+                "last_run": state.and_then(|v| v.last_run),
+                "result": result,
+                "message": message
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "cmd": "health",
+        "code": SUCCESS_CODE,
+        "pid": std::process::id(),
+        "uptime": uptime,
+        "maintenance": tasks
+    })
+}
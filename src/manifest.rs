@@ -0,0 +1,994 @@
+use std::path::{Path, PathBuf};
+#[cfg(windows)]
+use std::process::{Command, Stdio};
+
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::{
+    commands::{is_executable, resolve_path, write_atomic, NAME, SUCCESS_CODE},
+    config,
+    profiles::parse_ini,
+};
+
+// what firefox accepts as a native messaging host name
+const HOST_NAME: &str = r"^\w+(\.\w+)*$";
+
+struct Browser {
+    name: &'static str,
+    // on unix the directory under home holding the browser's `profiles.ini`,
+    // and the directory under home its manifests go in
+    linux: Option<(&'static str, &'static str)>,
+    macos: Option<(&'static str, &'static str)>,
+    // the `profiles.ini` directory under %APPDATA%, and the registry key the
+    // browser looks the manifest up under; the manifest itself may live
+    // anywhere
+    windows: Option<(&'static str, &'static str)>,
+    // where packages put the browser (its `application.ini`), so it is found
+    // before it has created a profile
+    installs: &'static [&'static str],
+}
+
+impl Browser {
+    fn location(&self) -> Option<(&'static str, &'static str)> {
+        if cfg!(target_os = "macos") {
+            self.macos
+        } else if cfg!(windows) {
+            self.windows
+        } else {
+            self.linux
+        }
+    }
+}
+
+const BROWSERS: [Browser; 8] = [
+    Browser {
+        name: "firefox",
+        linux: Some((".mozilla/firefox", ".mozilla/native-messaging-hosts")),
+        macos: Some((
+            "Library/Application Support/Firefox",
+            "Library/Application Support/Mozilla/NativeMessagingHosts",
+        )),
+        windows: Some(("Mozilla/Firefox", r"Software\Mozilla\NativeMessagingHosts")),
+        installs: &[
+            "/usr/lib/firefox",
+            "/usr/lib64/firefox",
+            "/opt/firefox",
+            "/Applications/Firefox.app/Contents/Resources",
+            r"C:\Program Files\Mozilla Firefox",
+        ],
+    },
+    // the snap only sees its own corner of home
+    Browser {
+        name: SNAP_BROWSER,
+        linux: Some((
+            "snap/firefox/common/.mozilla/firefox",
+            "snap/firefox/common/.mozilla/native-messaging-hosts",
+        )),
+        macos: None,
+        windows: None,
+        installs: &["/snap/firefox/current/usr/lib/firefox"],
+    },
+    Browser {
+        name: "librewolf",
+        linux: Some((".librewolf", ".librewolf/native-messaging-hosts")),
+        macos: Some((
+            "Library/Application Support/LibreWolf",
+            "Library/Application Support/LibreWolf/NativeMessagingHosts",
+        )),
+        windows: Some(("LibreWolf", r"Software\LibreWolf\NativeMessagingHosts")),
+        installs: &[
+            "/usr/lib/librewolf",
+            "/usr/share/librewolf",
+            "/Applications/LibreWolf.app/Contents/Resources",
+            r"C:\Program Files\LibreWolf",
+        ],
+    },
+    Browser {
+        name: "zen",
+        linux: Some((".zen", ".zen/native-messaging-hosts")),
+        macos: Some((
+            "Library/Application Support/zen",
+            "Library/Application Support/zen/NativeMessagingHosts",
+        )),
+        windows: Some(("zen", r"Software\Mozilla\NativeMessagingHosts")),
+        installs: &["/opt/zen", "/Applications/Zen.app/Contents/Resources"],
+    },
+    Browser {
+        name: "floorp",
+        linux: Some((".floorp", ".floorp/native-messaging-hosts")),
+        macos: Some((
+            "Library/Application Support/Floorp",
+            "Library/Application Support/Floorp/NativeMessagingHosts",
+        )),
+        windows: Some(("Floorp", r"Software\Mozilla\NativeMessagingHosts")),
+        installs: &["/opt/floorp", "/Applications/Floorp.app/Contents/Resources"],
+    },
+    Browser {
+        name: "waterfox",
+        linux: Some((".waterfox", ".waterfox/native-messaging-hosts")),
+        macos: Some((
+            "Library/Application Support/Waterfox",
+            "Library/Application Support/Waterfox/NativeMessagingHosts",
+        )),
+        windows: Some(("Waterfox", r"Software\Waterfox\NativeMessagingHosts")),
+        installs: &[
+            "/opt/waterfox",
+            "/Applications/Waterfox.app/Contents/Resources",
+        ],
+    },
+    // icecat keeps its profiles inside firefox's directory and shares its
+    // manifests
+    Browser {
+        name: "icecat",
+        linux: Some((".mozilla/icecat", ".mozilla/native-messaging-hosts")),
+        macos: None,
+        windows: None,
+        installs: &["/usr/lib/icecat", "/usr/lib64/icecat"],
+    },
+    Browser {
+        name: "thunderbird",
+        linux: Some((".thunderbird", ".mozilla/native-messaging-hosts")),
+        macos: Some((
+            "Library/Thunderbird",
+            "Library/Application Support/Mozilla/NativeMessagingHosts",
+        )),
+        windows: Some(("Thunderbird", r"Software\Mozilla\NativeMessagingHosts")),
+        installs: &[
+            "/usr/lib/thunderbird",
+            "/usr/lib64/thunderbird",
+            "/Applications/Thunderbird.app/Contents/Resources",
+            r"C:\Program Files\Mozilla Thunderbird",
+        ],
+    },
+];
+
+// chromium's manifests allow extension origins rather than ids; tridactyl
+// itself is firefox only, but look-alikes for chromium can reuse the host.
+// the first directory is the browser's user data, found under
+// %LOCALAPPDATA% on windows
+const CHROMIUM_BROWSERS: [Browser; 3] = [
+    Browser {
+        name: "chrome",
+        linux: Some((
+            ".config/google-chrome",
+            ".config/google-chrome/NativeMessagingHosts",
+        )),
+        macos: Some((
+            "Library/Application Support/Google/Chrome",
+            "Library/Application Support/Google/Chrome/NativeMessagingHosts",
+        )),
+        windows: Some((
+            "Google/Chrome/User Data",
+            r"Software\Google\Chrome\NativeMessagingHosts",
+        )),
+        installs: &[],
+    },
+    Browser {
+        name: "chromium",
+        linux: Some((".config/chromium", ".config/chromium/NativeMessagingHosts")),
+        macos: Some((
+            "Library/Application Support/Chromium",
+            "Library/Application Support/Chromium/NativeMessagingHosts",
+        )),
+        windows: Some((
+            "Chromium/User Data",
+            r"Software\Chromium\NativeMessagingHosts",
+        )),
+        installs: &[],
+    },
+    // brave reads chrome's registry key on windows
+    Browser {
+        name: "brave",
+        linux: Some((
+            ".config/BraveSoftware/Brave-Browser",
+            ".config/BraveSoftware/Brave-Browser/NativeMessagingHosts",
+        )),
+        macos: Some((
+            "Library/Application Support/BraveSoftware/Brave-Browser",
+            "Library/Application Support/BraveSoftware/Brave-Browser/NativeMessagingHosts",
+        )),
+        windows: Some((
+            "BraveSoftware/Brave-Browser/User Data",
+            r"Software\Google\Chrome\NativeMessagingHosts",
+        )),
+        installs: &[],
+    },
+];
+
+// chromium extension ids are 32 letters from a to p
+const CHROMIUM_EXTENSION_ID: &str = "^[a-p]{32}$";
+
+const SNAP_BROWSER: &str = "firefox-snap";
+
+// where `--setup --system` installs for every user; on windows the registry
+// keys move to HKLM instead
+#[cfg(all(unix, not(target_os = "macos")))]
+const SYSTEM_DIRECTORY: &str = "/usr/lib/mozilla/native-messaging-hosts";
+#[cfg(target_os = "macos")]
+const SYSTEM_DIRECTORY: &str = "/Library/Application Support/Mozilla/NativeMessagingHosts";
+
+pub(crate) fn browser_names() -> Vec<&'static str> {
+    BROWSERS
+        .iter()
+        .chain(&CHROMIUM_BROWSERS)
+        .filter(|v| v.location().is_some())
+        .map(|v| v.name)
+        .collect()
+}
+
+// the executable manifests point at; `manifest.bin_path` keeps a stable
+// wrapper there for systems like nixos, whose store paths change on rebuild
+pub(crate) fn executable() -> PathBuf {
+    config::get_string("manifest.bin_path")
+        .map(|v| resolve_path(&v))
+        .unwrap_or_else(|| std::env::current_exe().unwrap())
+}
+
+// forks of the extension can look for a host of their own name, so both
+// messengers can be installed side by side
+fn host_name() -> String {
+    config::get_string("manifest.host_name").unwrap_or(NAME.into())
+}
+
+fn manifest_file(host_name: &str) -> String {
+    format!("{}.json", host_name)
+}
+
+// extension ids beyond tridactyl's own, such as forks
+fn extensions() -> Vec<String> {
+    config::get_strings("manifest.allowed_extensions")
+}
+
+fn manifest_content_for(executable: &Path, extensions: &[String], host_name: &str) -> String {
+    let builtin = include_str!("../tridactyl.json");
+    let mut extra = String::new();
+    for id in extensions {
+        let id = Value::String(id.clone());
+        if !builtin.contains(&id.to_string()) && !extra.contains(&id.to_string()) {
+            extra += &format!(", {}", id);
+        }
+    }
+
+    format!(
+        include_str!("../tridactyl.json"),
+        Value::String(host_name.into()),
+        Value::String(executable.to_string_lossy().to_string()),
+        extra
+    )
+}
+
+pub(crate) fn manifest_content() -> String {
+    manifest_content_for(&executable(), &extensions(), &host_name())
+}
+
+// what an installed manifest should hold: ids added with `--extensions` stay
+// allowed, and a `--bin-path` or a copy that still runs stays in use unless
+// `manifest.bin_path` names another
+pub(crate) fn expected_content(current: Option<&Value>) -> String {
+    let mut extensions = extensions();
+    extensions.extend(
+        current
+            .and_then(|v| v["allowed_extensions"].as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|v| v.as_str())
+            .map(String::from),
+    );
+
+    let existing = current
+        .and_then(|v| v["path"].as_str())
+        .map(PathBuf::from)
+        .filter(|v| v.is_absolute() && is_executable(v));
+
+    let executable = match existing {
+        Some(path) if config::get_string("manifest.bin_path").is_none() => path,
+        _ => executable(),
+    };
+
+    manifest_content_for(&executable, &extensions, &host_name())
+}
+
+fn chromium_extensions() -> Vec<String> {
+    config::get_strings("manifest.chromium_extensions")
+}
+
+fn chromium_content_for(executable: &Path, extensions: &[String], host_name: &str) -> String {
+    let origins = extensions
+        .iter()
+        .map(|id| format!("chrome-extension://{}/", id))
+        .collect::<Vec<_>>();
+
+    serde_json::to_string_pretty(&json!({
+        "name": host_name,
+        "description": "Tridactyl native command handler",
+        "path": executable,
+        "type": "stdio",
+        "allowed_origins": origins
+    }))
+    .unwrap()
+}
+
+// what the browsers' directories are relative to
+fn base_directory() -> PathBuf {
+    if cfg!(windows) {
+        dirs::config_dir().unwrap()
+    } else {
+        dirs::home_dir().unwrap()
+    }
+}
+
+fn profile_count(root: &Path) -> usize {
+    std::fs::read_to_string(root.join("profiles.ini")).map_or(0, |v| {
+        parse_ini(&v)
+            .iter()
+            .filter(|section| section.0.starts_with("Profile"))
+            .count()
+    })
+}
+
+// why the browser counts as installed: a `profiles.ini` listing profiles,
+// which may live anywhere, or a package that has not created one yet
+fn detect(browser: &Browser) -> Option<String> {
+    let (profiles, _) = browser.location()?;
+    let root = base_directory().join(profiles);
+    let count = profile_count(&root);
+    if count > 0 {
+        return Some(format!(
+            "{} profile{} in {}",
+            count,
+            if count == 1 { "" } else { "s" },
+            root.to_string_lossy()
+        ));
+    }
+
+    browser
+        .installs
+        .iter()
+        .map(Path::new)
+        .find(|v| v.join("application.ini").is_file())
+        .map(|v| format!("installed in {}", v.to_string_lossy()))
+}
+
+// the directories holding a `profiles.ini`, with the browser they belong to
+pub(crate) fn profile_roots() -> Vec<(&'static str, PathBuf)> {
+    let base = base_directory();
+    let mut roots = Vec::<(&str, PathBuf)>::new();
+    for browser in &BROWSERS {
+        let Some((profiles, _)) = browser.location() else {
+            continue;
+        };
+
+        let root = base.join(profiles);
+        if root.join("profiles.ini").is_file() && !roots.iter().any(|(_, v)| *v == root) {
+            roots.push((browser.name, root));
+        }
+    }
+
+    roots
+}
+
+// the browsers that may have started the host: the one whose executable is
+// the parent process, else those sharing the directory of the manifest the
+// browser passes as the first argument; empty when neither says
+pub(crate) fn launching_browsers(
+    binary: Option<&Path>,
+    manifest: Option<&Path>,
+) -> Vec<&'static str> {
+    let name = binary
+        .and_then(|v| v.file_stem())
+        .map(|v| v.to_string_lossy().to_lowercase());
+    let name = name.as_deref().map(|v| v.trim_end_matches("-bin"));
+    let snap = binary.is_some_and(|v| v.starts_with("/snap"));
+
+    if let Some(browser) = BROWSERS
+        .iter()
+        .filter(|v| v.location().is_some())
+        .find(|v| Some(v.name) == name)
+    {
+        return vec![match snap && browser.name == "firefox" {
+            true => SNAP_BROWSER,
+            false => browser.name,
+        }];
+    }
+
+    let Some(directory) = manifest.and_then(|v| v.parent()) else {
+        return Vec::new();
+    };
+
+    let base = base_directory();
+    BROWSERS
+        .iter()
+        .filter(|v| {
+            v.location()
+                .is_some_and(|(_, manifests)| base.join(manifests) == directory)
+        })
+        .map(|v| v.name)
+        .collect()
+}
+
+// the browsers in `only` (or all of them) found on this machine, with where
+// each one's manifest goes; browsers sharing a manifest directory are listed
+// once
+#[cfg(not(windows))]
+fn find_manifests(only: &[String], host_name: &str) -> Vec<(&'static str, PathBuf)> {
+    let home = dirs::home_dir().unwrap();
+    let mut paths = Vec::<(&str, PathBuf)>::new();
+    for browser in BROWSERS
+        .iter()
+        .filter(|v| only.is_empty() || only.iter().any(|name| name == v.name))
+    {
+        let Some((_, directory)) = browser.location() else {
+            continue;
+        };
+
+        let path = home.join(directory).join(manifest_file(host_name));
+        if detect(browser).is_some() && !paths.iter().any(|(_, v)| *v == path) {
+            paths.push((browser.name, path));
+        }
+    }
+
+    paths
+}
+
+// every browser gets the same manifest, but one registry key each
+#[cfg(windows)]
+fn find_manifests(only: &[String], host_name: &str) -> Vec<(&'static str, PathBuf)> {
+    let path = dirs::data_dir()
+        .unwrap()
+        .join(NAME)
+        .join(manifest_file(host_name));
+
+    let mut keys = Vec::new();
+    let mut paths = Vec::new();
+    for browser in BROWSERS
+        .iter()
+        .filter(|v| only.is_empty() || only.iter().any(|name| name == v.name))
+    {
+        let Some((_, key)) = browser.location() else {
+            continue;
+        };
+
+        if detect(browser).is_some() && !keys.contains(&key) {
+            keys.push(key);
+            paths.push((browser.name, path.clone()));
+        }
+    }
+
+    paths
+}
+
+// chromium writes `Local State` into its user data once it has run
+fn detect_chromium(browser: &Browser) -> Option<String> {
+    let (data, _) = browser.location()?;
+    let base = if cfg!(windows) {
+        dirs::data_local_dir()?
+    } else {
+        base_directory()
+    };
+
+    let data = base.join(data);
+    data.join("Local State")
+        .is_file()
+        .then(|| format!("user data in {}", data.to_string_lossy()))
+}
+
+#[cfg(not(windows))]
+fn find_chromium_manifests(only: &[String], host_name: &str) -> Vec<(&'static str, PathBuf)> {
+    let home = dirs::home_dir().unwrap();
+    CHROMIUM_BROWSERS
+        .iter()
+        .filter(|v| only.is_empty() || only.iter().any(|name| name == v.name))
+        .filter(|v| detect_chromium(v).is_some())
+        .filter_map(|v| {
+            Some((
+                v.name,
+                home.join(v.location()?.1).join(manifest_file(host_name)),
+            ))
+        })
+        .collect()
+}
+
+// kept apart from firefox's manifest, whose content differs
+#[cfg(windows)]
+fn find_chromium_manifests(only: &[String], host_name: &str) -> Vec<(&'static str, PathBuf)> {
+    let path = dirs::data_dir()
+        .unwrap()
+        .join(NAME)
+        .join("chromium")
+        .join(manifest_file(host_name));
+
+    let mut keys = Vec::new();
+    let mut paths = Vec::new();
+    for browser in CHROMIUM_BROWSERS
+        .iter()
+        .filter(|v| only.is_empty() || only.iter().any(|name| name == v.name))
+    {
+        let Some((_, key)) = browser.location() else {
+            continue;
+        };
+
+        if detect_chromium(browser).is_some() && !keys.contains(&key) {
+            keys.push(key);
+            paths.push((browser.name, path.clone()));
+        }
+    }
+
+    paths
+}
+
+pub(crate) fn manifest_paths() -> Vec<(&'static str, PathBuf)> {
+    find_manifests(&[], &host_name())
+}
+
+#[cfg(not(windows))]
+fn system_manifests(_only: &[String], host_name: &str) -> Vec<(&'static str, PathBuf)> {
+    vec![(
+        "system",
+        Path::new(SYSTEM_DIRECTORY).join(manifest_file(host_name)),
+    )]
+}
+
+// other users' browsers may not have run yet, so every browser is registered
+#[cfg(windows)]
+fn system_manifests(only: &[String], host_name: &str) -> Vec<(&'static str, PathBuf)> {
+    let path = PathBuf::from(std::env::var_os("ProgramData").unwrap_or(r"C:\ProgramData".into()))
+        .join(NAME)
+        .join(manifest_file(host_name));
+
+    let mut keys = Vec::new();
+    let mut paths = Vec::new();
+    for browser in BROWSERS
+        .iter()
+        .filter(|v| only.is_empty() || only.iter().any(|name| name == v.name))
+    {
+        let Some((_, key)) = browser.location() else {
+            continue;
+        };
+
+        if !keys.contains(&key) {
+            keys.push(key);
+            paths.push((browser.name, path.clone()));
+        }
+    }
+
+    paths
+}
+
+// the key is named after the host, as is the manifest
+#[cfg(windows)]
+fn registry_key(browser: &str, path: &Path, system: bool) -> Option<String> {
+    let (_, key) = BROWSERS
+        .iter()
+        .chain(&CHROMIUM_BROWSERS)
+        .find(|v| v.name == browser)?
+        .location()?;
+    let hive = if system { "HKLM" } else { "HKCU" };
+    let host_name = path.file_stem()?.to_string_lossy();
+    Some(format!(r"{}\{}\{}", hive, key, host_name))
+}
+
+// whether the browser's key names the manifest at `path`
+#[cfg(windows)]
+pub(crate) fn is_registered(browser: &str, path: &Path) -> bool {
+    let Some(key) = registry_key(browser, path, false) else {
+        return false;
+    };
+
+    let output = Command::new("reg")
+        .args(["query", &key, "/ve"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output();
+
+    // `    (Default)    REG_SZ    C:\path\to\tridactyl.json`
+    output.is_ok_and(|v| {
+        String::from_utf8_lossy(&v.stdout)
+            .lines()
+            .find_map(|v| v.split_once("REG_SZ"))
+            .is_some_and(|(_, value)| Path::new(value.trim()) == path)
+    })
+}
+
+#[cfg(windows)]
+fn register(browser: &str, path: &Path, system: bool) -> std::io::Result<()> {
+    let key = registry_key(browser, path, system).ok_or(std::io::ErrorKind::NotFound)?;
+    let status = Command::new("reg")
+        .args(["add", &key, "/ve", "/t", "REG_SZ", "/f", "/d"])
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .status()?;
+
+    // reg only says "access is denied" on stderr
+    if !status.success() && system {
+        return Err(std::io::ErrorKind::PermissionDenied.into());
+    }
+
+    if !status.success() {
+        return Err(std::io::Error::other(format!(
+            "reg add exited with {}",
+            status
+        )));
+    }
+
+    info!(
+        "(manifest::register) {} now points at {}",
+        key,
+        path.to_string_lossy()
+    );
+
+    Ok(())
+}
+
+// snap confinement keeps firefox out of hidden directories in home (such as
+// ~/.cargo/bin) and gives it a private /tmp, so a host there cannot start
+pub(crate) fn snap_warning(browser: &str, executable: &Path) -> Option<String> {
+    if browser != SNAP_BROWSER {
+        return None;
+    }
+
+    let home = dirs::home_dir()?;
+    let hidden = executable.strip_prefix(&home).is_ok_and(|v| {
+        v.components()
+            .any(|v| v.as_os_str().to_string_lossy().starts_with('.'))
+    });
+
+    (hidden || executable.starts_with("/tmp")).then(|| {
+        format!(
+            "the firefox snap cannot run {}, install it outside hidden directories and /tmp (e.g. ~/bin) and run --setup again",
+            executable.to_string_lossy()
+        )
+    })
+}
+
+const SETUP_USAGE: &str =
+    "usage: --setup [--system] [--browsers name,...] [--extensions id,...] [--chromium id,...] [--host-name name] [--manifest-dir directory] [--bin-path executable]";
+
+#[cfg(unix)]
+const ADMINISTRATOR: &str = "run it as root, e.g. with sudo";
+#[cfg(windows)]
+const ADMINISTRATOR: &str = "run it from an administrator prompt";
+
+fn denied(path: &Path) -> ! {
+    eprintln!(
+        "permission denied writing {}; --system installs for every user, {}",
+        path.to_string_lossy(),
+        ADMINISTRATOR
+    );
+    std::process::exit(1);
+}
+
+fn failed(path: &Path, err: std::io::Error) -> ! {
+    eprintln!("failed writing {}: {}", path.to_string_lossy(), err);
+    std::process::exit(1);
+}
+
+fn install(
+    manifests: Vec<(&'static str, PathBuf)>,
+    content: &str,
+    executable: &Path,
+    system: bool,
+) {
+    for (browser, path) in manifests {
+        println!("installing manifest to: {}", path.to_string_lossy());
+        let written = std::fs::create_dir_all(path.parent().unwrap())
+            .and_then(|_| std::fs::write(&path, content));
+
+        match written {
+            Err(err) if system && err.kind() == std::io::ErrorKind::PermissionDenied => {
+                denied(&path)
+            }
+            Err(err) => failed(&path, err),
+            Ok(()) => {}
+        }
+
+        if let Some(warning) = snap_warning(browser, executable) {
+            println!("warning: {}", warning);
+        }
+
+        #[cfg(windows)]
+        {
+            println!("registering manifest for: {}", browser);
+            match register(browser, &path, system) {
+                Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => denied(&path),
+                Err(err) => failed(&path, err),
+                Ok(()) => {}
+            }
+        }
+    }
+}
+
+// `--browsers firefox,zen` installs for those browsers only, `--manifest-dir`
+// puts the manifest somewhere else, `--bin-path` points it at something other
+// than this executable, `--extensions` allows more extension ids,
+// `--chromium` installs for chromium browsers too, `--host-name` installs
+// under another name and `--system` installs for every user
+pub(crate) fn setup(arguments: &[String]) {
+    let mut only = Vec::new();
+    let mut extra = extensions();
+    let mut chromium = chromium_extensions();
+    let mut manifest_dir = None;
+    let mut bin_path = None;
+    let mut system = false;
+    let mut host_name = host_name();
+
+    let mut arguments = arguments.iter();
+    while let Some(flag) = arguments.next() {
+        if flag == "--system" {
+            system = true;
+            continue;
+        }
+
+        let Some(value) = arguments.next() else {
+            eprintln!("{}", SETUP_USAGE);
+            std::process::exit(1);
+        };
+
+        match flag.as_str() {
+            "--browsers" => {
+                only = value
+                    .split(',')
+                    .map(|v| v.trim().to_lowercase())
+                    .filter(|v| !v.is_empty())
+                    .collect()
+            }
+
+            "--extensions" => extra.extend(
+                value
+                    .split(',')
+                    .map(|v| v.trim().to_string())
+                    .filter(|v| !v.is_empty()),
+            ),
+
+            "--chromium" => chromium.extend(
+                value
+                    .split(',')
+                    .map(|v| v.trim().to_string())
+                    .filter(|v| !v.is_empty()),
+            ),
+
+            "--host-name" => host_name = value.clone(),
+            "--manifest-dir" => manifest_dir = Some(resolve_path(value)),
+            "--bin-path" => bin_path = Some(resolve_path(value)),
+            _ => {
+                eprintln!("{}", SETUP_USAGE);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // browsers start the host without a working directory to resolve from
+    let executable = bin_path.unwrap_or_else(executable);
+    if !executable.is_absolute() {
+        eprintln!("{} is not an absolute path", executable.to_string_lossy());
+        std::process::exit(1);
+    }
+
+    if !is_executable(&executable) {
+        println!(
+            "warning: {} is not an executable (yet)",
+            executable.to_string_lossy()
+        );
+    }
+
+    if !Regex::new(HOST_NAME).unwrap().is_match(&host_name) {
+        eprintln!(
+            "{} is not a valid host name, expected letters, digits and _ separated by dots",
+            host_name
+        );
+        std::process::exit(1);
+    }
+
+    let valid = Regex::new(CHROMIUM_EXTENSION_ID).unwrap();
+    if let Some(invalid) = chromium.iter().find(|v| !valid.is_match(v)) {
+        eprintln!(
+            "{} is not a chromium extension id, expected 32 letters from a to p",
+            invalid
+        );
+        std::process::exit(1);
+    }
+
+    // both would need a place of their own for chromium's manifest
+    if !chromium.is_empty() && (system || manifest_dir.is_some()) {
+        eprintln!(
+            "--chromium only installs per-user manifests, without --system or --manifest-dir"
+        );
+        std::process::exit(1);
+    }
+
+    let known = browser_names();
+    if let Some(unknown) = only.iter().find(|v| !known.contains(&v.as_str())) {
+        eprintln!(
+            "unknown browser {}, expected some of: {}",
+            unknown,
+            known.join(", ")
+        );
+        std::process::exit(1);
+    }
+
+    let wants_chromium = only
+        .iter()
+        .any(|name| CHROMIUM_BROWSERS.iter().any(|v| v.name == name));
+    if wants_chromium && chromium.is_empty() {
+        eprintln!("chromium browsers need the extension's id, given with --chromium");
+        std::process::exit(1);
+    }
+
+    #[cfg(unix)]
+    if system && unsafe { libc::geteuid() } != 0 {
+        eprintln!(
+            "--system installs for every user under {}, {}",
+            SYSTEM_DIRECTORY, ADMINISTRATOR
+        );
+        std::process::exit(1);
+    }
+
+    // windows still needs to know which browsers' keys to point at it
+    let found = if system {
+        system_manifests(&only, &host_name)
+    } else {
+        find_manifests(&only, &host_name)
+    };
+
+    let manifests = match manifest_dir {
+        Some(directory) if cfg!(windows) => found
+            .into_iter()
+            .map(|(browser, _)| (browser, directory.join(manifest_file(&host_name))))
+            .collect(),
+        Some(directory) => vec![("custom", directory.join(manifest_file(&host_name)))],
+        None => found,
+    };
+
+    if manifests.is_empty() {
+        println!("no supported browser found");
+    }
+
+    for (browser, _) in &manifests {
+        if let Some(found) = BROWSERS
+            .iter()
+            .find(|v| v.name == *browser)
+            .and_then(detect)
+        {
+            println!("found {}: {}", browser, found);
+        }
+    }
+
+    let content = manifest_content_for(&executable, &extra, &host_name);
+    install(manifests, &content, &executable, system);
+
+    if chromium.is_empty() {
+        return;
+    }
+
+    let manifests = find_chromium_manifests(&only, &host_name);
+    if manifests.is_empty() {
+        println!("no chromium browser found");
+    }
+
+    for (browser, _) in &manifests {
+        if let Some(found) = CHROMIUM_BROWSERS
+            .iter()
+            .find(|v| v.name == *browser)
+            .and_then(detect_chromium)
+        {
+            println!("found {}: {}", browser, found);
+        }
+    }
+
+    let content = chromium_content_for(&executable, &chromium, &host_name);
+    install(manifests, &content, &executable, false);
+}
+
+// rewrites existing manifests that no longer match what `--setup` would
+// install, usually because the executable moved
+fn repair_manifests() -> Vec<Value> {
+    manifest_paths()
+        .into_iter()
+        .map(|(browser, path)| {
+            let Ok(current) = std::fs::read_to_string(&path) else {
+                return json!({
+                    "browser": browser,
+                    "manifest": path,
+                    "status": "missing"
+                });
+            };
+
+            // a manifest nothing points at is as good as missing
+            #[cfg(windows)]
+            if !is_registered(browser, &path) {
+                if let Err(err) = register(browser, &path, false) {
+                    error!(
+                        "(manifest::repair_manifests) Failed to register {}, error: {}",
+                        path.to_string_lossy(),
+                        err
+                    );
+                }
+            }
+
+            let current = serde_json::from_str::<Value>(&current).ok();
+            let content = expected_content(current.as_ref());
+            let expected: Value = match serde_json::from_str(&content) {
+                Ok(expected) => expected,
+                Err(err) => {
+                    error!(
+                        "(manifest::repair_manifests) Generated an invalid manifest for {}, error: {}",
+                        path.to_string_lossy(),
+                        err
+                    );
+
+                    return json!({
+                        "browser": browser,
+                        "manifest": path,
+                        "status": "failed",
+                        "error": err.to_string()
+                    });
+                }
+            };
+            if current.as_ref() == Some(&expected) {
+                return json!({
+                    "browser": browser,
+                    "manifest": path,
+                    "status": "ok"
+                });
+            }
+
+            let old_path = current.as_ref().and_then(|v| v.get("path")).cloned();
+            let status = match write_atomic(&path, content.as_bytes(), None) {
+                Ok(()) => {
+                    info!(
+                        "(manifest::repair_manifests) Rewrote {}",
+                        path.to_string_lossy()
+                    );
+                    "updated"
+                }
+
+                Err(err) => {
+                    error!(
+                        "(manifest::repair_manifests) Failed to rewrite {}, error: {}",
+                        path.to_string_lossy(),
+                        err
+                    );
+                    "failed"
+                }
+            };
+
+            json!({
+                "browser": browser,
+                "manifest": path,
+                "status": status,
+                "old_path": old_path,
+                "new_path": expected["path"]
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn fix_manifest() -> Value {
+    let manifests = repair_manifests();
+    let failed = manifests.iter().any(|v| v["status"] == "failed");
+
+    json!({
+        "cmd": "fix_manifest",
+        "code": if failed { 2 } else { SUCCESS_CODE },
+        "content": manifests
+    })
+}
+
+pub(crate) fn repair() {
+    let manifests = repair_manifests();
+    for manifest in &manifests {
+        let path = manifest["manifest"].as_str().unwrap_or_default();
+        match manifest["status"].as_str().unwrap_or_default() {
+            "updated" => println!(
+                "updated {}: {} -> {}",
+                path, manifest["old_path"], manifest["new_path"]
+            ),
+            status => println!("{} {}", status, path),
+        }
+    }
+
+    if manifests.iter().any(|v| v["status"] == "failed") {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,147 @@
+use std::{fs::FileTimes, path::Path};
+
+use crate::config;
+
+// extended attributes carry POSIX ACLs (system.posix_acl_*), SELinux labels
+// and user data; copying them is opt-in as not every filesystem takes them
+pub(crate) fn xattrs_enabled(requested: Option<bool>) -> bool {
+    requested
+        .or_else(|| config::get_bool("copy.xattrs"))
+        .unwrap_or(false)
+}
+
+#[cfg(target_os = "linux")]
+mod xattr {
+    use std::{ffi::CString, io, os::unix::ffi::OsStrExt, path::Path};
+
+    fn c_path(path: &Path) -> io::Result<CString> {
+        CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+
+    // the usual size query, then the read; retried if the value grew between
+    fn read_sized<F>(mut call: F) -> io::Result<Vec<u8>>
+    where
+        F: FnMut(*mut libc::c_void, usize) -> isize,
+    {
+        loop {
+            let size = call(std::ptr::null_mut(), 0);
+            if size < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut buffer = vec![0u8; size as usize];
+            let read = call(buffer.as_mut_ptr().cast(), buffer.len());
+            if read >= 0 {
+                buffer.truncate(read as usize);
+                return Ok(buffer);
+            }
+
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::ERANGE) {
+                return Err(err);
+            }
+        }
+    }
+
+    pub(super) fn list(path: &Path) -> io::Result<Vec<CString>> {
+        let path = c_path(path)?;
+        let names = read_sized(|buffer, size| unsafe {
+            libc::listxattr(path.as_ptr(), buffer.cast(), size)
+        })?;
+
+        Ok(names
+            .split(|v| *v == 0)
+            .filter(|v| !v.is_empty())
+            .filter_map(|v| CString::new(v).ok())
+            .collect())
+    }
+
+    pub(super) fn copy(from: &Path, to: &Path, name: &CString) -> io::Result<()> {
+        let source = c_path(from)?;
+        let destination = c_path(to)?;
+
+        let value = read_sized(|buffer, size| unsafe {
+            libc::getxattr(source.as_ptr(), name.as_ptr(), buffer, size)
+        })?;
+
+        let result = unsafe {
+            libc::setxattr(
+                destination.as_ptr(),
+                name.as_ptr(),
+                value.as_ptr().cast(),
+                value.len(),
+                0,
+            )
+        };
+
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn copy_xattrs(from: &Path, to: &Path, failed: &mut Vec<String>) {
+    let names = match xattr::list(from) {
+        Ok(names) => names,
+        Err(err) => {
+            failed.push(format!("xattrs: {}", err));
+            return;
+        }
+    };
+
+    for name in names {
+        if let Err(err) = xattr::copy(from, to, &name) {
+            failed.push(format!("xattr {}: {}", name.to_string_lossy(), err));
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn copy_xattrs(_from: &Path, _to: &Path, failed: &mut Vec<String>) {
+    failed.push("xattrs: not supported on this platform".into());
+}
+
+// carries xattrs (when asked), timestamps and permissions from `from` over
+// to `to`; whatever could not be applied is returned instead of failing the
+// whole operation
+pub(crate) fn preserve(from: &Path, to: &Path, xattrs: bool) -> Vec<String> {
+    let mut failed = Vec::new();
+
+    let metadata = match std::fs::metadata(from) {
+        Ok(metadata) => metadata,
+        Err(err) => return vec![format!("metadata: {}", err)],
+    };
+
+    if xattrs {
+        copy_xattrs(from, to, &mut failed);
+    }
+
+    let mut times = FileTimes::new();
+    if let Ok(modified) = metadata.modified() {
+        times = times.set_modified(modified);
+    }
+
+    if let Ok(accessed) = metadata.accessed() {
+        times = times.set_accessed(accessed);
+    }
+
+    let result = std::fs::File::options()
+        .write(true)
+        .open(to)
+        .and_then(|file| file.set_times(times));
+
+    if let Err(err) = result {
+        failed.push(format!("timestamps: {}", err));
+    }
+
+    // last, so a read-only mode does not get in the way of the rest
+    if let Err(err) = std::fs::set_permissions(to, metadata.permissions()) {
+        failed.push(format!("permissions: {}", err));
+    }
+
+    failed
+}
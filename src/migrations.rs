@@ -0,0 +1,101 @@
+use std::sync::Mutex;
+
+use serde_json::{json, Value};
+
+use crate::{commands::write_atomic, config};
+
+// bump together with a new entry in MIGRATIONS
+pub(crate) const CONFIG_VERSION: u64 = 1;
+
+type Migration = fn(Value) -> Result<Value, String>;
+
+// MIGRATIONS[n] upgrades a config from version n to n + 1
+const MIGRATIONS: [Migration; 1] = [unversioned_to_v1];
+
+static RESULTS: Mutex<Vec<Value>> = Mutex::new(Vec::new());
+
+// configs written before versioning existed already match the first layout
+fn unversioned_to_v1(config: Value) -> Result<Value, String> {
+    Ok(config)
+}
+
+fn migrate_config() -> Result<Option<(u64, u64)>, String> {
+    let path = config::path();
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let mut value: Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+
+    let from = value.get("version").and_then(|v| v.as_u64()).unwrap_or(0);
+    if from >= CONFIG_VERSION {
+        return Ok(None);
+    }
+
+    let original = value.clone();
+    for migration in &MIGRATIONS[from as usize..] {
+        value = migration(value)?;
+    }
+
+    // a config the migrations leave as it was keeps the user's formatting
+    if value == original {
+        return Ok(None);
+    }
+
+    if let Some(map) = value.as_object_mut() {
+        map.insert("version".into(), json!(CONFIG_VERSION));
+    }
+
+    let backup = path.with_extension(format!("json.v{}.bak", from));
+    std::fs::copy(&path, &backup).map_err(|e| e.to_string())?;
+
+    // the config can hold `healthcheck.token`, so it keeps its mode
+    #[cfg(unix)]
+    let mode = {
+        use std::os::unix::fs::PermissionsExt;
+        let metadata = std::fs::metadata(&path).map_err(|e| e.to_string())?;
+        Some(metadata.permissions().mode() & 0o7777)
+    };
+
+    #[cfg(not(unix))]
+    let mode = None;
+
+    let content = serde_json::to_string_pretty(&value).map_err(|e| e.to_string())?;
+    write_atomic(&path, content.as_bytes(), mode).map_err(|e| e.to_string())?;
+
+    Ok(Some((from, CONFIG_VERSION)))
+}
+
+pub(crate) fn run() {
+    let result = match migrate_config() {
+        Ok(None) => return,
+        Ok(Some((from, to))) => {
+            info!(
+                "(migrations::run) Migrated config from v{} to v{}",
+                from, to
+            );
+            json!({
+                "store": "config",
+                "from": from,
+                "to": to,
+                "code": 0
+            })
+        }
+
+        Err(err) => {
+            error!("(migrations::run) Failed to migrate config, error: {}", err);
+            json!({
+                "store": "config",
+                "code": 2,
+                "error": err
+            })
+        }
+    };
+
+    RESULTS.lock().unwrap().push(result);
+}
+
+pub(crate) fn results() -> Vec<Value> {
+    RESULTS.lock().unwrap().clone()
+}
@@ -0,0 +1,89 @@
+use std::{fs::File, io::Read, path::Path};
+
+use serde_json::{json, Value};
+
+use crate::commands::{resolve_path, SUCCESS_CODE};
+
+// enough for every signature infer knows, including zip based formats
+const SNIFF_LENGTH: u64 = 8192;
+
+fn head(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    File::open(path)?
+        .take(SNIFF_LENGTH)
+        .read_to_end(&mut buffer)?;
+
+    Ok(buffer)
+}
+
+// magic bytes first, since a download's name is often wrong; then the
+// extension, then a guess between text and binary
+fn detect(path: &Path, bytes: &[u8]) -> (String, &'static str) {
+    if let Some(kind) = infer::get(bytes) {
+        return (kind.mime_type().to_string(), "magic");
+    }
+
+    if let Some(mime) = mime_guess::from_path(path).first() {
+        return (mime.essence_str().to_string(), "extension");
+    }
+
+    if bytes.is_empty() {
+        return ("application/x-empty".into(), "content");
+    }
+
+    // a multi-byte character cut at the end of the sample is still text
+    let text = match std::str::from_utf8(bytes) {
+        Ok(_) => true,
+        Err(err) => err.error_len().is_none(),
+    };
+
+    if text && !bytes.contains(&0) {
+        ("text/plain".into(), "content")
+    } else {
+        ("application/octet-stream".into(), "content")
+    }
+}
+
+pub(crate) fn mimetype(path: &str) -> Value {
+    let path = resolve_path(path);
+    if !path.is_file() {
+        error!(
+            "(mimetype::mimetype) Not a file: {}",
+            path.to_string_lossy()
+        );
+
+        return json!({
+            "cmd": "mimetype",
+            "code": 1,
+            "error": "Not a file"
+        });
+    }
+
+    match head(&path) {
+        Ok(bytes) => {
+            let (mime, source) = detect(&path, &bytes);
+            info!(
+                "(mimetype::mimetype) {} is {} (from {})",
+                path.to_string_lossy(),
+                mime,
+                source
+            );
+
+            json!({
+                "cmd": "mimetype",
+                "code": SUCCESS_CODE,
+                "content": mime,
+                "source": source
+            })
+        }
+
+        Err(err) => {
+            error!("(mimetype::mimetype) Failed to read, error: {}", err);
+            json!({
+                "cmd": "mimetype",
+                "code": 2,
+                "error": err.to_string()
+            })
+        }
+    }
+}
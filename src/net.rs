@@ -0,0 +1,157 @@
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::commands::{NAME, SUCCESS_CODE};
+
+const DEFAULT_TIMEOUT: u64 = 3000;
+// plain http only, there is no tls to check anything else with
+const HTTP_URL: &str = r"^(?i:http)://(\[[^\]]+\]|[^:/?#]+)(?::(\d+))?([^#]*)";
+
+// tries every address the host resolves to until one accepts
+fn connect(host: &str, port: u16, timeout: Duration) -> std::io::Result<(TcpStream, SocketAddr)> {
+    let mut last_error = None;
+    for address in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, timeout) {
+            Ok(stream) => return Ok((stream, address)),
+            Err(err) => last_error = Some(err),
+        }
+    }
+
+    Err(last_error.unwrap_or(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        "host has no addresses",
+    )))
+}
+
+fn milliseconds(duration: Duration) -> f64 {
+    (duration.as_secs_f64() * 1000.0 * 1000.0).round() / 1000.0
+}
+
+// the status code of a HEAD request, timed up to the status line
+fn head(stream: &mut TcpStream, host: &str, path: &str, timeout: Duration) -> std::io::Result<u16> {
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    write!(
+        stream,
+        "HEAD {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: {}-native\r\nConnection: close\r\n\r\n",
+        if path.is_empty() { "/" } else { path },
+        host,
+        NAME
+    )?;
+
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+    while !line.ends_with(b"\r\n") && line.len() < 256 {
+        if stream.read(&mut byte)? == 0 {
+            break;
+        }
+
+        line.push(byte[0]);
+    }
+
+    String::from_utf8_lossy(&line)
+        .split_whitespace()
+        .nth(1)
+        .and_then(|v| v.parse().ok())
+        .ok_or(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "not an http response",
+        ))
+}
+
+fn unreachable(err: std::io::Error) -> Value {
+    info!("(net::net_check) Unreachable, error: {}", err);
+    json!({
+        "cmd": "net_check",
+        "code": SUCCESS_CODE,
+        "reachable": false,
+        "error": err.to_string()
+    })
+}
+
+// a tcp connect to `host` and `port`, or a HEAD request to `url`; either way
+// an unreachable service is a result, not a failure
+pub(crate) fn net_check(
+    host: Option<&str>,
+    port: Option<u64>,
+    url: Option<&str>,
+    timeout: Option<u64>,
+) -> Value {
+    let timeout = Duration::from_millis(timeout.unwrap_or(DEFAULT_TIMEOUT).max(1));
+
+    let target = match url {
+        Some(url) => {
+            // the host and path go into the request as they are, so a line
+            // break in them would add headers or a second request
+            let captures = Regex::new(HTTP_URL)
+                .unwrap()
+                .captures(url)
+                .filter(|_| !url.chars().any(|c| c.is_whitespace() || c.is_control()));
+            let Some(captures) = captures else {
+                return json!({
+                    "cmd": "net_check",
+                    "code": 1,
+                    "error": "Expected an http url"
+                });
+            };
+
+            let host = captures[1].trim_matches(['[', ']']).to_string();
+            let port = captures
+                .get(2)
+                .map_or(Some(80), |v| v.as_str().parse().ok());
+            port.map(|port| (host, port, Some(captures[3].to_string())))
+        }
+
+        None => host
+            .zip(port.and_then(|v| u16::try_from(v).ok()))
+            .map(|(host, port)| (host.to_string(), port, None)),
+    };
+
+    let Some((host, port, path)) = target.filter(|(_, port, _)| *port != 0) else {
+        return json!({
+            "cmd": "net_check",
+            "code": 1,
+            "error": "Expected a url or a host and port"
+        });
+    };
+
+    let start = Instant::now();
+    let (mut stream, address) = match connect(&host, port, timeout) {
+        Ok(connection) => connection,
+        Err(err) => return unreachable(err),
+    };
+
+    let connected = start.elapsed();
+    let status = match path {
+        Some(path) => {
+            let remaining = timeout
+                .saturating_sub(connected)
+                .max(Duration::from_millis(1));
+            match head(&mut stream, &host, &path, remaining) {
+                Ok(status) => Some(status),
+                Err(err) => return unreachable(err),
+            }
+        }
+
+        None => None,
+    };
+
+    let latency = milliseconds(start.elapsed());
+    info!("(net::net_check) {} is reachable in {}ms", address, latency);
+
+    json!({
+        "cmd": "net_check",
+        "code": SUCCESS_CODE,
+        "reachable": true,
+        "address": address.to_string(),
+        "connect": milliseconds(connected),
+        "latency": latency,
+        "status": status
+    })
+}
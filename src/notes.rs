@@ -0,0 +1,171 @@
+use std::path::PathBuf;
+
+use serde_json::{json, Value};
+
+use crate::{
+    commands::{resolve_path, sanitize_file_name, url_host, write_atomic, SUCCESS_CODE},
+    config, logging,
+};
+
+const DEFAULT_SEARCH_LIMIT: usize = 100;
+
+fn notes_directory() -> PathBuf {
+    match config::get_string("notes.dir") {
+        Some(directory) => resolve_path(&directory),
+        None => logging::data_directory().join("notes"),
+    }
+}
+
+// a url is reduced to its host, so every page of a site shares one note
+fn domain(key: &str) -> Option<String> {
+    let host = url_host(key).unwrap_or(key.to_lowercase());
+    let host = host.strip_prefix("www.").unwrap_or(&host);
+    let name = sanitize_file_name(host);
+    (!name.is_empty() && !name.starts_with('.')).then_some(name)
+}
+
+fn note_path(key: &str) -> Option<(String, PathBuf)> {
+    let domain = domain(key)?;
+    let path = notes_directory().join(format!("{}.md", domain));
+    Some((domain, path))
+}
+
+fn invalid_key(cmd: &str, key: &str) -> Value {
+    error!("(notes::{}) Invalid key: {}", cmd, key);
+    json!({
+        "cmd": cmd,
+        "code": 1,
+        "error": "Expected a url or domain"
+    })
+}
+
+pub(crate) fn note_get(key: &str) -> Value {
+    let Some((domain, path)) = note_path(key) else {
+        return invalid_key("note_get", key);
+    };
+
+    match std::fs::read_to_string(&path) {
+        Ok(content) => {
+            info!("(notes::note_get) Read note for {}", domain);
+            json!({
+                "cmd": "note_get",
+                "code": SUCCESS_CODE,
+                "domain": domain,
+                "file": path,
+                "content": content
+            })
+        }
+
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => json!({
+            "cmd": "note_get",
+            "code": 1,
+            "domain": domain,
+            "file": path
+        }),
+
+        Err(err) => {
+            error!("(notes::note_get) Failed to read note, error: {}", err);
+            json!({
+                "cmd": "note_get",
+                "code": 2,
+                "error": err.to_string()
+            })
+        }
+    }
+}
+
+// `append` adds to the end of the note instead of replacing it; an empty
+// note is removed
+pub(crate) fn note_set(key: &str, content: &str, append: bool) -> Value {
+    let Some((domain, path)) = note_path(key) else {
+        return invalid_key("note_set", key);
+    };
+
+    let mut text = String::new();
+    if append {
+        text = std::fs::read_to_string(&path).unwrap_or_default();
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+    }
+
+    text.push_str(content);
+
+    let result = if text.trim().is_empty() {
+        match std::fs::remove_file(&path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    } else {
+        std::fs::create_dir_all(notes_directory())
+            .and_then(|_| write_atomic(&path, text.as_bytes(), None))
+    };
+
+    match result {
+        Ok(()) => {
+            info!("(notes::note_set) Saved note for {}", domain);
+            json!({
+                "cmd": "note_set",
+                "code": SUCCESS_CODE,
+                "domain": domain,
+                "file": path
+            })
+        }
+
+        Err(err) => {
+            error!("(notes::note_set) Failed to save note, error: {}", err);
+            json!({
+                "cmd": "note_set",
+                "code": 2,
+                "error": err.to_string()
+            })
+        }
+    }
+}
+
+// case-insensitive match on each line of every note
+pub(crate) fn note_search(query: &str, limit: Option<usize>) -> Value {
+    let query = query.to_lowercase();
+    let limit = limit.unwrap_or(DEFAULT_SEARCH_LIMIT);
+
+    let mut entries = std::fs::read_dir(notes_directory())
+        .map(|v| v.flatten().map(|v| v.path()).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    entries.retain(|v| v.extension().is_some_and(|v| v == "md"));
+    entries.sort();
+
+    let mut content = Vec::new();
+    'notes: for path in entries {
+        let domain = path.file_stem().unwrap_or_default().to_string_lossy();
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+
+        for (index, line) in text.lines().enumerate() {
+            if content.len() >= limit {
+                break 'notes;
+            }
+
+            if line.to_lowercase().contains(&query) {
+                content.push(json!({
+                    "domain": domain,
+                    "line": index + 1,
+                    "text": line
+                }));
+            }
+        }
+    }
+
+    info!(
+        "(notes::note_search) query: {}, results: {}",
+        query,
+        content.len()
+    );
+
+    json!({
+        "cmd": "note_search",
+        "code": SUCCESS_CODE,
+        "content": content
+    })
+}
@@ -0,0 +1,166 @@
+use std::{process::Command, time::Duration};
+
+use serde_json::{json, Value};
+
+use crate::{
+    commands::{find_program, run_with_timeout, SUCCESS_CODE},
+    config,
+};
+
+// the user has to get around to choosing
+const DEFAULT_TIMEOUT: u64 = 300_000;
+
+struct Launcher {
+    name: &'static str,
+    arguments: &'static [&'static str],
+    prompt: &'static str,
+    wayland: bool,
+    x11: bool,
+}
+
+// in order of preference; they all read choices from stdin and print the
+// selected line
+const LAUNCHERS: [Launcher; 4] = [
+    Launcher {
+        name: "fuzzel",
+        arguments: &["--dmenu"],
+        prompt: "--prompt",
+        wayland: true,
+        x11: false,
+    },
+    Launcher {
+        name: "wofi",
+        arguments: &["--dmenu"],
+        prompt: "--prompt",
+        wayland: true,
+        x11: false,
+    },
+    Launcher {
+        name: "rofi",
+        arguments: &["-dmenu"],
+        prompt: "-p",
+        wayland: true,
+        x11: true,
+    },
+    Launcher {
+        name: "dmenu",
+        arguments: &[],
+        prompt: "-p",
+        wayland: false,
+        x11: true,
+    },
+];
+
+fn known(launcher: &Launcher, prompt: Option<&str>) -> Command {
+    let mut process = Command::new(launcher.name);
+    process.args(launcher.arguments);
+    if let Some(prompt) = prompt {
+        process.args([launcher.prompt, prompt]);
+    }
+
+    process
+}
+
+// `pick.launcher` is either the name of a known launcher or a whole command
+// line, with `%p` standing for the prompt
+fn configured(command: &str, prompt: Option<&str>) -> Option<Command> {
+    if let Some(launcher) = LAUNCHERS.iter().find(|v| v.name == command) {
+        return Some(known(launcher, prompt));
+    }
+
+    let arguments = shell_words::split(command)
+        .ok()
+        .filter(|v| !v.is_empty())?
+        .into_iter()
+        .map(|v| v.replace("%p", prompt.unwrap_or_default()))
+        .collect::<Vec<_>>();
+
+    let mut process = Command::new(&arguments[0]);
+    process.args(&arguments[1..]);
+    Some(process)
+}
+
+fn detect(prompt: Option<&str>) -> Option<Command> {
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+    let x11 = std::env::var_os("DISPLAY").is_some();
+
+    LAUNCHERS
+        .iter()
+        .filter(|v| (wayland && v.wayland) || (x11 && v.x11))
+        .find(|v| find_program(v.name).is_some())
+        .map(|v| known(v, prompt))
+}
+
+pub(crate) fn launcher(prompt: Option<&str>) -> Option<Command> {
+    match config::get_string("pick.launcher") {
+        Some(command) => configured(&command, prompt),
+        None => detect(prompt),
+    }
+}
+
+pub(crate) fn pick(choices: &[Value], prompt: Option<&str>) -> Value {
+    let choices = choices
+        .iter()
+        .filter_map(|v| v.as_str())
+        .map(|v| v.replace(['\n', '\r'], " "))
+        .collect::<Vec<_>>();
+
+    if choices.is_empty() {
+        return json!({
+            "cmd": "pick",
+            "code": 1,
+            "error": "Expected at least one choice"
+        });
+    }
+
+    let Some(process) = launcher(prompt) else {
+        error!("(picker::pick) No launcher found");
+        return json!({
+            "cmd": "pick",
+            "code": 2,
+            "error": "No launcher found"
+        });
+    };
+
+    let input = choices.join("\n") + "\n";
+    let timeout = config::get_u64("pick.timeout").unwrap_or(DEFAULT_TIMEOUT);
+    let output = match run_with_timeout(
+        process,
+        Some(input.as_bytes()),
+        Duration::from_millis(timeout),
+    ) {
+        Ok(output) => output,
+        Err(err) => {
+            error!("(picker::pick) Failed to run the launcher, error: {}", err);
+            return json!({
+                "cmd": "pick",
+                "code": 2,
+                "error": err.to_string()
+            });
+        }
+    };
+
+    let selection = String::from_utf8_lossy(&output.stdout);
+    let selection = selection.trim_end_matches(['\n', '\r']);
+
+    // launchers exit non-zero when escape is pressed
+    if !output.status.success() || selection.is_empty() {
+        info!("(picker::pick) Cancelled");
+        return json!({
+            "cmd": "pick",
+            "code": 1,
+            "error": "Cancelled"
+        });
+    }
+
+    // most launchers also accept text that is not one of the choices
+    let index = choices.iter().position(|v| v == selection);
+    info!("(picker::pick) Picked {:?}", index);
+
+    json!({
+        "cmd": "pick",
+        "code": SUCCESS_CODE,
+        "content": selection,
+        "index": index
+    })
+}
@@ -0,0 +1,160 @@
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::{commands::SUCCESS_CODE, config, events};
+
+// `{{ prev.content }}`, `{{ steps.0.path }}` or `{{ args.url }}`
+const PLACEHOLDER: &str = r"\{\{\s*([A-Za-z0-9_.-]+)\s*\}\}";
+
+fn lookup<'a>(context: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(context, |value, key| match value {
+        Value::Array(array) => array.get(key.parse::<usize>().ok()?),
+        Value::Object(map) => map.get(key),
+        _ => None,
+    })
+}
+
+// a string that is nothing but a placeholder keeps the referenced value's
+// type, anything else is interpolated as text
+fn substitute(value: &Value, context: &Value, placeholder: &Regex) -> Value {
+    match value {
+        Value::String(string) => {
+            if let Some(captures) = placeholder.captures(string) {
+                if captures[0].len() == string.len() {
+                    return lookup(context, &captures[1])
+                        .cloned()
+                        .unwrap_or(Value::Null);
+                }
+            }
+
+            let result =
+                placeholder.replace_all(string, |captures: &regex::Captures| {
+                    match lookup(context, &captures[1]) {
+                        Some(Value::String(v)) => v.clone(),
+                        Some(Value::Null) | None => String::new(),
+                        Some(v) => v.to_string(),
+                    }
+                });
+
+            Value::String(result.into_owned())
+        }
+
+        Value::Array(array) => Value::Array(
+            array
+                .iter()
+                .map(|v| substitute(v, context, placeholder))
+                .collect(),
+        ),
+
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| (k.clone(), substitute(v, context, placeholder)))
+                .collect(),
+        ),
+
+        _ => value.clone(),
+    }
+}
+
+pub(crate) fn pipeline_run(
+    name: &str,
+    args: &Value,
+    id: Value,
+    handle_command: fn(&Value) -> Value,
+) -> Value {
+    let Some(steps) =
+        config::get(&format!("pipelines.{}.steps", name)).and_then(|v| v.as_array().cloned())
+    else {
+        error!("(pipelines::pipeline_run) No such pipeline: '{}'", name);
+        return json!({
+            "cmd": "pipeline_run",
+            "code": 1,
+            "error": "No such pipeline"
+        });
+    };
+
+    let placeholder = Regex::new(PLACEHOLDER).unwrap();
+    let mut context = json!({
+        "args": args,
+        "steps": [],
+        "prev": null
+    });
+
+    let total = steps.len();
+    for (index, step) in steps.iter().enumerate() {
+        let optional = step
+            .get("optional")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let mut message = substitute(step, &context, &placeholder);
+        if let Value::Object(map) = &mut message {
+            map.remove("optional");
+        }
+
+        let command = message
+            .get("cmd")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string();
+
+        let result = if command == "pipeline_run" {
+            json!({
+                "cmd": command,
+                "code": 1,
+                "error": "Pipelines cannot run other pipelines"
+            })
+        } else {
+            handle_command(&message)
+        };
+
+        let code = result
+            .get("code")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(SUCCESS_CODE as i64);
+
+        events::emit(&json!({
+            "cmd": "pipeline_run",
+            "id": id,
+            "event": "step",
+            "pipeline": name,
+            "step": index,
+            "total": total,
+            "step_cmd": command,
+            "code": code
+        }));
+
+        context["steps"]
+            .as_array_mut()
+            .unwrap()
+            .push(result.clone());
+        context["prev"] = result;
+
+        if code != SUCCESS_CODE as i64 && !optional {
+            error!(
+                "(pipelines::pipeline_run) Pipeline '{}' failed at step {} ({})",
+                name, index, command
+            );
+
+            return json!({
+                "cmd": "pipeline_run",
+                "code": code,
+                "pipeline": name,
+                "failed_step": index,
+                "results": context["steps"]
+            });
+        }
+    }
+
+    info!(
+        "(pipelines::pipeline_run) Ran pipeline '{}' ({} steps)",
+        name, total
+    );
+
+    json!({
+        "cmd": "pipeline_run",
+        "code": SUCCESS_CODE,
+        "pipeline": name,
+        "results": context["steps"]
+    })
+}
@@ -0,0 +1,347 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    commands::{config_directory, resolve_path, run_with_timeout},
+    config,
+};
+
+#[cfg(feature = "wasm")]
+mod wasm {
+    use std::{path::Path, sync::mpsc, time::Duration};
+
+    use wasmtime::{Config, Engine, Linker, Module, Store, Trap};
+    use wasmtime_wasi::{
+        pipe::{MemoryInputPipe, MemoryOutputPipe},
+        preview1::{self, WasiP1Ctx},
+        DirPerms, FilePerms, I32Exit, WasiCtxBuilder,
+    };
+
+    use super::Grants;
+
+    const MAX_OUTPUT: usize = 16 * 1024 * 1024;
+
+    // runs a wasi module with nothing but the granted directories and,
+    // optionally, the network; returns its exit code and stdout
+    pub(super) fn run(
+        path: &Path,
+        input: &[u8],
+        grants: &Grants,
+        timeout: Duration,
+    ) -> wasmtime::Result<(i32, Vec<u8>)> {
+        let mut config = Config::new();
+        config.epoch_interruption(true);
+
+        let engine = Engine::new(&config)?;
+        let module = Module::from_file(&engine, path)?;
+
+        let mut linker: Linker<WasiP1Ctx> = Linker::new(&engine);
+        preview1::add_to_linker_sync(&mut linker, |cx| cx)?;
+
+        let stdout = MemoryOutputPipe::new(MAX_OUTPUT);
+        let mut builder = WasiCtxBuilder::new();
+        builder
+            .stdin(MemoryInputPipe::new(input.to_vec()))
+            .stdout(stdout.clone());
+
+        let (dir_perms, file_perms) = if grants.writable {
+            (DirPerms::all(), FilePerms::all())
+        } else {
+            (DirPerms::READ, FilePerms::READ)
+        };
+
+        for directory in &grants.directories {
+            builder.preopened_dir(
+                directory,
+                directory.to_string_lossy(),
+                dir_perms,
+                file_perms,
+            )?;
+        }
+
+        if grants.network {
+            builder.inherit_network().allow_ip_name_lookup(true);
+        }
+
+        let mut store = Store::new(&engine, builder.build_p1());
+        store.set_epoch_deadline(1);
+
+        // bump the epoch once the timeout passes, which traps the guest
+        let (done, finished) = mpsc::channel::<()>();
+        let watchdog = engine.clone();
+        std::thread::spawn(move || {
+            if finished.recv_timeout(timeout).is_err() {
+                watchdog.increment_epoch();
+            }
+        });
+
+        let instance = linker.instantiate(&mut store, &module)?;
+        let start = instance.get_typed_func::<(), ()>(&mut store, "_start")?;
+        let result = start.call(&mut store, ());
+        let _ = done.send(());
+
+        let code = match result {
+            Ok(()) => 0,
+            Err(err) => match err.downcast_ref::<I32Exit>() {
+                Some(exit) => exit.0,
+                None if err.downcast_ref::<Trap>() == Some(&Trap::Interrupt) => {
+                    return Err(wasmtime::Error::msg("plugin timed out"));
+                }
+                None => return Err(err),
+            },
+        };
+
+        drop(store);
+        Ok((code, stdout.contents().to_vec()))
+    }
+}
+
+pub(crate) const PREFIX: &str = "plugin:";
+
+const DIRECTORY: &str = "native-plugins";
+const DEFAULT_TIMEOUT: u64 = 10_000;
+
+fn directory() -> PathBuf {
+    config_directory().join(DIRECTORY)
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// what a wasm plugin may touch, from `plugins.grants.<name>`
+#[cfg_attr(not(feature = "wasm"), allow(dead_code))]
+struct Grants {
+    directories: Vec<PathBuf>,
+    writable: bool,
+    network: bool,
+}
+
+impl Grants {
+    fn for_plugin(name: &str) -> Self {
+        let key = format!("plugins.grants.{}", name);
+        Self {
+            directories: config::get_strings(&format!("{}.dirs", key))
+                .into_iter()
+                .map(|v| resolve_path(&v))
+                .collect(),
+            writable: config::get_bool(&format!("{}.writable", key)).unwrap_or(false),
+            network: config::get_bool(&format!("{}.network", key)).unwrap_or(false),
+        }
+    }
+}
+
+fn is_wasm(path: &Path) -> bool {
+    path.extension().is_some_and(|v| v == "wasm")
+}
+
+// wasm modules are only plugins when the runtime is compiled in, they are
+// never executed directly
+fn is_supported(path: &Path) -> bool {
+    path.is_file() && (cfg!(feature = "wasm") || !is_wasm(path))
+}
+
+fn is_allowed(name: &str) -> bool {
+    if !config::get_bool("plugins.enabled").unwrap_or(true) {
+        return false;
+    }
+
+    match config::get("plugins.allow") {
+        Some(_) => config::get_strings("plugins.allow")
+            .iter()
+            .any(|v| v == name),
+
+        None => true,
+    }
+}
+
+// `<name>` itself or `<name>.<extension>` for scripts
+fn find(name: &str) -> Option<PathBuf> {
+    if !is_valid_name(name) {
+        return None;
+    }
+
+    let entries = directory().read_dir().ok()?;
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| is_supported(path))
+        .find(|path| {
+            path.file_name().is_some_and(|v| v == name)
+                || (path.file_stem().is_some_and(|v| v == name) && path.extension().is_some())
+        })
+}
+
+pub(crate) fn list() -> Vec<String> {
+    let Ok(entries) = directory().read_dir() else {
+        return Vec::new();
+    };
+
+    let mut names = entries
+        .flatten()
+        .filter(|entry| is_supported(&entry.path()))
+        .filter_map(|entry| {
+            let path = entry.path();
+            let name = path.file_stem()?.to_string_lossy().to_string();
+            (is_valid_name(&name) && is_allowed(&name)).then(|| format!("{}{}", PREFIX, name))
+        })
+        .collect::<Vec<_>>();
+
+    names.sort();
+    names.dedup();
+    names
+}
+
+// runtime and grants of every plugin, so the extension can tell what each one
+// is able to do before calling it
+pub(crate) fn capabilities() -> Value {
+    let plugins = list()
+        .into_iter()
+        .filter_map(|command| {
+            let name = command.strip_prefix(PREFIX)?.to_string();
+            let path = find(&name)?;
+
+            let capabilities = if is_wasm(&path) {
+                let grants = Grants::for_plugin(&name);
+                json!({
+                    "runtime": "wasm",
+                    "dirs": grants.directories,
+                    "writable": grants.writable,
+                    "network": grants.network
+                })
+            } else {
+                json!({ "runtime": "process" })
+            };
+
+            Some((command, capabilities))
+        })
+        .collect::<serde_json::Map<_, _>>();
+
+    Value::Object(plugins)
+}
+
+pub(crate) fn exists(command: &str) -> bool {
+    command
+        .strip_prefix(PREFIX)
+        .is_some_and(|name| is_allowed(name) && find(name).is_some())
+}
+
+fn run_process(
+    name: &str,
+    path: &Path,
+    input: &[u8],
+    timeout: Duration,
+) -> Result<(Option<i32>, Vec<u8>), String> {
+    let mut process = Command::new(path);
+    process.current_dir(directory());
+
+    let output = run_with_timeout(process, Some(input), timeout).map_err(|v| v.to_string())?;
+    if !output.stderr.is_empty() {
+        debug!(
+            "(plugins::run_process) Plugin '{}' stderr: {}",
+            name,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok((output.status.code(), output.stdout))
+}
+
+#[cfg(feature = "wasm")]
+fn run_wasm(
+    name: &str,
+    path: &Path,
+    input: &[u8],
+    timeout: Duration,
+) -> Result<(Option<i32>, Vec<u8>), String> {
+    wasm::run(path, input, &Grants::for_plugin(name), timeout)
+        .map(|(code, stdout)| (Some(code), stdout))
+        .map_err(|v| v.to_string())
+}
+
+#[cfg(not(feature = "wasm"))]
+fn run_wasm(
+    _name: &str,
+    _path: &Path,
+    _input: &[u8],
+    _timeout: Duration,
+) -> Result<(Option<i32>, Vec<u8>), String> {
+    Err("Built without the wasm feature".into())
+}
+
+pub(crate) fn run(command: &str, message: &Value) -> Value {
+    let name = command.strip_prefix(PREFIX).unwrap_or_default();
+
+    if !is_allowed(name) {
+        error!("(plugins::run) Plugin '{}' is not allowed by policy", name);
+        return json!({
+            "cmd": command,
+            "code": 1,
+            "error": "Plugin is not allowed"
+        });
+    }
+
+    let Some(path) = find(name) else {
+        error!("(plugins::run) No such plugin: '{}'", name);
+        return json!({
+            "cmd": command,
+            "code": 1,
+            "error": "No such plugin"
+        });
+    };
+
+    let timeout = config::get_u64("plugins.timeout").unwrap_or(DEFAULT_TIMEOUT);
+    let timeout = Duration::from_millis(timeout);
+    let input = message.to_string();
+
+    let output = if is_wasm(&path) {
+        run_wasm(name, &path, input.as_bytes(), timeout)
+    } else {
+        run_process(name, &path, input.as_bytes(), timeout)
+    };
+
+    let (exit_code, stdout) = match output {
+        Ok(output) => output,
+        Err(err) => {
+            error!("(plugins::run) Plugin '{}' failed, error: {}", name, err);
+            return json!({
+                "cmd": command,
+                "code": 2,
+                "error": err
+            });
+        }
+    };
+
+    match serde_json::from_slice::<Value>(&stdout) {
+        Ok(Value::Object(mut response)) => {
+            info!("(plugins::run) Ran plugin '{}'", name);
+            response
+                .entry("cmd")
+                .or_insert_with(|| Value::String(command.into()));
+
+            Value::Object(response)
+        }
+
+        _ => {
+            error!(
+                "(plugins::run) Plugin '{}' did not answer with a json object",
+                name
+            );
+
+            json!({
+                "cmd": command,
+                "code": 2,
+                "error": "Plugin did not answer with a json object",
+                "exit_code": exit_code
+            })
+        }
+    }
+}
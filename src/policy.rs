@@ -0,0 +1,423 @@
+use std::{
+    path::{Component, Path, PathBuf},
+    process::Command,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use regex::Regex;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    commands::{find_program, resolve_path, temp_directory},
+    config, confirm, plugins,
+    registry::{self, Kind},
+};
+
+// the `error` of a request the `security` settings refused, so the extension
+// can tell it apart from a failure
+pub(crate) const POLICY_DENIED: &str = "policy_denied";
+
+// the `error` of a request the user refused in a confirmation dialog
+pub(crate) const DENIED_BY_USER: &str = "denied_by_user";
+
+// shell syntax that could run something besides the first word
+const SHELL_OPERATORS: [char; 9] = [';', '&', '|', '<', '>', '`', '$', '(', '\n'];
+
+// the fields naming files a command overwrites; `move` and `copy` only do
+// with `overwrite`
+const DESTINATIONS: [(&str, &str); 7] = [
+    ("write", "file"),
+    ("write_many", "files"),
+    ("writerc", "file"),
+    ("move", "to"),
+    ("copy", "to"),
+    ("encrypt_file", "output"),
+    ("decrypt_file", "output"),
+];
+
+// the commands that start programs, gone without the `shell` feature or with
+// `security.run.enabled` set to false
+pub(crate) const SHELL_COMMANDS: [&str; 5] = ["run", "run_async", "run_stream", "run_pty", "exec"];
+
+static READONLY: AtomicBool = AtomicBool::new(false);
+
+pub(crate) enum Refusal {
+    Policy(String),
+    User(String),
+}
+
+pub(crate) fn denied(cmd: &str, refusal: Refusal) -> Value {
+    let (error, reason) = match refusal {
+        Refusal::Policy(reason) => (POLICY_DENIED, reason),
+        Refusal::User(reason) => (DENIED_BY_USER, reason),
+    };
+
+    info!("(policy::denied) Refused {}: {}", cmd, reason);
+    json!({
+        "cmd": cmd,
+        "code": 1,
+        "error": error,
+        "reason": reason
+    })
+}
+
+// with `security.confirm.enabled`, what the settings would refuse for not
+// being listed is put to the user instead
+fn ask_or_refuse(question: String, reason: String) -> Result<(), Refusal> {
+    if !confirm::enabled() {
+        return Err(Refusal::Policy(reason));
+    }
+
+    if confirm::ask(&question) {
+        Ok(())
+    } else {
+        Err(Refusal::User(format!("the user refused: {}", question)))
+    }
+}
+
+// allow patterns have to match the whole command line; a pattern that does
+// not compile allows nothing and denies everything
+fn allowed_by(pattern: &str, line: &str) -> bool {
+    Regex::new(&format!("^(?:{})$", pattern)).is_ok_and(|v| v.is_match(line))
+}
+
+fn denied_by(pattern: &str, line: &str) -> bool {
+    Regex::new(pattern).map_or(true, |v| v.is_match(line))
+}
+
+// the spellings of a program a `security.run.deny` entry is compared with:
+// as written, its file name, and the file it runs, looked up in PATH for a
+// bare name, with symlinks followed
+fn program_names(program: &str) -> Vec<String> {
+    let path = if program.contains(std::path::is_separator) {
+        Some(resolve_path(program))
+    } else {
+        find_program(program)
+    };
+
+    let mut paths = Vec::new();
+    if let Some(path) = path {
+        paths.extend(path.canonicalize());
+        paths.push(path);
+    }
+
+    paths.push(PathBuf::from(program));
+
+    let mut names = Vec::new();
+    for path in paths {
+        names.push(path.to_string_lossy().to_string());
+        if let Some(name) = path.file_name() {
+            names.push(name.to_string_lossy().to_string());
+        }
+    }
+
+    names
+}
+
+fn denied_program(deny: &[String], program: &str) -> Option<String> {
+    let names = program_names(program);
+    deny.iter().find(|v| names.contains(v)).cloned()
+}
+
+// `program` is what would run and `line` the whole command line; a bare name
+// in `security.run.allow` only allows the program looked up in PATH
+fn check(program: &str, line: &str, shell: bool) -> Result<(), Refusal> {
+    let deny = config::get_strings("security.run.deny");
+    if let Some(entry) = denied_program(&deny, program) {
+        return Err(Refusal::Policy(format!(
+            "{} is in security.run.deny",
+            entry
+        )));
+    }
+
+    // past an operator any word may be a program, so none may be denied
+    if shell && line.contains(SHELL_OPERATORS) {
+        let words = line
+            .split(|c: char| c.is_whitespace() || SHELL_OPERATORS.contains(&c) || "\"'".contains(c))
+            .filter(|v| !v.is_empty());
+
+        for word in words {
+            if let Some(entry) = denied_program(&deny, word) {
+                return Err(Refusal::Policy(format!(
+                    "{} is in security.run.deny and the command line has shell operators",
+                    entry
+                )));
+            }
+        }
+    }
+
+    if let Some(pattern) = config::get_strings("security.run.deny_patterns")
+        .into_iter()
+        .find(|v| denied_by(v, line))
+    {
+        return Err(Refusal::Policy(format!(
+            "the command matches {:?} in security.run.deny_patterns",
+            pattern
+        )));
+    }
+
+    let allow = config::get_strings("security.run.allow");
+    let patterns = config::get_strings("security.run.allow_patterns");
+    if allow.is_empty() && patterns.is_empty() && !confirm::enabled() {
+        return Ok(());
+    }
+
+    if patterns.iter().any(|v| allowed_by(v, line)) {
+        return Ok(());
+    }
+
+    let question = format!("Run {}?", line);
+    if !allow.iter().any(|v| v == program) {
+        return ask_or_refuse(
+            question,
+            format!("{} is not in security.run.allow", program),
+        );
+    }
+
+    if shell && line.contains(SHELL_OPERATORS) {
+        return ask_or_refuse(
+            question,
+            "shell operators and substitutions need a match in security.run.allow_patterns".into(),
+        );
+    }
+
+    Ok(())
+}
+
+// a command line for the shell
+pub(crate) fn check_shell(line: &str) -> Result<(), Refusal> {
+    let program = shell_words::split(line)
+        .ok()
+        .and_then(|v| v.into_iter().next())
+        .unwrap_or_default();
+
+    check(&program, line, true)
+}
+
+// a program run directly, without a shell
+pub(crate) fn check_exec(program: &str, arguments: &[String]) -> Result<(), Refusal> {
+    let line =
+        shell_words::join(std::iter::once(program).chain(arguments.iter().map(String::as_str)));
+    check(program, &line, false)
+}
+
+// a program about to be started some other way than `run` or `exec`
+pub(crate) fn check_command(process: &Command) -> Result<(), Refusal> {
+    let program = process.get_program().to_string_lossy();
+    let arguments = process
+        .get_args()
+        .map(|v| v.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+
+    check_exec(&program, &arguments)
+}
+
+// how many symlinks `resolve` follows, as many as Linux does before ELOOP
+const MAX_LINKS: usize = 40;
+
+// symlinks are followed as far as the path exists, dangling ones through
+// their target, and the rest is taken as written, so neither `..` nor a link
+// can lead out of a root; a link loop is left as it is, which nothing can
+// open
+fn resolve(path: &Path) -> PathBuf {
+    resolve_links(path, 0)
+}
+
+fn resolve_links(path: &Path, depth: usize) -> PathBuf {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().unwrap_or_default().join(path)
+    };
+
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+
+            Component::CurDir => {}
+            component => {
+                resolved.push(component);
+                if let Ok(real) = resolved.canonicalize() {
+                    resolved = real;
+                } else if depth < MAX_LINKS
+                    && resolved.symlink_metadata().is_ok_and(|v| v.is_symlink())
+                {
+                    if let Ok(target) = std::fs::read_link(&resolved) {
+                        resolved.pop();
+                        resolved = resolve_links(&resolved.join(target), depth + 1);
+                    }
+                }
+            }
+        }
+    }
+
+    resolved
+}
+
+// `security.fs.roots`, by default home and the temporary directory, where
+// `temp` puts the files tridactyl edits
+fn roots() -> Vec<PathBuf> {
+    let configured = config::get_strings("security.fs.roots");
+    let roots = if configured.is_empty() {
+        dirs::home_dir()
+            .into_iter()
+            .chain([temp_directory()])
+            .collect()
+    } else {
+        configured
+            .iter()
+            .map(|v| resolve_path(v))
+            .collect::<Vec<_>>()
+    };
+
+    roots.iter().map(|v| resolve(v)).collect()
+}
+
+// a glob is checked up to its first wildcard
+fn literal_prefix(pattern: &str) -> &str {
+    pattern
+        .find(['*', '?', '[', '{'])
+        .map_or(pattern, |v| &pattern[..v])
+}
+
+fn paths(kind: Kind, value: &Value) -> Vec<String> {
+    match (kind, value) {
+        (Kind::Glob, Value::String(path)) => vec![literal_prefix(path).to_string()],
+        (Kind::Profile, Value::String(path)) if path.is_empty() || path == "auto" => Vec::new(),
+        (_, Value::String(path)) => vec![path.clone()],
+        (_, Value::Array(paths)) => paths
+            .iter()
+            .filter_map(|v| v.as_str())
+            .map(String::from)
+            .collect(),
+        (_, Value::Object(files)) => files.keys().cloned().collect(),
+        _ => Vec::new(),
+    }
+}
+
+// refuses a path outside the roots, unless `security.fs.enabled` is false
+pub(crate) fn check_path(path: &Path) -> Result<(), Refusal> {
+    if config::get_bool("security.fs.enabled") == Some(false) {
+        return Ok(());
+    }
+
+    let resolved = resolve(path);
+    if roots().iter().any(|root| resolved.starts_with(root)) {
+        return Ok(());
+    }
+
+    Err(Refusal::Policy(format!(
+        "{} is outside security.fs.roots",
+        resolved.to_string_lossy()
+    )))
+}
+
+// refuses a command naming a path outside the roots in any of the fields
+// its registry row marks as paths
+pub(crate) fn check_paths(map: &Map<String, Value>) -> Option<Value> {
+    let name = map.get("cmd").and_then(|v| v.as_str())?;
+    let command = registry::find(name)?;
+
+    for field in command.fields.iter().filter(|v| v.kind.is_path()) {
+        let Some(value) = map.get(field.name) else {
+            continue;
+        };
+
+        for path in paths(field.kind, value) {
+            if let Err(refusal) = check_path(&resolve_path(&path)) {
+                return Some(denied(name, refusal));
+            }
+        }
+    }
+
+    None
+}
+
+// with `security.confirm.enabled`, overwriting a file outside home has to be
+// confirmed
+pub(crate) fn confirm_overwrite(map: &Map<String, Value>) -> Option<Value> {
+    let name = map.get("cmd").and_then(|v| v.as_str())?;
+    let (_, field) = DESTINATIONS.iter().find(|(command, _)| *command == name)?;
+    if !confirm::enabled() {
+        return None;
+    }
+
+    let overwrite = map.get("overwrite").and_then(|v| v.as_bool());
+    if matches!(name, "move" | "copy") && overwrite != Some(true) {
+        return None;
+    }
+
+    let home = dirs::home_dir().map(|v| resolve(&v));
+    for path in paths(Kind::Path, map.get(*field)?) {
+        let resolved = resolve(&resolve_path(&path));
+        if !resolved.exists() || home.as_ref().is_some_and(|v| resolved.starts_with(v)) {
+            continue;
+        }
+
+        let question = format!("Overwrite {}?", resolved.to_string_lossy());
+        if !confirm::ask(&question) {
+            return Some(denied(
+                name,
+                Refusal::User(format!("the user refused: {}", question)),
+            ));
+        }
+    }
+
+    None
+}
+
+// `--readonly` on the command line
+pub(crate) fn set_readonly() {
+    READONLY.store(true, Ordering::Relaxed);
+}
+
+pub(crate) fn is_readonly() -> bool {
+    READONLY.load(Ordering::Relaxed) || config::get_bool("readonly").unwrap_or(false)
+}
+
+// in readonly mode only commands that leave nothing behind go through
+pub(crate) fn check_readonly(map: &Map<String, Value>) -> Option<Value> {
+    let name = map.get("cmd").and_then(|v| v.as_str())?;
+
+    // a plugin is a program of the user's, which may do anything
+    let mutates = match registry::find(name) {
+        Some(command) => command.mutates.applies(map),
+        None => name.starts_with(plugins::PREFIX),
+    };
+
+    if !mutates || !is_readonly() {
+        return None;
+    }
+
+    Some(denied(
+        name,
+        Refusal::Policy(format!("{} is disabled in readonly mode", name)),
+    ))
+}
+
+pub(crate) fn shell_enabled() -> bool {
+    cfg!(feature = "shell") && config::get_bool("security.run.enabled").unwrap_or(true)
+}
+
+// whether `hello` should list the command
+pub(crate) fn is_available(name: &str) -> bool {
+    shell_enabled() || !SHELL_COMMANDS.contains(&name)
+}
+
+pub(crate) fn check_shell_enabled(map: &Map<String, Value>) -> Option<Value> {
+    let name = map.get("cmd").and_then(|v| v.as_str())?;
+    if is_available(name) {
+        return None;
+    }
+
+    let reason = if cfg!(feature = "shell") {
+        format!("{} is disabled by security.run.enabled", name)
+    } else {
+        format!("{} is not built in", name)
+    };
+
+    Some(denied(name, Refusal::Policy(reason)))
+}
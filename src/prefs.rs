@@ -0,0 +1,176 @@
+use std::path::Path;
+
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::{
+    commands::{write_atomic, SUCCESS_CODE},
+    config, profiles,
+};
+
+// user.js is applied over prefs.js on startup, so it wins
+const PREF_FILES: [&str; 2] = ["user.js", "prefs.js"];
+
+const PREF_LINE: &str =
+    r#"^\s*(?:user_)?pref\(\s*"((?:[^"\\]|\\.)*)"\s*,\s*("(?:[^"\\]|\\.)*"|[^\s)]+)\s*\)\s*;"#;
+
+// values are js literals, which for strings, numbers and booleans is json
+fn parse_value(value: &str) -> Value {
+    serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.into()))
+}
+
+fn pref_name(line: &str, regex: &Regex) -> Option<String> {
+    let captures = regex.captures(line)?;
+    match parse_value(&format!("\"{}\"", &captures[1])) {
+        Value::String(name) => Some(name),
+        _ => None,
+    }
+}
+
+pub(crate) fn find_pref(path: &Path, name: &str) -> Option<Value> {
+    let content = std::fs::read_to_string(path).ok()?;
+    let regex = Regex::new(PREF_LINE).unwrap();
+
+    // the last assignment is the one that sticks
+    content
+        .lines()
+        .rev()
+        .find(|line| pref_name(line, &regex).as_deref() == Some(name))
+        .and_then(|line| regex.captures(line))
+        .map(|captures| parse_value(&captures[2]))
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "._-[]@{}".contains(c))
+}
+
+// replaces every existing assignment of `name` with a single new one, in
+// place of the first, or appends it
+fn update_user_js(content: &str, name: &str, line: &str) -> String {
+    let regex = Regex::new(PREF_LINE).unwrap();
+    let mut replaced = false;
+    let mut lines = Vec::new();
+
+    for current in content.lines() {
+        if pref_name(current, &regex).as_deref() == Some(name) {
+            if !replaced {
+                lines.push(line.to_string());
+                replaced = true;
+            }
+        } else {
+            lines.push(current.to_string());
+        }
+    }
+
+    if !replaced {
+        lines.push(line.to_string());
+    }
+
+    lines.join("\n") + "\n"
+}
+
+pub(crate) fn setpref(name: &str, value: &Value, profile: Option<&str>) -> Value {
+    if config::get_bool("prefs.readonly").unwrap_or(false) {
+        error!("(prefs::setpref) Refusing to write, prefs.readonly is set");
+        return json!({
+            "cmd": "setpref",
+            "code": 1,
+            "error": "Preferences are read-only"
+        });
+    }
+
+    // only what user.js can hold, serialized by us so nothing can break out
+    // of the user_pref call
+    let valid_value = matches!(value, Value::String(_) | Value::Number(_) | Value::Bool(_));
+    if !is_valid_name(name) || !valid_value {
+        error!("(prefs::setpref) Invalid preference: {} = {}", name, value);
+        return json!({
+            "cmd": "setpref",
+            "code": 1,
+            "error": "Invalid preference name or value"
+        });
+    }
+
+    let Some(profile) = profiles::resolve_profile(profile) else {
+        error!("(prefs::setpref) Could not find a profile directory");
+        return json!({
+            "cmd": "setpref",
+            "code": 2,
+            "error": "No profile found"
+        });
+    };
+
+    let path = profile.join("user.js");
+    let existing = std::fs::read_to_string(&path).ok();
+
+    let result = (|| {
+        if existing.is_some() {
+            std::fs::copy(&path, profile.join("user.js.bak"))?;
+        }
+
+        let line = format!("user_pref({}, {});", Value::String(name.into()), value);
+        let content = update_user_js(existing.as_deref().unwrap_or_default(), name, &line);
+        write_atomic(&path, content.as_bytes(), None)
+    })();
+
+    match result {
+        Ok(()) => {
+            info!("(prefs::setpref) {} = {}", name, value);
+            json!({
+                "cmd": "setpref",
+                "code": SUCCESS_CODE,
+                "file": path,
+                "backup": existing.map(|_| profile.join("user.js.bak"))
+            })
+        }
+
+        Err(err) => {
+            error!("(prefs::setpref) Failed to write user.js, error: {}", err);
+            json!({
+                "cmd": "setpref",
+                "code": 2,
+                "error": err.to_string()
+            })
+        }
+    }
+}
+
+pub(crate) fn getpref(name: &str, profile: Option<&str>) -> Value {
+    let Some(profile) = profiles::resolve_profile(profile) else {
+        error!("(prefs::getpref) Could not find a profile directory");
+        return json!({
+            "cmd": "getpref",
+            "code": 2,
+            "error": "No profile found"
+        });
+    };
+
+    let result = PREF_FILES
+        .iter()
+        .find_map(|file| find_pref(&profile.join(file), name).map(|v| (*file, v)));
+
+    match result {
+        Some((file, value)) => {
+            info!("(prefs::getpref) {} from {}", name, file);
+            json!({
+                "cmd": "getpref",
+                "code": SUCCESS_CODE,
+                "content": value,
+                "source": file,
+                "profile": profile
+            })
+        }
+
+        None => {
+            info!("(prefs::getpref) {} is not set", name);
+            json!({
+                "cmd": "getpref",
+                "code": 1,
+                "profile": profile
+            })
+        }
+    }
+}
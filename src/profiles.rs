@@ -0,0 +1,226 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    commands::{resolve_path, SUCCESS_CODE},
+    manifest, restart,
+};
+
+type Section = (String, Vec<(String, String)>);
+
+pub(crate) fn parse_ini(content: &str) -> Vec<Section> {
+    let mut sections: Vec<Section> = Vec::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            sections.push((name.to_string(), Vec::new()));
+        } else if let (Some((key, value)), Some(section)) =
+            (line.split_once('='), sections.last_mut())
+        {
+            section
+                .1
+                .push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    sections
+}
+
+pub(crate) fn ini_value<'a>(section: &'a Section, key: &str) -> Option<&'a str> {
+    section
+        .1
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+}
+
+// worked out once, as it can take a powershell on windows
+fn launching_browsers() -> &'static [&'static str] {
+    static BROWSERS: OnceLock<Vec<&'static str>> = OnceLock::new();
+    BROWSERS.get_or_init(|| {
+        let binary = restart::browser_pid().and_then(restart::browser_binary);
+        let manifest = std::env::args().nth(1).map(PathBuf::from);
+        manifest::launching_browsers(binary.as_deref(), manifest.as_deref())
+    })
+}
+
+// the roots of the browser that started the host, so another one's
+// profile, such as thunderbird's, is not taken for its own
+pub(crate) fn profile_roots() -> Vec<PathBuf> {
+    let browsers = launching_browsers();
+    manifest::profile_roots()
+        .into_iter()
+        .filter(|(browser, _)| browsers.is_empty() || browsers.contains(browser))
+        .map(|(_, root)| root)
+        .collect()
+}
+
+// `IsRelative` is not always written, so a path that is not absolute is
+// taken as relative to the root either way
+fn profile_path(root: &Path, path: &str) -> PathBuf {
+    let path = Path::new(path);
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        root.join(path)
+    }
+}
+
+// a running browser holds `lock` (a dangling symlink on unix) or
+// `parent.lock` (windows, where it is only removable while unlocked)
+fn is_locked(profile: &Path) -> bool {
+    std::fs::symlink_metadata(profile.join("lock")).is_ok()
+        || (cfg!(windows) && profile.join("parent.lock").exists())
+}
+
+// the profile firefox would pick on its own: the install default, or the
+// profile marked `Default=1` by older versions
+fn default_profile(root: &Path) -> Option<PathBuf> {
+    let content = std::fs::read_to_string(root.join("profiles.ini")).ok()?;
+    let sections = parse_ini(&content);
+
+    let install = sections
+        .iter()
+        .filter(|section| section.0.starts_with("Install"))
+        .find_map(|section| ini_value(section, "Default"));
+
+    let legacy = || {
+        sections
+            .iter()
+            .filter(|section| section.0.starts_with("Profile"))
+            .find(|section| ini_value(section, "Default") == Some("1"))
+            .and_then(|section| ini_value(section, "Path"))
+    };
+
+    install
+        .or_else(legacy)
+        .map(|path| profile_path(root, path))
+        .filter(|path| path.is_dir())
+}
+
+// `-profile <dir>` on the browser's command line, the browser being the
+// process that started us
+#[cfg(target_os = "linux")]
+fn profile_from_parent() -> Option<PathBuf> {
+    let parent = std::os::unix::process::parent_id();
+    let cmdline = std::fs::read(format!("/proc/{}/cmdline", parent)).ok()?;
+    let arguments = cmdline
+        .split(|c| *c == 0)
+        .map(|v| String::from_utf8_lossy(v).to_string())
+        .collect::<Vec<_>>();
+
+    arguments
+        .windows(2)
+        .find(|pair| pair[0] == "-profile" || pair[0] == "--profile")
+        .map(|pair| PathBuf::from(&pair[1]))
+        .filter(|path| path.is_dir())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn profile_from_parent() -> Option<PathBuf> {
+    None
+}
+
+fn all_profiles(root: &Path) -> Vec<PathBuf> {
+    let Ok(content) = std::fs::read_to_string(root.join("profiles.ini")) else {
+        return Vec::new();
+    };
+
+    parse_ini(&content)
+        .iter()
+        .filter(|section| section.0.starts_with("Profile"))
+        .filter_map(|section| ini_value(section, "Path"))
+        .map(|path| profile_path(root, path))
+        .collect()
+}
+
+// the profile's name in `profiles.ini`, or its directory's name for one
+// that is not listed
+pub(crate) fn profile_name(profile: &Path) -> Option<String> {
+    let listed = profile_roots().into_iter().find_map(|root| {
+        let content = std::fs::read_to_string(root.join("profiles.ini")).ok()?;
+        parse_ini(&content)
+            .iter()
+            .filter(|section| section.0.starts_with("Profile"))
+            .find(|section| {
+                ini_value(section, "Path").is_some_and(|v| profile_path(&root, v) == profile)
+            })
+            .and_then(|section| ini_value(section, "Name"))
+            .map(String::from)
+    });
+
+    listed.or_else(|| Some(profile.file_name()?.to_string_lossy().to_string()))
+}
+
+// the browser's own `-profile`, else a profile that is locked by a running
+// browser, else the default one
+pub(crate) fn active_profile() -> Option<PathBuf> {
+    let roots = profile_roots();
+    profile_from_parent()
+        .or_else(|| {
+            roots
+                .iter()
+                .flat_map(|v| all_profiles(v))
+                .find(|v| is_locked(v))
+        })
+        .or_else(|| roots.iter().find_map(|v| default_profile(v)))
+}
+
+// an explicit directory from the message wins over detection, `auto` or
+// nothing means detect
+pub(crate) fn resolve_profile(explicit: Option<&str>) -> Option<PathBuf> {
+    match explicit {
+        Some(path) if !path.is_empty() && path != "auto" => {
+            Some(resolve_path(path)).filter(|v| v.is_dir())
+        }
+
+        _ => active_profile(),
+    }
+}
+
+pub(crate) fn profiles() -> Value {
+    let active = active_profile();
+    let mut result = Vec::new();
+
+    for (browser, root) in manifest::profile_roots() {
+        let Ok(content) = std::fs::read_to_string(root.join("profiles.ini")) else {
+            continue;
+        };
+
+        let default = default_profile(&root);
+
+        for section in parse_ini(&content)
+            .iter()
+            .filter(|section| section.0.starts_with("Profile"))
+        {
+            let Some(path) = ini_value(section, "Path") else {
+                continue;
+            };
+
+            let path = profile_path(&root, path);
+            result.push(json!({
+                "name": ini_value(section, "Name"),
+                "path": path,
+                "browser": browser,
+                "exists": path.is_dir(),
+                "default": default.as_ref() == Some(&path),
+                "locked": is_locked(&path),
+                "current": active.as_ref() == Some(&path)
+            }));
+        }
+    }
+
+    info!("(profiles::profiles) Found {} profiles", result.len());
+    json!({
+        "cmd": "profiles",
+        "code": SUCCESS_CODE,
+        "content": result
+    })
+}
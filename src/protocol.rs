@@ -0,0 +1,70 @@
+use std::{
+    io::{BufReader, BufWriter, Read, Stdin, Stdout, Write},
+    sync::Mutex,
+};
+
+use serde_json::Value;
+
+use crate::shims;
+
+// replies and events come from several threads; the lock keeps their frames
+// whole, and the buffer sends each one out in a single write
+static WRITER: Mutex<Option<BufWriter<Stdout>>> = Mutex::new(None);
+
+pub(crate) fn input() -> BufReader<Stdin> {
+    BufReader::new(std::io::stdin())
+}
+
+// the largest frame read from the browser; Firefox would send up to 4 GiB,
+// far more than any request needs and enough to exhaust memory
+pub(crate) const MAX_FRAME: u32 = 64 * 1024 * 1024;
+
+// a frame is the length of the json as a native-endian u32, then the json;
+// `Ok(None)` for an empty frame, `InvalidData` for a frame that is too large
+// or not json, which leaves the stream at the next frame, and any other `Err`
+// once the browser closed our stdin
+pub(crate) fn read_frame(reader: &mut impl Read) -> std::io::Result<Option<Value>> {
+    let mut header = [0u8; 4];
+    reader.read_exact(&mut header)?;
+
+    let length = u32::from_ne_bytes(header);
+    if length == 0 {
+        return Ok(None);
+    }
+
+    debug!("Received message from client with length of {}", length);
+
+    if length > MAX_FRAME {
+        let skipped = std::io::copy(&mut reader.take(length.into()), &mut std::io::sink())?;
+        if skipped < length.into() {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "Message of {} bytes is over the limit of {}",
+                length, MAX_FRAME
+            ),
+        ));
+    }
+
+    let mut body = vec![0u8; length as usize];
+    reader.read_exact(&mut body)?;
+
+    let json = serde_json::from_slice(&body)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+    Ok(Some(json))
+}
+
+// every frame goes through the shims, replies and events alike
+pub(crate) fn write_frame(json: &Value) -> std::io::Result<()> {
+    let body = shims::apply(json.clone()).to_string();
+
+    let mut writer = WRITER.lock().unwrap();
+    let writer = writer.get_or_insert_with(|| BufWriter::new(std::io::stdout()));
+    writer.write_all(&(body.len() as u32).to_ne_bytes())?;
+    writer.write_all(body.as_bytes())?;
+    writer.flush()
+}
@@ -0,0 +1,361 @@
+use serde_json::Value;
+
+use crate::policy;
+
+#[cfg(unix)]
+mod unix {
+    use std::{
+        collections::HashMap,
+        fs::File,
+        io::{Read, Write},
+        os::fd::{AsRawFd, FromRawFd, OwnedFd},
+        os::unix::process::{CommandExt, ExitStatusExt},
+        process::{Command, Stdio},
+        sync::Mutex,
+    };
+
+    use serde_json::{json, Value};
+
+    use crate::{
+        children,
+        commands::{resolve_path, shell, SUCCESS_CODE},
+        events,
+    };
+
+    struct Session {
+        master: File,
+        pid: u32,
+    }
+
+    static SESSIONS: Mutex<Option<HashMap<String, Session>>> = Mutex::new(None);
+
+    fn key(id: &Value) -> String {
+        match id {
+            Value::String(id) => id.clone(),
+            id => id.to_string(),
+        }
+    }
+
+    fn window_size(rows: u16, cols: u16) -> libc::winsize {
+        libc::winsize {
+            ws_row: rows,
+            ws_col: cols,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        }
+    }
+
+    fn open_pty(rows: u16, cols: u16) -> std::io::Result<(OwnedFd, OwnedFd)> {
+        let mut master = 0;
+        let mut slave = 0;
+        let size = window_size(rows, cols);
+
+        let result = unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                &size,
+            )
+        };
+
+        if result != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        Ok(unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) })
+    }
+
+    // terminal output is split at arbitrary byte offsets, so keep any trailing
+    // partial utf-8 sequence around for the next read
+    fn decode(pending: &mut Vec<u8>) -> String {
+        let valid = match std::str::from_utf8(pending) {
+            Ok(_) => pending.len(),
+            Err(err) if err.error_len().is_none() => err.valid_up_to(),
+            Err(_) => pending.len(),
+        };
+
+        let rest = pending.split_off(valid);
+        let text = String::from_utf8_lossy(pending).to_string();
+        *pending = rest;
+
+        text
+    }
+
+    pub(super) fn run_pty(
+        command: &str,
+        cwd: Option<&str>,
+        rows: u16,
+        cols: u16,
+        id: Value,
+    ) -> Value {
+        let (master, slave) = match open_pty(rows, cols) {
+            Ok(pair) => pair,
+            Err(err) => {
+                error!("(pty::run_pty) Failed to open a pty, error: {}", err);
+                return json!({
+                    "cmd": "run_pty",
+                    "code": 2,
+                    "id": id
+                });
+            }
+        };
+
+        let stdio = || slave.try_clone().map(Stdio::from);
+        let (Ok(stdin), Ok(stdout), Ok(stderr)) = (stdio(), stdio(), stdio()) else {
+            return json!({
+                "cmd": "run_pty",
+                "code": 2,
+                "id": id
+            });
+        };
+
+        let mut process = Command::new(shell());
+        process
+            .arg("-c")
+            .arg(command)
+            .env("TERM", "xterm-256color")
+            .stdin(stdin)
+            .stdout(stdout)
+            .stderr(stderr);
+
+        if let Some(cwd) = cwd {
+            process.current_dir(resolve_path(cwd));
+        }
+
+        // give the child its own session with the pty as controlling terminal
+        unsafe {
+            process.pre_exec(|| {
+                if libc::setsid() == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+
+                if libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+
+                Ok(())
+            });
+        }
+
+        let mut child = match process.spawn() {
+            Ok(child) => child,
+            Err(err) => {
+                error!(
+                    "(pty::run_pty) Failed to run process: '{}', error: {}",
+                    command, err
+                );
+
+                return json!({
+                    "cmd": "run_pty",
+                    "code": 2,
+                    "id": id
+                });
+            }
+        };
+
+        drop(process);
+        drop(slave);
+
+        let pid = child.id();
+        let id = if id.is_null() { json!(pid) } else { id };
+        children::register(pid, "run_pty", command);
+        let master = File::from(master);
+
+        let (Ok(mut reader), Ok(writer)) = (master.try_clone(), master.try_clone()) else {
+            let _ = child.kill();
+            let _ = child.wait();
+            children::unregister(pid);
+
+            return json!({
+                "cmd": "run_pty",
+                "code": 2,
+                "id": id
+            });
+        };
+
+        SESSIONS
+            .lock()
+            .unwrap()
+            .get_or_insert_with(HashMap::new)
+            .insert(
+                key(&id),
+                Session {
+                    master: writer,
+                    pid,
+                },
+            );
+
+        info!(
+            "(pty::run_pty) Ran process: '{}', successfully, pid: {}",
+            command, pid
+        );
+
+        let reply = events::pending_reply();
+        let event_id = id.clone();
+        std::thread::spawn(move || {
+            events::wait_for_reply(reply);
+
+            let mut buffer = [0u8; 4096];
+            let mut pending = Vec::new();
+
+            // reading fails with EIO once the last slave descriptor is closed
+            while let Ok(length) = reader.read(&mut buffer) {
+                if length == 0 {
+                    break;
+                }
+
+                pending.extend_from_slice(&buffer[..length]);
+                let data = decode(&mut pending);
+                if !data.is_empty() {
+                    events::emit(&json!({
+                        "cmd": "run_pty",
+                        "id": event_id,
+                        "event": "output",
+                        "data": data
+                    }));
+                }
+            }
+
+            let status = child.wait().ok();
+            children::unregister(pid);
+
+            if let Some(sessions) = SESSIONS.lock().unwrap().as_mut() {
+                sessions.remove(&key(&event_id));
+            }
+
+            let exit_code = status.and_then(|v| v.code());
+            let signal = status.and_then(|v| v.signal());
+
+            events::emit(&json!({
+                "cmd": "run_pty",
+                "id": event_id,
+                "event": "exit",
+                "code": exit_code.or(signal.map(|v| 128 + v)).unwrap_or(2),
+                "exit_code": exit_code,
+                "signaled": signal.is_some(),
+                "signal": signal
+            }));
+        });
+
+        json!({
+            "cmd": "run_pty",
+            "code": SUCCESS_CODE,
+            "id": id,
+            "pid": pid
+        })
+    }
+
+    pub(super) fn pty_input(id: &Value, data: &str) -> Value {
+        let mut sessions = SESSIONS.lock().unwrap();
+        let Some(session) = sessions.as_mut().and_then(|v| v.get_mut(&key(id))) else {
+            return json!({
+                "cmd": "pty_input",
+                "code": 1
+            });
+        };
+
+        let code = match session.master.write_all(data.as_bytes()) {
+            Ok(_) => SUCCESS_CODE,
+            Err(err) => {
+                error!(
+                    "(pty::pty_input) Failed to write to pid: {}, error: {}",
+                    session.pid, err
+                );
+
+                2
+            }
+        };
+
+        json!({
+            "cmd": "pty_input",
+            "code": code
+        })
+    }
+
+    pub(super) fn pty_resize(id: &Value, rows: u16, cols: u16) -> Value {
+        let sessions = SESSIONS.lock().unwrap();
+        let Some(session) = sessions.as_ref().and_then(|v| v.get(&key(id))) else {
+            return json!({
+                "cmd": "pty_resize",
+                "code": 1
+            });
+        };
+
+        // the kernel delivers SIGWINCH to the foreground process group itself
+        let size = window_size(rows, cols);
+        let result =
+            unsafe { libc::ioctl(session.master.as_raw_fd(), libc::TIOCSWINSZ as _, &size) };
+
+        json!({
+            "cmd": "pty_resize",
+            "code": if result == 0 { SUCCESS_CODE } else { 2 }
+        })
+    }
+}
+
+const DEFAULT_ROWS: u16 = 24;
+const DEFAULT_COLS: u16 = 80;
+
+#[cfg(not(unix))]
+fn unsupported(name: &str) -> Value {
+    error!("(pty::{}) Pseudo-terminals are not supported here", name);
+    serde_json::json!({
+        "cmd": name,
+        "code": 1
+    })
+}
+
+pub(crate) fn run_pty(
+    command: &str,
+    cwd: Option<&str>,
+    rows: Option<u16>,
+    cols: Option<u16>,
+    id: Value,
+) -> Value {
+    if let Err(refusal) = policy::check_shell(command) {
+        return policy::denied("run_pty", refusal);
+    }
+
+    #[cfg(unix)]
+    return unix::run_pty(
+        command,
+        cwd,
+        rows.unwrap_or(DEFAULT_ROWS),
+        cols.unwrap_or(DEFAULT_COLS),
+        id,
+    );
+
+    #[cfg(not(unix))]
+    {
+        let _ = (command, cwd, rows, cols, id);
+        unsupported("run_pty")
+    }
+}
+
+pub(crate) fn pty_input(id: &Value, data: &str) -> Value {
+    #[cfg(unix)]
+    return unix::pty_input(id, data);
+
+    #[cfg(not(unix))]
+    {
+        let _ = (id, data);
+        unsupported("pty_input")
+    }
+}
+
+pub(crate) fn pty_resize(id: &Value, rows: Option<u16>, cols: Option<u16>) -> Value {
+    #[cfg(unix)]
+    return unix::pty_resize(
+        id,
+        rows.unwrap_or(DEFAULT_ROWS),
+        cols.unwrap_or(DEFAULT_COLS),
+    );
+
+    #[cfg(not(unix))]
+    {
+        let _ = (id, rows, cols);
+        unsupported("pty_resize")
+    }
+}
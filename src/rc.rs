@@ -0,0 +1,128 @@
+use std::path::{Path, PathBuf};
+
+use regex::{Captures, Regex};
+
+use crate::{commands::resolve_path, sysinfo};
+
+// deep enough for any sane split, shallow enough to stop a runaway chain
+const MAX_DEPTH: usize = 16;
+const VARIABLE: &str = r"\$\{([A-Za-z_][A-Za-z0-9_]*)\}";
+const PLACEHOLDER: &str = r"%\{([a-z_]+)\}";
+
+// `source <path>` with a path; tridactyl's own `source` without one (or with
+// a flag such as `-url`) is left for the extension
+fn include_target(line: &str) -> Option<&str> {
+    let argument = line.trim().strip_prefix("source")?;
+    if !argument.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let argument = argument.trim().trim_matches(['"', '\'']);
+    (!argument.is_empty() && !argument.starts_with('-')).then_some(argument)
+}
+
+// relative includes are relative to the file including them
+fn resolve(target: &str, including: &Path) -> PathBuf {
+    let path = resolve_path(target);
+    if path.is_absolute() {
+        return path;
+    }
+
+    including.parent().map_or(path.clone(), |v| v.join(&path))
+}
+
+fn inline(
+    path: &Path,
+    chain: &mut Vec<PathBuf>,
+    includes: &mut Vec<PathBuf>,
+) -> Result<String, (u8, String)> {
+    let unreadable = |e: std::io::Error| (2, format!("{}: {}", path.to_string_lossy(), e));
+    let canonical = path.canonicalize().map_err(unreadable)?;
+
+    if let Some(start) = chain.iter().position(|v| *v == canonical) {
+        let cycle = chain[start..]
+            .iter()
+            .chain([&canonical])
+            .map(|v| v.to_string_lossy())
+            .collect::<Vec<_>>();
+        return Err((1, format!("include cycle: {}", cycle.join(" -> "))));
+    }
+
+    if chain.len() > MAX_DEPTH {
+        return Err((
+            1,
+            format!(
+                "includes nested deeper than {} at {}",
+                MAX_DEPTH,
+                path.to_string_lossy()
+            ),
+        ));
+    }
+
+    let content = std::fs::read_to_string(&canonical).map_err(unreadable)?;
+    if !chain.is_empty() {
+        includes.push(canonical.clone());
+    }
+
+    if !content.lines().any(|v| include_target(v).is_some()) {
+        return Ok(content);
+    }
+
+    chain.push(canonical);
+    let mut result = String::new();
+    for line in content.lines() {
+        match include_target(line) {
+            Some(target) => {
+                result += &inline(&resolve(target, path), chain, includes)?;
+            }
+
+            None => {
+                result += line;
+                result.push('\n');
+            }
+        }
+    }
+
+    chain.pop();
+    Ok(result)
+}
+
+// the rc with every `source <path>` line replaced by that file, recursively;
+// returns the content and the files that were included, or a reply code and
+// what went wrong
+pub(crate) fn preprocess(path: &Path) -> Result<(String, Vec<PathBuf>), (u8, String)> {
+    let mut includes = Vec::new();
+    let content = inline(path, &mut Vec::new(), &mut includes)?;
+    Ok((content, includes))
+}
+
+fn placeholder(name: &str) -> Option<String> {
+    match name {
+        "hostname" => sysinfo::hostname(),
+        "user" => std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .ok(),
+        "home" => dirs::home_dir().map(|v| v.to_string_lossy().to_string()),
+        "os" => Some(std::env::consts::OS.into()),
+        "arch" => Some(std::env::consts::ARCH.into()),
+        "desktop" => sysinfo::desktop(),
+        _ => None,
+    }
+}
+
+// `${VAR}` from the environment and `%{name}` from the machine; anything
+// unknown is left as written, so a typo shows up in tridactyl
+pub(crate) fn template(content: &str) -> String {
+    let content = Regex::new(VARIABLE)
+        .unwrap()
+        .replace_all(content, |captures: &Captures| {
+            std::env::var(&captures[1]).unwrap_or(captures[0].to_string())
+        });
+
+    Regex::new(PLACEHOLDER)
+        .unwrap()
+        .replace_all(&content, |captures: &Captures| {
+            placeholder(&captures[1]).unwrap_or(captures[0].to_string())
+        })
+        .to_string()
+}
@@ -0,0 +1,542 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    capabilities,
+    commands::{SUCCESS_CODE, VERSION},
+    plugins, policy, shims,
+};
+
+pub(crate) const PROTOCOL_VERSION: u64 = 1;
+
+// fields every message may carry regardless of the command
+const COMMON_FIELDS: [&str; 2] = ["cmd", "id"];
+
+// json types, as used by the schema; the path kinds are strings, arrays and
+// objects whose paths `security.fs.roots` confines
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Kind {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    Array,
+    Object,
+    Any,
+    Path,
+    Paths,
+    // checked up to its first wildcard
+    Glob,
+    // an object keyed by paths
+    PathKeys,
+    // a profile directory, where "auto" or nothing means the detected one
+    Profile,
+}
+
+impl Kind {
+    pub(crate) fn is_path(self) -> bool {
+        matches!(
+            self,
+            Kind::Path | Kind::Paths | Kind::Glob | Kind::PathKeys | Kind::Profile
+        )
+    }
+
+    // replies use null for anything that does not apply
+    fn schema(self, nullable: bool) -> Value {
+        let name = match self {
+            Kind::String | Kind::Path | Kind::Glob | Kind::Profile => "string",
+            Kind::Integer => "integer",
+            Kind::Number => "number",
+            Kind::Boolean => "boolean",
+            Kind::Array | Kind::Paths => "array",
+            Kind::Object | Kind::PathKeys => "object",
+            Kind::Any => return json!({}),
+        };
+
+        if nullable {
+            json!({ "type": [name, "null"] })
+        } else {
+            json!({ "type": name })
+        }
+    }
+}
+
+// a `redacted` field's value stays out of the audit log; an object keyed by
+// paths keeps its keys
+pub(crate) struct Field {
+    pub(crate) name: &'static str,
+    pub(crate) kind: Kind,
+    pub(crate) redacted: bool,
+}
+
+// whether a command changes files, runs programs or otherwise leaves
+// something behind, which `--readonly` refuses; `When` is for commands that
+// only do with a field set (to anything but null or false), or set to a value
+pub(crate) enum Mutates {
+    Never,
+    Always,
+    When(&'static str, Option<&'static str>),
+}
+
+impl Mutates {
+    pub(crate) fn applies(&self, map: &Map<String, Value>) -> bool {
+        match self {
+            Mutates::Never => false,
+            Mutates::Always => true,
+            Mutates::When(field, None) => map
+                .get(*field)
+                .is_some_and(|v| !v.is_null() && *v != Value::Bool(false)),
+            Mutates::When(field, Some(value)) => {
+                map.get(*field).and_then(|v| v.as_str()) == Some(*value)
+            }
+        }
+    }
+}
+
+// `fields` are what a request may carry, `reply` what a successful reply may
+// carry besides `cmd` and `code`
+pub(crate) struct Command {
+    pub(crate) name: &'static str,
+    pub(crate) fields: &'static [Field],
+    pub(crate) reply: &'static [Field],
+    pub(crate) mutates: Mutates,
+}
+
+macro_rules! redacted {
+    () => {
+        false
+    };
+    (redacted) => {
+        true
+    };
+}
+
+// a row starts with `mutating` for a command that always mutates, or with
+// `mutating("field")` or `mutating("field" = "value")` for one that only
+// does with that field set
+macro_rules! command {
+    (
+        @$mutates:expr; $name:literal $(, $field:literal: $kind:ident $($redacted:ident)?)*
+        $(=> $($reply:literal: $reply_kind:ident),*)? $(,)?
+    ) => {
+        Command {
+            name: $name,
+            mutates: $mutates,
+            fields: &[$(Field {
+                name: $field,
+                kind: Kind::$kind,
+                redacted: redacted!($($redacted)?),
+            }),*],
+            reply: &[$($(Field {
+                name: $reply,
+                kind: Kind::$reply_kind,
+                redacted: false,
+            }),*)?],
+        }
+    };
+    (mutating($field:literal = $value:literal) $($row:tt)*) => {
+        command!(@Mutates::When($field, Some($value)); $($row)*)
+    };
+    (mutating($field:literal) $($row:tt)*) => {
+        command!(@Mutates::When($field, None); $($row)*)
+    };
+    (mutating $($row:tt)*) => {
+        command!(@Mutates::Always; $($row)*)
+    };
+    ($($row:tt)*) => {
+        command!(@Mutates::Never; $($row)*)
+    };
+}
+
+pub(crate) const COMMANDS: &[Command] = &[
+    command!(
+        "hello", "strict": Boolean, "protocol": Integer
+        => "version": String, "protocol": Integer, "strict": Boolean, "readonly": Boolean,
+           "commands": Array, "plugins": Array, "plugin_capabilities": Object, "shims": Array,
+           "available_shims": Array, "capabilities": Object
+    ),
+    command!("capabilities", "refresh": Boolean => "content": Object, "probed": Integer),
+    command!("version" => "version": String, "config_version": Integer, "migrations": Array),
+    command!("health" => "pid": Integer, "uptime": Integer, "maintenance": Array),
+    command!("env", "var": String => "content": Any),
+    command!("which", "name": String => "content": String),
+    command!(
+        "net_check", "host": String, "port": Integer, "url": String, "timeout": Integer
+        => "reachable": Boolean, "address": String, "connect": Number, "latency": Number,
+           "status": Integer
+    ),
+    command!(
+        "sysinfo"
+        => "os": String, "family": String, "arch": String, "kernel": String, "distribution": String,
+           "desktop": String, "display_server": String, "clipboard": String, "version": String
+    ),
+    command!(mutating "set_loglevel", "level": String => "level": String, "previous": String),
+    command!("audit_tail", "lines": Integer => "content": Array, "file": String),
+    command!("ppid" => "content": Integer),
+    command!(
+        "getconfig", "profile": String
+        => "content": String, "includes": Array, "profile": String
+    ),
+    command!(
+        "getconfigpath", "profile": String, "resolve": Boolean
+        => "content": String, "source": String, "profile": String
+    ),
+    command!("read", "file": Path, "encoding": String => "content": String, "encoding": String),
+    command!("read_many", "files": Paths, "glob": Glob, "encoding": String => "content": Object),
+    command!(
+        "read_table", "file": Path, "delimiter": String, "header": Boolean, "limit": Integer
+        => "headers": Array, "rows": Array, "truncated": Boolean
+    ),
+    command!(mutating "write", "file": Path, "content": String redacted),
+    command!(mutating "write_many", "files": PathKeys redacted => "files": Array, "errors": Object),
+    command!(mutating "writerc", "file": Path, "force": Boolean, "content": String redacted),
+    command!(
+        mutating "move", "from": Path, "to": Path, "overwrite": Boolean, "cleanup": Boolean,
+        "scan": Boolean
+        => "scan": Object
+    ),
+    command!(
+        mutating "copy", "from": Path, "to": Path, "overwrite": Boolean, "resume": Boolean,
+        "xattrs": Boolean
+        => "file": String, "bytes": Integer, "resumed": Integer, "unpreserved": Array
+    ),
+    command!("mimetype", "file": Path => "content": String, "source": String),
+    command!(
+        "hash_many", "files": Paths, "dir": Path, "recursive": Boolean, "algorithm": String
+        => "algorithm": String, "content": Array, "duplicates": Array
+    ),
+    command!(mutating "mkdir", "dir": Path),
+    command!(
+        "list_dir", "path": Path, "sort": String, "show_hidden": Boolean
+        => "files": Array, "entries": Array, "sep": String
+    ),
+    command!(
+        mutating "temp", "prefix": String, "content": String redacted, "dir": Path
+        => "content": String
+    ),
+    command!(
+        mutating "editor", "content": String redacted, "prefix": String, "extension": String,
+        "line": Integer, "column": Integer
+        => "content": String, "exit_code": Integer
+    ),
+    #[cfg(feature = "shell")]
+    command!(
+        mutating "run", "command": String redacted, "content": String redacted, "cwd": String,
+        "nice": Integer, "max_output": Integer, "cpu_limit": Integer, "memory_limit": Integer
+        => "result": String, "stderr": String, "exit_code": Integer, "signaled": Boolean,
+           "signal": Integer, "truncated": Boolean
+    ),
+    #[cfg(feature = "shell")]
+    command!(
+        mutating "run_async", "command": String redacted, "cwd": String, "mode": String,
+        "detach": Boolean
+        => "job": Integer, "pid": Integer
+    ),
+    #[cfg(feature = "shell")]
+    command!(
+        mutating "run_stream", "command": String redacted, "content": String redacted, "cwd": String
+        => "pid": Integer
+    ),
+    #[cfg(feature = "shell")]
+    command!(
+        mutating "run_pty", "command": String redacted, "cwd": String, "rows": Integer,
+        "cols": Integer
+        => "pid": Integer
+    ),
+    command!(mutating "pty_input", "data": String redacted),
+    command!("pty_resize", "rows": Integer, "cols": Integer),
+    #[cfg(feature = "shell")]
+    command!(
+        mutating "exec", "program": String, "args": Array redacted, "content": String redacted,
+        "cwd": String, "nice": Integer, "max_output": Integer, "cpu_limit": Integer,
+        "memory_limit": Integer
+        => "result": String, "stderr": String, "exit_code": Integer, "signaled": Boolean,
+           "signal": Integer, "truncated": Boolean
+    ),
+    command!(mutating "kill", "pid": Integer, "signal": String),
+    command!(mutating "fix_manifest" => "content": Array),
+    command!("profiles" => "content": Array),
+    command!(
+        "history_query", "query": String, "sort": String, "limit": Integer, "profiledir": Profile
+        => "content": Array, "profile": String
+    ),
+    command!(
+        "getpref", "name": String, "profiledir": Profile
+        => "content": Any, "source": String, "profile": String
+    ),
+    command!(
+        mutating "setpref", "name": String, "value": Any redacted, "profiledir": Profile
+        => "file": String, "backup": String
+    ),
+    command!(
+        mutating "session_backup", "dir": Path, "profiledir": Profile
+        => "dir": String, "files": Array, "profile": String
+    ),
+    command!(mutating "restart", "profiledir": Profile => "pid": Integer, "command": Array),
+    command!("secret", "attributes": Object => "content": String),
+    command!(
+        mutating "encrypt_file", "file": Path, "output": Path, "key": String, "recipients": Array
+        => "file": String, "key": String, "recipient": String
+    ),
+    command!(
+        mutating("output") "decrypt_file", "file": Path, "output": Path
+        => "file": String, "content": String
+    ),
+    command!("rewrite_url", "url": String => "url": String, "rewritten": Boolean, "rule": String),
+    command!(mutating "open", "target": String => "pid": Integer),
+    command!(
+        mutating "route_open", "url": String
+        => "routed": Boolean, "route": Integer, "handler": String, "pid": Integer
+    ),
+    command!(
+        "pipeline_run", "name": String, "args": Object redacted
+        => "pipeline": String, "results": Array, "failed_step": Integer
+    ),
+    command!("jobs_list" => "content": Array),
+    command!(
+        "job_status", "job": Integer
+        => "job": Integer, "pid": Integer, "command": String, "started": Integer, "state": String,
+           "exit_code": Integer, "signal": Integer
+    ),
+    command!(
+        "job_output", "job": Integer
+        => "job": Integer, "stdout": String, "stderr": String, "stdout_dropped": Integer,
+           "stderr_dropped": Integer
+    ),
+    command!(mutating "job_kill", "job": Integer, "signal": String),
+    command!(
+        mutating "focus_start", "duration": Integer, "label": String, "block": Array
+        => "session": Object
+    ),
+    command!(mutating "focus_stop"),
+    command!("focus_status" => "active": Boolean, "session": Object),
+    command!("should_block", "domains": Array => "active": Boolean, "content": Object),
+    command!(
+        mutating "tt_start", "label": String, "url": String
+        => "label": String, "started": Integer, "previous": Object
+    ),
+    command!(mutating "tt_stop" => "entry": Object),
+    command!(
+        "tt_report", "since": String
+        => "content": Array, "total": Integer, "running": Object, "file": String
+    ),
+    command!(
+        mutating "timer_start", "duration": Integer, "label": String, "notify": Boolean
+        => "timer": Integer
+    ),
+    command!("timer_list" => "content": Array),
+    command!(mutating "timer_cancel", "timer": Integer => "timer": Integer),
+    command!(
+        mutating("write") "sqlite_query", "db": String, "query": String, "params": Array,
+        "write": Boolean, "limit": Integer
+        => "columns": Array, "rows": Array, "truncated": Boolean
+    ),
+    command!(
+        "zoxide_query", "query": String, "limit": Integer
+        => "content": Array, "source": String
+    ),
+    command!(
+        "shell_history", "shell": String, "query": String, "sort": String, "limit": Integer
+        => "content": Array
+    ),
+    command!(
+        "help_lookup", "program": String, "source": String, "section": String
+        => "content": String, "source": String
+    ),
+    command!("note_get", "key": String => "domain": String, "file": String, "content": String),
+    command!(
+        mutating "note_set", "key": String, "content": String redacted, "append": Boolean
+        => "domain": String, "file": String
+    ),
+    command!("note_search", "query": String, "limit": Integer => "content": Array),
+    command!(
+        mutating("action" = "set") "clipboard", "action": String, "content": String redacted
+        => "content": String, "tool": String
+    ),
+    command!("pick", "choices": Array, "prompt": String => "content": String, "index": Integer),
+    command!("cliphist_list", "query": String, "limit": Integer => "content": Array),
+    command!("cliphist_get", "entry": Integer => "content": String, "timestamp": Integer),
+    command!("pick_pixel" => "hex": String, "rgb": Array, "tool": String),
+];
+
+static STRICT: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn find(name: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|command| command.name == name)
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b) in b.iter().enumerate() {
+            let cost = usize::from(a != *b);
+            current[j + 1] = (previous[j] + cost)
+                .min(previous[j + 1] + 1)
+                .min(current[j] + 1);
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+// the closest candidate, as long as it is near enough to plausibly be a typo
+fn suggest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let threshold = (name.chars().count() / 3).max(2);
+
+    candidates
+        .map(|candidate| (levenshtein(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= threshold)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+pub(crate) fn is_strict() -> bool {
+    STRICT.load(Ordering::Relaxed)
+}
+
+// checks a message against the registry, only consulted in strict mode
+pub(crate) fn validate(map: &Map<String, Value>) -> Option<Value> {
+    let name = map.get("cmd").and_then(|v| v.as_str()).unwrap_or_default();
+
+    // plugins define their own fields
+    if plugins::exists(name) {
+        return None;
+    }
+
+    let Some(command) = find(name) else {
+        let suggestion = suggest(name, COMMANDS.iter().map(|v| v.name));
+        error!(
+            "(registry::validate) Unknown command: '{}', suggestion: {:?}",
+            name, suggestion
+        );
+
+        return Some(json!({
+            "cmd": "error",
+            "code": 1,
+            "error": format!("Unknown command '{}'", name),
+            "suggestion": suggestion
+        }));
+    };
+
+    let unknown = map
+        .keys()
+        .filter(|key| {
+            !COMMON_FIELDS.contains(&key.as_str())
+                && !command.fields.iter().any(|v| v.name == key.as_str())
+        })
+        .collect::<Vec<_>>();
+
+    if unknown.is_empty() {
+        return None;
+    }
+
+    let suggestions = unknown
+        .iter()
+        .filter_map(|key| {
+            let suggestion = suggest(key, command.fields.iter().map(|v| v.name))?;
+            Some((key.to_string(), Value::String(suggestion.into())))
+        })
+        .collect::<Map<_, _>>();
+
+    error!(
+        "(registry::validate) Unexpected fields for '{}': {:?}",
+        name, unknown
+    );
+
+    Some(json!({
+        "cmd": name,
+        "code": 1,
+        "error": format!(
+            "Unexpected fields: {}",
+            unknown.iter().map(|v| v.as_str()).collect::<Vec<_>>().join(", ")
+        ),
+        "suggestions": suggestions
+    }))
+}
+
+pub(crate) fn hello(strict: bool, protocol: Option<u64>) -> Value {
+    STRICT.store(strict, Ordering::Relaxed);
+    shims::set_client_protocol(protocol);
+
+    info!(
+        "(registry::hello) strict: {}, client protocol: {:?}",
+        strict, protocol
+    );
+
+    json!({
+        "cmd": "hello",
+        "code": SUCCESS_CODE,
+        "version": VERSION,
+        "protocol": PROTOCOL_VERSION,
+        "strict": strict,
+        "readonly": policy::is_readonly(),
+        "commands": COMMANDS
+            .iter()
+            .map(|v| v.name)
+            .filter(|v| policy::is_available(v))
+            .collect::<Vec<_>>(),
+        "plugins": plugins::list(),
+        "plugin_capabilities": plugins::capabilities(),
+        "shims": shims::active(),
+        "available_shims": shims::names(),
+        "capabilities": capabilities::cached().1
+    })
+}
+
+fn object_schema(properties: Map<String, Value>, required: &[&str], closed: bool) -> Value {
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": !closed
+    })
+}
+
+// a json schema of every request and reply, built from the table above
+pub(crate) fn schema() -> Value {
+    let mut definitions = Map::new();
+    for command in COMMANDS {
+        let mut request = Map::new();
+        request.insert("cmd".into(), json!({ "const": command.name }));
+        request.insert("id".into(), json!({}));
+        for field in command.fields {
+            request.insert(field.name.into(), field.kind.schema(false));
+        }
+
+        let mut reply = Map::new();
+        reply.insert("cmd".into(), json!({ "type": "string" }));
+        reply.insert("code".into(), json!({ "type": "integer" }));
+        reply.insert("error".into(), json!({ "type": "string" }));
+        for field in command.reply {
+            reply.insert(field.name.into(), field.kind.schema(true));
+        }
+
+        definitions.insert(
+            format!("{}.request", command.name),
+            object_schema(request, &["cmd"], true),
+        );
+
+        definitions.insert(
+            format!("{}.reply", command.name),
+            object_schema(reply, &["cmd", "code"], false),
+        );
+    }
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "tridactyl native messaging protocol",
+        "version": VERSION,
+        "protocol": PROTOCOL_VERSION,
+        "oneOf": COMMANDS
+            .iter()
+            .map(|v| json!({ "$ref": format!("#/$defs/{}.request", v.name) }))
+            .collect::<Vec<_>>(),
+        "$defs": definitions
+    })
+}
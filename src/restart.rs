@@ -0,0 +1,176 @@
+use std::{path::PathBuf, process::Command, time::Duration};
+
+use serde_json::{json, Value};
+
+use crate::{
+    commands::{parse_signal, send_signal, spawn_detached, Pid, SUCCESS_CODE},
+    config, events, profiles,
+};
+
+// time the browser gets to hand the reply to the extension before it is
+// asked to quit
+const REPLY_GRACE: Duration = Duration::from_millis(500);
+
+// waits for the browser to be gone, then starts it again
+#[cfg(unix)]
+const RELAUNCHER: &str =
+    r#"pid=$1; shift; while kill -0 "$pid" 2>/dev/null; do sleep 0.2; done; exec "$@""#;
+
+#[cfg(unix)]
+pub(crate) fn browser_pid() -> Option<u32> {
+    Some(std::os::unix::process::parent_id())
+}
+
+#[cfg(windows)]
+pub(crate) fn browser_pid() -> Option<u32> {
+    let script = format!(
+        "(Get-CimInstance Win32_Process -Filter 'ProcessId={}').ParentProcessId",
+        std::process::id()
+    );
+
+    let output = Command::new("powershell")
+        .args(["-NoProfile", "-Command", &script])
+        .output()
+        .ok()?;
+
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn browser_binary(pid: u32) -> Option<PathBuf> {
+    std::fs::read_link(format!("/proc/{}/exe", pid)).ok()
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+pub(crate) fn browser_binary(pid: u32) -> Option<PathBuf> {
+    let output = Command::new("ps")
+        .args(["-o", "comm=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+
+    let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!path.is_empty()).then(|| PathBuf::from(path))
+}
+
+#[cfg(windows)]
+pub(crate) fn browser_binary(pid: u32) -> Option<PathBuf> {
+    let script = format!("(Get-Process -Id {}).Path", pid);
+    let output = Command::new("powershell")
+        .args(["-NoProfile", "-Command", &script])
+        .output()
+        .ok()?;
+
+    let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!path.is_empty()).then(|| PathBuf::from(path))
+}
+
+#[cfg(unix)]
+fn relauncher(pid: u32, arguments: &[String]) -> Command {
+    let mut process = Command::new("sh");
+    process
+        .args(["-c", RELAUNCHER, "sh", &pid.to_string()])
+        .args(arguments);
+
+    process
+}
+
+#[cfg(windows)]
+fn relauncher(pid: u32, arguments: &[String]) -> Command {
+    let quote = |v: &str| format!("'{}'", v.replace('\'', "''"));
+
+    let mut script = format!(
+        "Wait-Process -Id {} -ErrorAction SilentlyContinue; Start-Process -FilePath {}",
+        pid,
+        quote(&arguments[0])
+    );
+
+    if arguments.len() > 1 {
+        let rest = arguments[1..]
+            .iter()
+            .map(|v| quote(&format!("\"{}\"", v)))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        script.push_str(&format!(" -ArgumentList {}", rest));
+    }
+
+    let mut process = Command::new("powershell");
+    process.args(["-NoProfile", "-WindowStyle", "Hidden", "-Command", &script]);
+    process
+}
+
+// `restart.command` replaces the detected binary, for wrappers and flatpaks
+fn launch_arguments(pid: u32, profile: Option<&PathBuf>) -> Option<Vec<String>> {
+    let mut arguments = match config::get_string("restart.command") {
+        Some(command) => shell_words::split(&command).ok()?,
+        None => vec![browser_binary(pid)?.to_string_lossy().to_string()],
+    };
+
+    if arguments.is_empty() {
+        return None;
+    }
+
+    if let Some(profile) = profile {
+        arguments.push("-profile".into());
+        arguments.push(profile.to_string_lossy().to_string());
+    }
+
+    Some(arguments)
+}
+
+pub(crate) fn restart(profile: Option<&str>) -> Value {
+    // a browser that went away leaves us to init, which is not ours to signal
+    let Some(target) = browser_pid().and_then(|v| Pid::new(v.into())) else {
+        error!("(restart::restart) Could not find the browser process");
+        return json!({
+            "cmd": "restart",
+            "code": 2,
+            "error": "Browser process not found"
+        });
+    };
+
+    let pid = target.id();
+    let profile = profiles::resolve_profile(profile);
+    let Some(arguments) = launch_arguments(pid, profile.as_ref()) else {
+        error!("(restart::restart) Could not find the browser binary");
+        return json!({
+            "cmd": "restart",
+            "code": 2,
+            "error": "Browser binary not found"
+        });
+    };
+
+    if let Err(err) = spawn_detached(relauncher(pid, &arguments)) {
+        error!(
+            "(restart::restart) Failed to spawn relauncher, error: {}",
+            err
+        );
+        return json!({
+            "cmd": "restart",
+            "code": 2,
+            "error": err.to_string()
+        });
+    }
+
+    // the browser goes away with our stdout, so the reply has to be out first
+    let reply = events::pending_reply();
+    std::thread::spawn(move || {
+        events::wait_for_reply(reply);
+        std::thread::sleep(REPLY_GRACE);
+
+        if let Err(err) = send_signal(target, parse_signal("TERM").unwrap()) {
+            error!(
+                "(restart::restart) Failed to signal the browser, error: {}",
+                err
+            );
+        }
+    });
+
+    info!("(restart::restart) Restarting {} ({})", arguments[0], pid);
+    json!({
+        "cmd": "restart",
+        "code": SUCCESS_CODE,
+        "pid": pid,
+        "command": arguments
+    })
+}
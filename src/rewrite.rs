@@ -0,0 +1,218 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::{
+    commands::{config_directory, resolve_path, SUCCESS_CODE},
+    config,
+};
+
+const DIRECTORY: &str = "rewrite.d";
+const EXTENSION: &str = "rules";
+
+enum Target {
+    // a regex replacement, `$1` and `${name}` style
+    Regex(String),
+    // a route, `:name` and `*name` filled in from the match
+    Route(String),
+}
+
+struct Rule {
+    source: String,
+    pattern: Regex,
+    target: Target,
+}
+
+struct Rules {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    rules: Vec<Rule>,
+}
+
+// parsed rules, kept until one of the files changes
+static RULES: Mutex<Option<Rules>> = Mutex::new(None);
+
+fn rule_files() -> Vec<PathBuf> {
+    let mut files = config::get_strings("rewrite.files")
+        .into_iter()
+        .map(|v| resolve_path(&v))
+        .collect::<Vec<_>>();
+
+    let directory = config_directory().join(DIRECTORY);
+    if let Ok(entries) = directory.read_dir() {
+        let mut entries = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|v| v == EXTENSION))
+            .collect::<Vec<_>>();
+
+        entries.sort();
+        files.extend(entries);
+    }
+
+    files
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|v| v.modified()).ok()
+}
+
+// `twitter.com/:user/status/:id` matches with or without scheme and `www.`,
+// the query and fragment are carried over to the target
+fn compile_route(route: &str) -> Option<Regex> {
+    let route = route
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_end_matches('/');
+
+    let mut pattern = String::from(r"^(?:[a-z]+://)?(?:www\.)?");
+    for (index, segment) in route.split('/').enumerate() {
+        if index > 0 {
+            pattern.push('/');
+        }
+
+        if let Some(name) = segment.strip_prefix(':') {
+            pattern.push_str(&format!("(?P<{}>[^/?#]+)", name));
+        } else if let Some(name) = segment.strip_prefix('*') {
+            pattern.push_str(&format!("(?P<{}>[^?#]*)", name));
+        } else {
+            pattern.push_str(&regex::escape(segment));
+        }
+    }
+
+    pattern.push_str(r"/?(?P<__rest>[?#].*)?$");
+    Regex::new(&pattern).ok()
+}
+
+fn parse_rule(source: String, line: &str) -> Result<Rule, String> {
+    let parts = line.split_whitespace().collect::<Vec<_>>();
+    let [kind, pattern, target] = parts[..] else {
+        return Err(format!(
+            "{}: expected '<regex|route> <pattern> <target>'",
+            source
+        ));
+    };
+
+    let (pattern, target) = match kind {
+        "regex" => (Regex::new(pattern).ok(), Target::Regex(target.into())),
+        "route" => (compile_route(pattern), Target::Route(target.into())),
+        _ => return Err(format!("{}: unknown rule kind '{}'", source, kind)),
+    };
+
+    let pattern = pattern.ok_or_else(|| format!("{}: invalid pattern", source))?;
+    Ok(Rule {
+        source,
+        pattern,
+        target,
+    })
+}
+
+fn load(files: Vec<(PathBuf, Option<SystemTime>)>) -> Rules {
+    let mut rules = Vec::new();
+    for (path, _) in &files {
+        let Ok(content) = std::fs::read_to_string(path) else {
+            continue;
+        };
+
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let source = format!("{}:{}", path.to_string_lossy(), index + 1);
+            match parse_rule(source, line) {
+                Ok(rule) => rules.push(rule),
+                Err(err) => error!("(rewrite::load) {}", err),
+            }
+        }
+    }
+
+    info!("(rewrite::load) Loaded {} rewrite rules", rules.len());
+    Rules { files, rules }
+}
+
+fn apply(rule: &Rule, url: &str) -> Option<String> {
+    let captures = rule.pattern.captures(url)?;
+    match &rule.target {
+        Target::Regex(target) => {
+            let mut result = String::new();
+            captures.expand(target, &mut result);
+            Some(result)
+        }
+
+        Target::Route(target) => {
+            let mut result = target
+                .split('/')
+                .map(|segment| {
+                    let name = segment
+                        .strip_prefix(':')
+                        .or_else(|| segment.strip_prefix('*'));
+
+                    match name.and_then(|v| captures.name(v)) {
+                        Some(value) => value.as_str(),
+                        None => segment,
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+
+            if !result.contains("://") {
+                result.insert_str(0, "https://");
+            }
+
+            if let Some(rest) = captures.name("__rest") {
+                result.push_str(rest.as_str());
+            }
+
+            Some(result)
+        }
+    }
+}
+
+pub(crate) fn rewrite_url(url: &str) -> Value {
+    let files = rule_files()
+        .into_iter()
+        .map(|path| {
+            let modified = modified(&path);
+            (path, modified)
+        })
+        .collect::<Vec<_>>();
+
+    let mut cache = RULES.lock().unwrap();
+    if cache.as_ref().is_none_or(|v| v.files != files) {
+        *cache = Some(load(files));
+    }
+
+    let rules = &cache.as_ref().unwrap().rules;
+    let result = rules
+        .iter()
+        .find_map(|rule| apply(rule, url).map(|v| (v, &rule.source)));
+
+    match result {
+        Some((rewritten, source)) => {
+            info!(
+                "(rewrite::rewrite_url) {} -> {} ({})",
+                url, rewritten, source
+            );
+            json!({
+                "cmd": "rewrite_url",
+                "code": SUCCESS_CODE,
+                "url": rewritten,
+                "rewritten": true,
+                "rule": source
+            })
+        }
+
+        None => json!({
+            "cmd": "rewrite_url",
+            "code": SUCCESS_CODE,
+            "url": url,
+            "rewritten": false
+        }),
+    }
+}
@@ -0,0 +1,106 @@
+use std::process::Command;
+
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::{
+    commands::{spawn_detached, SUCCESS_CODE},
+    config, policy,
+};
+
+// `routes` is a list of `{ "match": "<regex>", "handler": "mpv %u" }`, tried
+// in order against the whole url; `%u` is the url and is appended if missing
+fn find_route(url: &str) -> Option<(usize, String)> {
+    let routes = config::get("routes")?.as_array()?.clone();
+    routes.iter().enumerate().find_map(|(index, route)| {
+        let pattern = route.get("match")?.as_str()?;
+        let handler = route.get("handler")?.as_str()?;
+
+        match Regex::new(pattern) {
+            Ok(regex) if regex.is_match(url) => Some((index, handler.to_string())),
+            Ok(_) => None,
+            Err(err) => {
+                error!(
+                    "(routes::find_route) Invalid pattern in route {}, error: {}",
+                    index, err
+                );
+                None
+            }
+        }
+    })
+}
+
+fn handler_arguments(handler: &str, url: &str) -> Option<Vec<String>> {
+    let mut arguments = shell_words::split(handler).ok()?;
+    if arguments.is_empty() {
+        return None;
+    }
+
+    if arguments.iter().any(|v| v.contains("%u")) {
+        arguments = arguments
+            .into_iter()
+            .map(|v| v.replace("%u", url))
+            .collect();
+    } else {
+        arguments.push(url.into());
+    }
+
+    Some(arguments)
+}
+
+pub(crate) fn route_open(url: &str) -> Value {
+    let Some((index, handler)) = find_route(url) else {
+        info!("(routes::route_open) No route for {}", url);
+        return json!({
+            "cmd": "route_open",
+            "code": 1,
+            "routed": false
+        });
+    };
+
+    let Some(arguments) = handler_arguments(&handler, url) else {
+        error!("(routes::route_open) Invalid handler: '{}'", handler);
+        return json!({
+            "cmd": "route_open",
+            "code": 1,
+            "routed": false,
+            "error": "Invalid handler"
+        });
+    };
+
+    if let Err(refusal) = policy::check_exec(&arguments[0], &arguments[1..]) {
+        return policy::denied("route_open", refusal);
+    }
+
+    let mut process = Command::new(&arguments[0]);
+    process.args(&arguments[1..]);
+
+    match spawn_detached(process) {
+        Ok(pid) => {
+            info!("(routes::route_open) {} -> {}", url, arguments[0]);
+            json!({
+                "cmd": "route_open",
+                "code": SUCCESS_CODE,
+                "routed": true,
+                "route": index,
+                "handler": arguments[0],
+                "pid": pid
+            })
+        }
+
+        Err(err) => {
+            error!(
+                "(routes::route_open) Failed to launch '{}', error: {}",
+                arguments[0], err
+            );
+
+            json!({
+                "cmd": "route_open",
+                "code": 2,
+                "routed": false,
+                "route": index,
+                "error": err.to_string()
+            })
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    commands::{find_program, run_with_timeout},
+    config, logging,
+};
+
+const DEFAULT_TIMEOUT: u64 = 120_000;
+
+pub(crate) enum Verdict {
+    Clean,
+    Infected {
+        output: String,
+        quarantine: Option<PathBuf>,
+    },
+    Failed(String),
+}
+
+impl Verdict {
+    pub(crate) fn is_clean(&self) -> bool {
+        matches!(self, Verdict::Clean)
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        match self {
+            Verdict::Clean => json!({ "status": "clean" }),
+            Verdict::Infected { output, quarantine } => json!({
+                "status": "infected",
+                "output": output,
+                "quarantine": quarantine
+            }),
+            Verdict::Failed(error) => json!({
+                "status": "error",
+                "error": error
+            }),
+        }
+    }
+}
+
+// scanning every move can be turned on here, a message can still ask for it
+pub(crate) fn is_enabled() -> bool {
+    config::get_bool("scan.on_move").unwrap_or(false)
+}
+
+// `scan.command` is split like a shell would and gets the file appended, or
+// substituted for `%f`; clamscan is used when nothing is configured
+pub(crate) fn scanner(path: &Path) -> Option<Vec<String>> {
+    let file = path.to_string_lossy().to_string();
+    let command = config::get_string("scan.command").or_else(|| {
+        find_program("clamscan").map(|_| "clamscan --no-summary --infected".to_string())
+    })?;
+
+    let mut arguments = shell_words::split(&command).ok()?;
+    if arguments.is_empty() {
+        return None;
+    }
+
+    if arguments.iter().any(|v| v.contains("%f")) {
+        arguments = arguments
+            .into_iter()
+            .map(|v| v.replace("%f", &file))
+            .collect();
+    } else {
+        arguments.push(file);
+    }
+
+    Some(arguments)
+}
+
+fn quarantine(path: &Path) -> std::io::Result<PathBuf> {
+    let directory = logging::data_directory().join("quarantine");
+    std::fs::create_dir_all(&directory)?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let destination = directory.join(format!("{}.{}", name, timestamp));
+    std::fs::rename(path, &destination)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&destination, std::fs::Permissions::from_mode(0o400))?;
+    }
+
+    Ok(destination)
+}
+
+// exit code 0 is clean and 1 is a detection, like clamscan; anything else is
+// a scanner failure and the file is left where it is
+pub(crate) fn scan(path: &Path) -> Verdict {
+    let Some(arguments) = scanner(path) else {
+        error!("(scan::scan) No scanner configured");
+        return Verdict::Failed("No scanner configured".into());
+    };
+
+    let timeout = config::get_u64("scan.timeout").unwrap_or(DEFAULT_TIMEOUT);
+    let mut process = Command::new(&arguments[0]);
+    process.args(&arguments[1..]);
+
+    let output = match run_with_timeout(process, None, Duration::from_millis(timeout)) {
+        Ok(output) => output,
+        Err(err) => {
+            error!("(scan::scan) Scanner failed, error: {}", err);
+            return Verdict::Failed(err.to_string());
+        }
+    };
+
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    match output.status.code() {
+        Some(0) => {
+            info!("(scan::scan) {} is clean", path.to_string_lossy());
+            Verdict::Clean
+        }
+
+        Some(1) => {
+            let quarantine = match quarantine(path) {
+                Ok(destination) => Some(destination),
+                Err(err) => {
+                    error!("(scan::scan) Failed to quarantine, error: {}", err);
+                    None
+                }
+            };
+
+            error!(
+                "(scan::scan) Detection in {}: {}",
+                path.to_string_lossy(),
+                text
+            );
+
+            Verdict::Infected {
+                output: text,
+                quarantine,
+            }
+        }
+
+        code => {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            error!("(scan::scan) Scanner exited with {:?}: {}", code, stderr);
+            Verdict::Failed(if stderr.is_empty() { text } else { stderr })
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use std::{collections::BTreeMap, process::Command, time::Duration};
+
+use serde_json::{json, Value};
+
+use crate::{
+    commands::{find_program, run_with_timeout, SUCCESS_CODE},
+    config,
+};
+
+// the keyring may ask to be unlocked first
+const DEFAULT_TIMEOUT: u64 = 60_000;
+
+pub(crate) type Attributes = BTreeMap<String, String>;
+
+// secret-tool looks up items by any attributes they were stored with
+#[cfg(all(unix, not(target_os = "macos")))]
+fn lookup(attributes: &Attributes) -> Result<Command, String> {
+    find_program("secret-tool").ok_or("secret-tool (libsecret) not found")?;
+
+    let mut process = Command::new("secret-tool");
+    process.arg("lookup");
+    for (key, value) in attributes {
+        process.args([key, value]);
+    }
+
+    Ok(process)
+}
+
+// the keychain only knows a fixed set of attributes
+#[cfg(target_os = "macos")]
+fn lookup(attributes: &Attributes) -> Result<Command, String> {
+    find_program("security").ok_or("security not found")?;
+
+    let mut process = Command::new("security");
+    process.arg("find-generic-password");
+    for (key, value) in attributes {
+        let flag = match key.as_str() {
+            "service" => "-s",
+            "account" => "-a",
+            "label" => "-l",
+            key => return Err(format!("unsupported attribute: {}", key)),
+        };
+
+        process.args([flag, value]);
+    }
+
+    process.arg("-w");
+    Ok(process)
+}
+
+// credential manager entries are found by their target name, given as
+// `target` (or `service`)
+#[cfg(windows)]
+fn lookup(attributes: &Attributes) -> Result<Command, String> {
+    const SCRIPT: &str = r#"
+$source = @'
+using System;
+using System.Runtime.InteropServices;
+public static class Vault {
+    [StructLayout(LayoutKind.Sequential, CharSet = CharSet.Unicode)]
+    struct Credential {
+        public int Flags; public int Type; public string TargetName; public string Comment;
+        public long LastWritten; public int BlobSize; public IntPtr Blob; public int Persist;
+        public int AttributeCount; public IntPtr Attributes; public string TargetAlias; public string UserName;
+    }
+    [DllImport("advapi32.dll", CharSet = CharSet.Unicode, SetLastError = true)]
+    static extern bool CredReadW(string target, int type, int flags, out IntPtr credential);
+    [DllImport("advapi32.dll")]
+    static extern void CredFree(IntPtr buffer);
+    public static string Read(string target) {
+        IntPtr pointer;
+        if (!CredReadW(target, 1, 0, out pointer)) { return null; }
+        var credential = (Credential)Marshal.PtrToStructure(pointer, typeof(Credential));
+        var secret = Marshal.PtrToStringUni(credential.Blob, credential.BlobSize / 2);
+        CredFree(pointer);
+        return secret;
+    }
+}
+'@
+Add-Type -TypeDefinition $source
+$secret = [Vault]::Read([Console]::In.ReadToEnd())
+if ($secret -eq $null) { exit 1 }
+[Console]::Out.Write($secret)
+"#;
+
+    if attributes.keys().any(|v| v != "target" && v != "service") {
+        return Err("only target or service are supported".into());
+    }
+
+    attributes
+        .get("target")
+        .or(attributes.get("service"))
+        .ok_or("target is required")?;
+
+    let mut process = Command::new("powershell");
+    process.args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT]);
+    Ok(process)
+}
+
+#[cfg(windows)]
+fn lookup_input(attributes: &Attributes) -> Option<Vec<u8>> {
+    attributes
+        .get("target")
+        .or(attributes.get("service"))
+        .map(|v| v.as_bytes().to_vec())
+}
+
+#[cfg(not(windows))]
+fn lookup_input(_attributes: &Attributes) -> Option<Vec<u8>> {
+    None
+}
+
+// the secret goes in on stdin, never on the command line
+#[cfg(all(unix, not(target_os = "macos")))]
+fn store(attributes: &Attributes, label: &str, secret: &str) -> Result<(Command, Vec<u8>), String> {
+    find_program("secret-tool").ok_or("secret-tool (libsecret) not found")?;
+
+    let mut process = Command::new("secret-tool");
+    process.args(["store", "--label", label]);
+    for (key, value) in attributes {
+        process.args([key, value]);
+    }
+
+    Ok((process, secret.as_bytes().to_vec()))
+}
+
+// `security -i` reads the command from stdin, which keeps the password out of
+// the process list
+#[cfg(target_os = "macos")]
+fn store(attributes: &Attributes, label: &str, secret: &str) -> Result<(Command, Vec<u8>), String> {
+    find_program("security").ok_or("security not found")?;
+
+    let quote = |v: &str| format!("\"{}\"", v.replace('\\', "\\\\").replace('"', "\\\""));
+    let mut line = format!("add-generic-password -U -l {}", quote(label));
+    for (key, value) in attributes {
+        let flag = match key.as_str() {
+            "service" => "-s",
+            "account" => "-a",
+            key => return Err(format!("unsupported attribute: {}", key)),
+        };
+
+        line.push_str(&format!(" {} {}", flag, quote(value)));
+    }
+
+    line.push_str(&format!(" -w {}\n", quote(secret)));
+
+    let mut process = Command::new("security");
+    process.arg("-i");
+    Ok((process, line.into_bytes()))
+}
+
+// the target name and the secret come in on stdin, one line each
+#[cfg(windows)]
+fn store(attributes: &Attributes, label: &str, secret: &str) -> Result<(Command, Vec<u8>), String> {
+    const SCRIPT: &str = r#"
+$source = @'
+using System;
+using System.Runtime.InteropServices;
+public static class Vault {
+    [StructLayout(LayoutKind.Sequential, CharSet = CharSet.Unicode)]
+    struct Credential {
+        public int Flags; public int Type; public string TargetName; public string Comment;
+        public long LastWritten; public int BlobSize; public IntPtr Blob; public int Persist;
+        public int AttributeCount; public IntPtr Attributes; public string TargetAlias; public string UserName;
+    }
+    [DllImport("advapi32.dll", CharSet = CharSet.Unicode, SetLastError = true)]
+    static extern bool CredWriteW(ref Credential credential, int flags);
+    public static bool Write(string target, string comment, string secret) {
+        var credential = new Credential();
+        credential.Type = 1;
+        credential.Persist = 2;
+        credential.TargetName = target;
+        credential.Comment = comment;
+        credential.BlobSize = secret.Length * 2;
+        credential.Blob = Marshal.StringToCoTaskMemUni(secret);
+        var written = CredWriteW(ref credential, 0);
+        Marshal.FreeCoTaskMem(credential.Blob);
+        return written;
+    }
+}
+'@
+Add-Type -TypeDefinition $source
+$target = [Console]::In.ReadLine()
+$comment = [Console]::In.ReadLine()
+if (-not [Vault]::Write($target, $comment, [Console]::In.ReadToEnd())) { exit 1 }
+"#;
+
+    let target = lookup_input(attributes).ok_or("target is required")?;
+
+    let mut input = target;
+    input.push(b'\n');
+    input.extend_from_slice(label.as_bytes());
+    input.push(b'\n');
+    input.extend_from_slice(secret.as_bytes());
+
+    let mut process = Command::new("powershell");
+    process.args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT]);
+    Ok((process, input))
+}
+
+fn timeout() -> Duration {
+    Duration::from_millis(config::get_u64("secret.timeout").unwrap_or(DEFAULT_TIMEOUT))
+}
+
+// `Ok(None)` when the keyring answered but has no such secret
+pub(crate) fn read_secret(attributes: &Attributes) -> Result<Option<String>, String> {
+    let process = lookup(attributes)?;
+    let input = lookup_input(attributes);
+    let output =
+        run_with_timeout(process, input.as_deref(), timeout()).map_err(|e| e.to_string())?;
+
+    if output.status.success() && !output.stdout.is_empty() {
+        let content = String::from_utf8_lossy(&output.stdout);
+        Ok(Some(
+            content.strip_suffix('\n').unwrap_or(&content).to_string(),
+        ))
+    } else {
+        Ok(None)
+    }
+}
+
+pub(crate) fn store_secret(
+    attributes: &Attributes,
+    label: &str,
+    secret: &str,
+) -> Result<(), String> {
+    let (process, input) = store(attributes, label, secret)?;
+    let output = run_with_timeout(process, Some(&input), timeout()).map_err(|e| e.to_string())?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "the keyring refused to store {}: {}",
+            label,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+// off unless `secret.enabled`; `secret.allow` further limits which
+// `service` values may be read
+fn is_allowed(attributes: &Attributes) -> bool {
+    if !config::get_bool("secret.enabled").unwrap_or(false) {
+        return false;
+    }
+
+    let allow = config::get_strings("secret.allow");
+    allow.is_empty()
+        || attributes
+            .get("service")
+            .is_some_and(|service| allow.iter().any(|v| v == service))
+}
+
+pub(crate) fn secret(attributes: &serde_json::Map<String, Value>) -> Value {
+    let attributes = attributes
+        .iter()
+        .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+        .collect::<Attributes>();
+
+    if attributes.is_empty() {
+        return json!({
+            "cmd": "secret",
+            "code": 1,
+            "error": "Expected at least one attribute"
+        });
+    }
+
+    if !is_allowed(&attributes) {
+        error!("(secret::secret) Refused lookup of {:?}", attributes.keys());
+        return json!({
+            "cmd": "secret",
+            "code": 1,
+            "error": "Secret lookups are not allowed"
+        });
+    }
+
+    let found = match read_secret(&attributes) {
+        Ok(found) => found,
+        Err(err) => {
+            error!("(secret::secret) Lookup failed, error: {}", err);
+            return json!({
+                "cmd": "secret",
+                "code": 2,
+                "error": err
+            });
+        }
+    };
+
+    // the secret itself never goes to the log
+    if let Some(content) = found {
+        info!(
+            "(secret::secret) Found a secret for {:?}",
+            attributes.keys()
+        );
+
+        json!({
+            "cmd": "secret",
+            "code": SUCCESS_CODE,
+            "content": content
+        })
+    } else {
+        info!("(secret::secret) No secret for {:?}", attributes.keys());
+        json!({
+            "cmd": "secret",
+            "code": 1,
+            "error": "Secret not found"
+        })
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    commands::{resolve_path, SUCCESS_CODE},
+    config, logging, profiles,
+};
+
+// recovery.jsonlz4 and friends while the browser runs, sessionstore.jsonlz4
+// once it has been shut down cleanly
+const SESSION_DIRECTORY: &str = "sessionstore-backups";
+const SESSION_FILE: &str = "sessionstore.jsonlz4";
+
+fn backup_directory(directory: Option<&str>) -> PathBuf {
+    match directory
+        .map(String::from)
+        .or_else(|| config::get_string("session.backup_dir"))
+    {
+        Some(directory) => resolve_path(&directory),
+        None => logging::data_directory().join("sessions"),
+    }
+}
+
+fn session_files(profile: &Path) -> Vec<PathBuf> {
+    let mut files = std::fs::read_dir(profile.join(SESSION_DIRECTORY))
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|v| v.path())
+                .filter(|v| v.is_file())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let file = profile.join(SESSION_FILE);
+    if file.is_file() {
+        files.push(file);
+    }
+
+    files
+}
+
+fn copy_files(files: &[PathBuf], destination: &Path) -> std::io::Result<Vec<String>> {
+    std::fs::create_dir_all(destination)?;
+
+    let mut copied = Vec::new();
+    for file in files {
+        let name = file.file_name().unwrap_or_default();
+        std::fs::copy(file, destination.join(name))?;
+        copied.push(name.to_string_lossy().to_string());
+    }
+
+    Ok(copied)
+}
+
+pub(crate) fn session_backup(directory: Option<&str>, profile: Option<&str>) -> Value {
+    let Some(profile) = profiles::resolve_profile(profile) else {
+        error!("(session::session_backup) Could not find a profile directory");
+        return json!({
+            "cmd": "session_backup",
+            "code": 2,
+            "error": "No profile found"
+        });
+    };
+
+    let files = session_files(&profile);
+    if files.is_empty() {
+        error!(
+            "(session::session_backup) No session files in {}",
+            profile.to_string_lossy()
+        );
+
+        return json!({
+            "cmd": "session_backup",
+            "code": 1,
+            "error": "No session files found",
+            "profile": profile
+        });
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let destination = backup_directory(directory).join(format!("session-{}", timestamp));
+    match copy_files(&files, &destination) {
+        Ok(copied) => {
+            info!(
+                "(session::session_backup) {} files to {}",
+                copied.len(),
+                destination.to_string_lossy()
+            );
+
+            json!({
+                "cmd": "session_backup",
+                "code": SUCCESS_CODE,
+                "dir": destination,
+                "files": copied,
+                "profile": profile
+            })
+        }
+
+        Err(err) => {
+            error!(
+                "(session::session_backup) Failed to copy session files, error: {}",
+                err
+            );
+
+            json!({
+                "cmd": "session_backup",
+                "code": 2,
+                "error": err.to_string()
+            })
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use serde_json::{json, Value};
+
+use crate::commands::{resolve_path, SUCCESS_CODE};
+
+const DEFAULT_LIMIT: usize = 100;
+
+struct Entry {
+    command: String,
+    timestamp: Option<u64>,
+}
+
+fn history_file(shell: &str) -> Option<PathBuf> {
+    let home = dirs::home_dir()?;
+
+    // HISTFILE is usually a shell variable rather than an exported one, but
+    // honour it whenever the browser was started with it in its environment
+    if let Ok(path) = std::env::var("HISTFILE") {
+        let path = resolve_path(&path);
+        let matches = path
+            .file_name()
+            .is_some_and(|v| v.to_string_lossy().contains(shell));
+
+        if matches && path.exists() {
+            return Some(path);
+        }
+    }
+
+    let path = match shell {
+        "bash" => home.join(".bash_history"),
+        "zsh" => std::env::var("ZDOTDIR")
+            .map(PathBuf::from)
+            .unwrap_or(home)
+            .join(".zsh_history"),
+
+        "fish" => dirs::data_dir()?.join("fish").join("fish_history"),
+        _ => return None,
+    };
+
+    path.exists().then_some(path)
+}
+
+fn parse_bash(content: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut timestamp = None;
+
+    for line in content.lines() {
+        // HISTTIMEFORMAT writes `#<epoch>` before each command
+        if let Some(value) = line.strip_prefix('#') {
+            if let Ok(value) = value.parse() {
+                timestamp = Some(value);
+                continue;
+            }
+        }
+
+        entries.push(Entry {
+            command: line.to_string(),
+            timestamp: timestamp.take(),
+        });
+    }
+
+    entries
+}
+
+// zsh "metafies" bytes above 0x80, prefixing them with 0x83 and xor-ing 0x20
+fn unmetafy(bytes: &[u8]) -> String {
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut iter = bytes.iter();
+    while let Some(&byte) = iter.next() {
+        if byte == 0x83 {
+            if let Some(&next) = iter.next() {
+                result.push(next ^ 0x20);
+            }
+        } else {
+            result.push(byte);
+        }
+    }
+
+    String::from_utf8_lossy(&result).to_string()
+}
+
+fn parse_zsh(content: &str) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut continued = false;
+
+    for line in content.lines() {
+        if continued {
+            if let Some(entry) = entries.last_mut() {
+                entry.command.push('\n');
+                entry
+                    .command
+                    .push_str(line.strip_suffix('\\').unwrap_or(line));
+            }
+
+            continued = line.ends_with('\\');
+            continue;
+        }
+
+        continued = line.ends_with('\\');
+        let line = line.strip_suffix('\\').unwrap_or(line);
+
+        // EXTENDED_HISTORY: `: <start>:<elapsed>;<command>`
+        let entry = line
+            .strip_prefix(": ")
+            .and_then(|rest| rest.split_once(';'))
+            .and_then(|(meta, command)| {
+                let timestamp = meta.split(':').next()?.trim().parse().ok()?;
+                Some(Entry {
+                    command: command.to_string(),
+                    timestamp: Some(timestamp),
+                })
+            });
+
+        entries.push(entry.unwrap_or(Entry {
+            command: line.to_string(),
+            timestamp: None,
+        }));
+    }
+
+    entries
+}
+
+fn parse_fish(content: &str) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = Vec::new();
+
+    for line in content.lines() {
+        if let Some(command) = line.strip_prefix("- cmd: ") {
+            let command = command.replace("\\n", "\n").replace("\\\\", "\\");
+            entries.push(Entry {
+                command,
+                timestamp: None,
+            });
+        } else if let Some(timestamp) = line.trim_start().strip_prefix("when: ") {
+            if let Some(entry) = entries.last_mut() {
+                entry.timestamp = timestamp.parse().ok();
+            }
+        }
+    }
+
+    entries
+}
+
+fn read_history(shell: &str) -> Vec<Entry> {
+    let Some(path) = history_file(shell) else {
+        return Vec::new();
+    };
+
+    let Ok(bytes) = std::fs::read(&path) else {
+        error!(
+            "(shell_history::read_history) Failed to read {}",
+            path.to_string_lossy()
+        );
+
+        return Vec::new();
+    };
+
+    match shell {
+        "bash" => parse_bash(&String::from_utf8_lossy(&bytes)),
+        "zsh" => parse_zsh(&unmetafy(&bytes)),
+        "fish" => parse_fish(&String::from_utf8_lossy(&bytes)),
+        _ => Vec::new(),
+    }
+}
+
+pub(crate) fn shell_history(
+    shell: Option<&str>,
+    query: &str,
+    sort: &str,
+    limit: Option<usize>,
+) -> Value {
+    let shells = match shell {
+        Some(shell) => vec![shell],
+        None => vec!["bash", "zsh", "fish"],
+    };
+
+    let entries = shells
+        .iter()
+        .flat_map(|shell| read_history(shell))
+        .collect::<Vec<_>>();
+
+    let total = entries.len().max(1) as f64;
+    let query = query.to_lowercase();
+
+    // (count, most recent position, most recent timestamp)
+    let mut ranked: HashMap<String, (u64, usize, Option<u64>)> = HashMap::new();
+    for (position, entry) in entries.into_iter().enumerate() {
+        let command = entry.command.trim();
+        if command.is_empty() || !command.to_lowercase().contains(&query) {
+            continue;
+        }
+
+        let value = ranked.entry(command.to_string()).or_default();
+        value.0 += 1;
+        value.1 = position;
+        value.2 = entry.timestamp.or(value.2);
+    }
+
+    let mut ranked = ranked.into_iter().collect::<Vec<_>>();
+    match sort {
+        "recent" => ranked.sort_by_key(|v| std::cmp::Reverse(v.1 .1)),
+        "frequent" => ranked.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then(b.1 .1.cmp(&a.1 .1))),
+
+        // frecency: repeated commands win, but recent use doubles their weight
+        _ => ranked.sort_by(|a, b| {
+            let score = |v: &(u64, usize, Option<u64>)| v.0 as f64 * (1.0 + v.1 as f64 / total);
+            score(&b.1).total_cmp(&score(&a.1))
+        }),
+    }
+
+    let content = ranked
+        .into_iter()
+        .take(limit.unwrap_or(DEFAULT_LIMIT))
+        .map(|(command, (count, _, timestamp))| {
+            json!({
+                "command": command,
+                "count": count,
+                "timestamp": timestamp
+            })
+        })
+        .collect::<Vec<_>>();
+
+    info!(
+        "(shell_history::shell_history) shells: {:?}, results: {}",
+        shells,
+        content.len()
+    );
+
+    json!({
+        "cmd": "shell_history",
+        "code": SUCCESS_CODE,
+        "content": content
+    })
+}
@@ -0,0 +1,130 @@
+use std::sync::Mutex;
+
+use serde_json::{Map, Value};
+
+use crate::config;
+
+// the protocol the extension announced in `hello`, None until it does
+static CLIENT_PROTOCOL: Mutex<Option<u64>> = Mutex::new(None);
+
+struct Shim {
+    name: &'static str,
+    apply: fn(&mut Map<String, Value>),
+}
+
+const SHIMS: [Shim; 4] = [
+    // releases before `error` existed only ever show `content`
+    Shim {
+        name: "error_as_content",
+        apply: |reply| {
+            let failed = reply.get("code").and_then(|v| v.as_u64()).unwrap_or(0) != 0;
+            if failed && !reply.contains_key("content") {
+                if let Some(error) = reply.get("error").cloned() {
+                    reply.insert("content".into(), error);
+                }
+            }
+        },
+    },
+    // older releases test for a field's presence rather than its value
+    Shim {
+        name: "drop_nulls",
+        apply: |reply| reply.retain(|_, v| !v.is_null()),
+    },
+    // `version` used to be a number
+    Shim {
+        name: "version_number",
+        apply: |reply| {
+            let version = reply.get("version").and_then(|v| v.as_str()).map(|v| {
+                let mut parts = v.splitn(3, '.');
+                format!(
+                    "{}.{}",
+                    parts.next().unwrap_or("0"),
+                    parts.next().unwrap_or("0")
+                )
+            });
+
+            if let Some(number) = version.and_then(|v| v.parse::<f64>().ok()) {
+                reply.insert("version".into(), Value::from(number));
+            }
+        },
+    },
+    Shim {
+        name: "camel_case",
+        apply: |reply| {
+            let fields = std::mem::take(reply);
+            for (key, value) in fields {
+                reply.insert(camel_case(&key), value);
+            }
+        },
+    },
+];
+
+// which shims a client speaking an older protocol gets; protocol 0 is an
+// extension that sent `hello` without one
+const PROTOCOL_SHIMS: [(u64, &[&str]); 1] = [(0, &["error_as_content", "drop_nulls"])];
+
+fn camel_case(name: &str) -> String {
+    let mut result = String::new();
+    let mut upper = false;
+    for c in name.chars() {
+        match c {
+            '_' => upper = !result.is_empty(),
+            c if upper => {
+                result.extend(c.to_uppercase());
+                upper = false;
+            }
+
+            c => result.push(c),
+        }
+    }
+
+    result
+}
+
+pub(crate) fn set_client_protocol(protocol: Option<u64>) {
+    *CLIENT_PROTOCOL.lock().unwrap() = protocol;
+}
+
+// `compat.shims` picks them by name and wins over everything; otherwise they
+// follow `compat.protocol` or the protocol from `hello`
+pub(crate) fn active() -> Vec<&'static str> {
+    let requested = config::get_strings("compat.shims");
+    if !requested.is_empty() {
+        return SHIMS
+            .iter()
+            .map(|v| v.name)
+            .filter(|v| requested.iter().any(|name| name == v))
+            .collect();
+    }
+
+    let protocol = config::get_u64("compat.protocol").or(*CLIENT_PROTOCOL.lock().unwrap());
+    let Some(protocol) = protocol else {
+        return Vec::new();
+    };
+
+    PROTOCOL_SHIMS
+        .iter()
+        .filter(|(version, _)| protocol <= *version)
+        .flat_map(|(_, names)| names.iter().copied())
+        .collect()
+}
+
+pub(crate) fn names() -> Vec<&'static str> {
+    SHIMS.iter().map(|v| v.name).collect()
+}
+
+// rewrites a reply for the extension on the other end, in table order
+pub(crate) fn apply(mut reply: Value) -> Value {
+    let active = active();
+    if active.is_empty() {
+        return reply;
+    }
+
+    if let Value::Object(map) = &mut reply {
+        for shim in SHIMS.iter().filter(|v| active.contains(&v.name)) {
+            (shim.apply)(map);
+        }
+    }
+
+    reply
+}
@@ -0,0 +1,157 @@
+use std::path::{Path, PathBuf};
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use rusqlite::{types::ValueRef, OpenFlags};
+use serde_json::{json, Map, Value};
+
+use crate::{
+    commands::{resolve_path, SUCCESS_CODE},
+    config,
+};
+
+const DEFAULT_ROW_LIMIT: usize = 1000;
+
+fn resolve(path: &str) -> Option<PathBuf> {
+    resolve_path(path).canonicalize().ok()
+}
+
+// a database may be listed by its own path or by any directory containing it
+fn is_allowed(path: &Path) -> bool {
+    config::get_strings("sqlite.databases")
+        .iter()
+        .filter_map(|entry| resolve(entry))
+        .any(|entry| path == entry || (entry.is_dir() && path.starts_with(&entry)))
+}
+
+fn to_json(value: ValueRef) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(value) => json!(value),
+        ValueRef::Real(value) => json!(value),
+        ValueRef::Text(value) => Value::String(String::from_utf8_lossy(value).to_string()),
+        ValueRef::Blob(value) => Value::String(BASE64_STANDARD.encode(value)),
+    }
+}
+
+fn to_sql(value: &Value) -> rusqlite::types::Value {
+    use rusqlite::types::Value as Sql;
+
+    match value {
+        Value::Null => Sql::Null,
+        Value::Bool(value) => Sql::Integer(*value as i64),
+        Value::Number(value) => match value.as_i64() {
+            Some(value) => Sql::Integer(value),
+            None => Sql::Real(value.as_f64().unwrap_or_default()),
+        },
+
+        Value::String(value) => Sql::Text(value.clone()),
+        value => Sql::Text(value.to_string()),
+    }
+}
+
+fn query(
+    path: &Path,
+    sql: &str,
+    params: &[Value],
+    write: bool,
+    limit: usize,
+) -> rusqlite::Result<(Vec<String>, Vec<Value>, bool)> {
+    let flags = if write {
+        OpenFlags::SQLITE_OPEN_READ_WRITE
+    } else {
+        OpenFlags::SQLITE_OPEN_READ_ONLY
+    };
+
+    let connection = rusqlite::Connection::open_with_flags(path, flags)?;
+    connection.busy_timeout(std::time::Duration::from_secs(1))?;
+
+    let mut statement = connection.prepare(sql)?;
+    let columns = statement
+        .column_names()
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>();
+
+    let params = params.iter().map(to_sql).collect::<Vec<_>>();
+    let mut rows = statement.query(rusqlite::params_from_iter(params))?;
+
+    let mut result = Vec::new();
+    let mut truncated = false;
+    while let Some(row) = rows.next()? {
+        if result.len() >= limit {
+            truncated = true;
+            break;
+        }
+
+        let mut object = Map::new();
+        for (index, column) in columns.iter().enumerate() {
+            object.insert(column.clone(), to_json(row.get_ref(index)?));
+        }
+
+        result.push(Value::Object(object));
+    }
+
+    Ok((columns, result, truncated))
+}
+
+pub(crate) fn sqlite_query(
+    database: &str,
+    sql: &str,
+    params: &[Value],
+    write: bool,
+    limit: Option<usize>,
+) -> Value {
+    let Some(path) = resolve(database).filter(|path| is_allowed(path)) else {
+        error!(
+            "(sqlite::sqlite_query) Refused database not in the allowlist: {}",
+            database
+        );
+
+        return json!({
+            "cmd": "sqlite_query",
+            "code": 1,
+            "error": "Database is not allowlisted"
+        });
+    };
+
+    if write && !config::get_bool("sqlite.writable").unwrap_or(false) {
+        return json!({
+            "cmd": "sqlite_query",
+            "code": 1,
+            "error": "Writing is disabled"
+        });
+    }
+
+    let limit = limit.unwrap_or(DEFAULT_ROW_LIMIT);
+    match query(&path, sql, params, write, limit) {
+        Ok((columns, rows, truncated)) => {
+            info!(
+                "(sqlite::sqlite_query) path: {}, rows: {}",
+                path.to_string_lossy(),
+                rows.len()
+            );
+
+            json!({
+                "cmd": "sqlite_query",
+                "code": SUCCESS_CODE,
+                "columns": columns,
+                "rows": rows,
+                "truncated": truncated
+            })
+        }
+
+        Err(err) => {
+            error!(
+                "(sqlite::sqlite_query) path: {}, error: {}",
+                path.to_string_lossy(),
+                err
+            );
+
+            json!({
+                "cmd": "sqlite_query",
+                "code": 2,
+                "error": err.to_string()
+            })
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use serde_json::{json, Value};
+
+use crate::{
+    clipboard,
+    commands::{SUCCESS_CODE, VERSION},
+};
+
+#[cfg(not(target_os = "linux"))]
+fn output(program: &str, arguments: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program)
+        .args(arguments)
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !text.is_empty()).then_some(text)
+}
+
+#[cfg(target_os = "linux")]
+fn kernel() -> Option<String> {
+    std::fs::read_to_string("/proc/sys/kernel/osrelease")
+        .ok()
+        .map(|v| v.trim().to_string())
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn kernel() -> Option<String> {
+    output("uname", &["-r"])
+}
+
+#[cfg(windows)]
+fn kernel() -> Option<String> {
+    output("cmd", &["/C", "ver"])
+}
+
+// PRETTY_NAME from os-release on linux, the product version elsewhere
+#[cfg(target_os = "linux")]
+fn distribution() -> Option<String> {
+    ["/etc/os-release", "/usr/lib/os-release"]
+        .iter()
+        .filter_map(|v| std::fs::read_to_string(v).ok())
+        .find_map(|content| {
+            content.lines().find_map(|line| {
+                let value = line.strip_prefix("PRETTY_NAME=")?;
+                Some(value.trim_matches('"').to_string())
+            })
+        })
+}
+
+#[cfg(target_os = "macos")]
+fn distribution() -> Option<String> {
+    output("sw_vers", &["-productVersion"]).map(|v| format!("macOS {}", v))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn distribution() -> Option<String> {
+    None
+}
+
+pub(crate) fn desktop() -> Option<String> {
+    ["XDG_CURRENT_DESKTOP", "DESKTOP_SESSION"]
+        .iter()
+        .filter_map(|v| std::env::var(v).ok())
+        .find(|v| !v.is_empty())
+}
+
+// what the browser's session runs on, which decides the clipboard and
+// screenshot tools that work
+fn display_server() -> Option<&'static str> {
+    if cfg!(target_os = "macos") {
+        return Some("quartz");
+    }
+
+    if cfg!(windows) {
+        return Some("windows");
+    }
+
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        return Some("wayland");
+    }
+
+    if std::env::var_os("DISPLAY").is_some() {
+        return Some("x11");
+    }
+
+    match std::env::var("XDG_SESSION_TYPE").ok()?.as_str() {
+        "wayland" => Some("wayland"),
+        "x11" => Some("x11"),
+        "tty" => Some("tty"),
+        _ => None,
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn hostname() -> Option<String> {
+    let mut buffer = [0u8; 256];
+    if unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) } != 0 {
+        return None;
+    }
+
+    let end = buffer.iter().position(|v| *v == 0).unwrap_or(buffer.len());
+    Some(String::from_utf8_lossy(&buffer[..end]).to_string()).filter(|v| !v.is_empty())
+}
+
+#[cfg(windows)]
+pub(crate) fn hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok()
+}
+
+pub(crate) fn sysinfo() -> Value {
+    info!("(sysinfo::sysinfo) Collecting system information");
+
+    json!({
+        "cmd": "sysinfo",
+        "code": SUCCESS_CODE,
+        "os": std::env::consts::OS,
+        "family": std::env::consts::FAMILY,
+        "arch": std::env::consts::ARCH,
+        "kernel": kernel(),
+        "distribution": distribution(),
+        "desktop": desktop(),
+        "display_server": display_server(),
+        "clipboard": clipboard::detect().map(|v| format!("{:?}", v)),
+        "version": VERSION
+    })
+}
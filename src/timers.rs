@@ -0,0 +1,212 @@
+use std::{
+    collections::BTreeMap,
+    process::Command,
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    commands::{io_failure, spawn_detached, SUCCESS_CODE},
+    config, events,
+};
+
+struct Timer {
+    label: String,
+    started: u64,
+    deadline: Instant,
+    // dropping it wakes the waiting thread up without firing
+    _cancel: Sender<()>,
+}
+
+static TIMERS: Mutex<BTreeMap<u64, Timer>> = Mutex::new(BTreeMap::new());
+static NEXT_ID: Mutex<u64> = Mutex::new(1);
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn notifier(summary: &str, body: &str) -> Option<Command> {
+    let quote = |v: &str| format!("\"{}\"", v.replace('\\', "\\\\").replace('"', "\\\""));
+
+    let mut process = Command::new("osascript");
+    process.args([
+        "-e",
+        &format!(
+            "display notification {} with title {}",
+            quote(body),
+            quote(summary)
+        ),
+    ]);
+
+    Some(process)
+}
+
+#[cfg(windows)]
+pub(crate) fn notifier(summary: &str, body: &str) -> Option<Command> {
+    let quote = |v: &str| format!("'{}'", v.replace('\'', "''"));
+    let script = format!(
+        "Add-Type -AssemblyName System.Windows.Forms; \
+         $n = New-Object System.Windows.Forms.NotifyIcon; \
+         $n.Icon = [System.Drawing.SystemIcons]::Information; $n.Visible = $true; \
+         $n.ShowBalloonTip(10000, {}, {}, 'Info'); Start-Sleep 10; $n.Dispose()",
+        quote(summary),
+        quote(body)
+    );
+
+    let mut process = Command::new("powershell");
+    process.args(["-NoProfile", "-WindowStyle", "Hidden", "-Command", &script]);
+    Some(process)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+pub(crate) fn notifier(summary: &str, body: &str) -> Option<Command> {
+    crate::commands::find_program("notify-send")?;
+
+    let mut process = Command::new("notify-send");
+    process.args(["--app-name", "Tridactyl", summary, body]);
+    Some(process)
+}
+
+// a desktop notification, for when no tab is around to show the event
+pub(crate) fn notify(summary: &str, body: &str) {
+    let Some(process) = notifier(summary, body) else {
+        error!("(timers::notify) No notification tool found");
+        return;
+    };
+
+    if let Err(err) = spawn_detached(process) {
+        error!("(timers::notify) Failed to notify, error: {}", err);
+    }
+}
+
+fn fire(timer: u64, label: &str, id: &Value, notify_desktop: bool) {
+    info!("(timers::fire) Timer {} ('{}') fired", timer, label);
+
+    events::emit(&json!({
+        "cmd": "timer",
+        "id": id,
+        "event": "fired",
+        "timer": timer,
+        "label": label
+    }));
+
+    if notify_desktop {
+        notify(
+            "Tridactyl timer",
+            if label.is_empty() {
+                "Time is up"
+            } else {
+                label
+            },
+        );
+    }
+}
+
+// `duration` is in milliseconds; `notify` falls back to `timers.notify`,
+// which is on by default
+pub(crate) fn timer_start(duration: u64, label: &str, notify: Option<bool>, id: Value) -> Value {
+    let notify = notify
+        .or_else(|| config::get_bool("timers.notify"))
+        .unwrap_or(true);
+
+    let timer = {
+        let mut next = NEXT_ID.lock().unwrap();
+        *next += 1;
+        *next - 1
+    };
+
+    let duration = Duration::from_millis(duration);
+    let (sender, receiver) = mpsc::channel::<()>();
+
+    TIMERS.lock().unwrap().insert(
+        timer,
+        Timer {
+            label: label.to_string(),
+            started: now(),
+            deadline: Instant::now() + duration,
+            _cancel: sender,
+        },
+    );
+
+    info!(
+        "(timers::timer_start) Timer {} ('{}') in {:?}",
+        timer, label, duration
+    );
+
+    let reply = events::pending_reply();
+    let label = label.to_string();
+    std::thread::spawn(move || {
+        if let Err(RecvTimeoutError::Timeout) = receiver.recv_timeout(duration) {
+            // whoever takes it out of the table decides whether it fires
+            if TIMERS.lock().unwrap().remove(&timer).is_some() {
+                events::wait_for_reply(reply);
+                fire(timer, &label, &id, notify);
+            }
+        }
+    });
+
+    json!({
+        "cmd": "timer_start",
+        "code": SUCCESS_CODE,
+        "timer": timer
+    })
+}
+
+pub(crate) fn timer_list() -> Value {
+    let timers = TIMERS.lock().unwrap();
+    let content = timers
+        .iter()
+        .map(|(timer, v)| {
+            json!({
+                "timer": timer,
+                "label": v.label,
+                "started": v.started,
+                "remaining": v.deadline.saturating_duration_since(Instant::now()).as_millis() as u64
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "cmd": "timer_list",
+        "code": SUCCESS_CODE,
+        "content": content
+    })
+}
+
+pub(crate) fn timer_cancel(timer: u64) -> Value {
+    match TIMERS.lock().unwrap().remove(&timer) {
+        Some(v) => {
+            info!(
+                "(timers::timer_cancel) Cancelled timer {} ('{}')",
+                timer, v.label
+            );
+
+            json!({
+                "cmd": "timer_cancel",
+                "code": SUCCESS_CODE,
+                "timer": timer
+            })
+        }
+
+        None => {
+            error!("(timers::timer_cancel) No such timer: {}", timer);
+            io_failure(
+                "timer_cancel",
+                1,
+                &std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("No such timer: {}", timer),
+                ),
+            )
+        }
+    }
+}
@@ -0,0 +1,375 @@
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::{
+    commands::{resolve_path, url_host, write_atomic, SUCCESS_CODE},
+    config, journal, logging,
+};
+
+const TIMEWARRIOR_LINE: &str = r"^inc (\d{8}T\d{6})Z - (\d{8}T\d{6})Z(?: # (.*))?$";
+const TIMECLOCK_IN: &str = r"^i (\d{4}/\d{2}/\d{2} \d{2}:\d{2}:\d{2}) ?(.*)$";
+const TIMECLOCK_OUT: &str = r"^o (\d{4}/\d{2}/\d{2} \d{2}:\d{2}:\d{2})";
+
+#[derive(Clone, Copy)]
+enum Format {
+    // `inc <start> - <end> # <tags>` in utc, as in timewarrior's data files
+    Timewarrior,
+    // `i <start> <account>` / `o <end>` pairs in local time, for ledger
+    Timeclock,
+}
+
+fn format() -> Format {
+    match config::get_string("timetrack.format").as_deref() {
+        Some("ledger") | Some("timeclock") => Format::Timeclock,
+        _ => Format::Timewarrior,
+    }
+}
+
+fn entries_file(format: Format) -> PathBuf {
+    match config::get_string("timetrack.file") {
+        Some(file) => resolve_path(&file),
+        None => logging::data_directory().join(match format {
+            Format::Timewarrior => "timetrack.data",
+            Format::Timeclock => "timetrack.timeclock",
+        }),
+    }
+}
+
+// the running entry, shared by every host like a focus session
+fn state_file() -> PathBuf {
+    logging::data_directory().join("timetrack.json")
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+// days since the epoch to a proleptic gregorian date, and back
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146097 + doe - 719468
+}
+
+fn split_time(seconds: i64) -> (i64, u32, u32, i64, i64, i64) {
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+    let time = seconds.rem_euclid(86400);
+    (year, month, day, time / 3600, time % 3600 / 60, time % 60)
+}
+
+#[cfg(unix)]
+fn utc_offset(seconds: i64) -> i64 {
+    let time = seconds as libc::time_t;
+    let mut local = unsafe { std::mem::zeroed::<libc::tm>() };
+    if unsafe { libc::localtime_r(&time, &mut local) }.is_null() {
+        return 0;
+    }
+
+    local.tm_gmtoff
+}
+
+#[cfg(not(unix))]
+fn utc_offset(_seconds: i64) -> i64 {
+    0
+}
+
+fn timewarrior_time(seconds: i64) -> String {
+    let (year, month, day, hour, minute, second) = split_time(seconds);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year, month, day, hour, minute, second
+    )
+}
+
+fn timeclock_time(seconds: i64) -> String {
+    let (year, month, day, hour, minute, second) = split_time(seconds + utc_offset(seconds));
+    format!(
+        "{:04}/{:02}/{:02} {:02}:{:02}:{:02}",
+        year, month, day, hour, minute, second
+    )
+}
+
+// both formats are parsed into naive seconds, which is all a report needs
+fn parse_time(text: &str) -> Option<i64> {
+    let digits = text
+        .chars()
+        .filter(char::is_ascii_digit)
+        .collect::<String>();
+
+    if digits.len() != 14 {
+        return None;
+    }
+
+    let number = |range: std::ops::Range<usize>| digits[range].parse::<i64>().ok();
+    let days = days_from_civil(number(0..4)?, number(4..6)? as u32, number(6..8)? as u32);
+
+    Some(days * 86400 + number(8..10)? * 3600 + number(10..12)? * 60 + number(12..14)?)
+}
+
+fn quote_tag(label: &str) -> String {
+    if label.contains([' ', '"', '#']) {
+        format!("\"{}\"", label.replace('"', "'"))
+    } else {
+        label.to_string()
+    }
+}
+
+fn entry(format: Format, label: &str, started: i64, ended: i64) -> String {
+    match format {
+        Format::Timewarrior => format!(
+            "inc {} - {} # {}\n",
+            timewarrior_time(started),
+            timewarrior_time(ended),
+            quote_tag(label)
+        ),
+
+        Format::Timeclock => format!(
+            "i {} {}\no {}\n",
+            timeclock_time(started),
+            label,
+            timeclock_time(ended)
+        ),
+    }
+}
+
+fn running() -> Option<(String, i64)> {
+    let content = std::fs::read_to_string(state_file()).ok()?;
+    let state: Value = serde_json::from_str(&content).ok()?;
+
+    Some((
+        state.get("label")?.as_str()?.to_string(),
+        state.get("started")?.as_i64()?,
+    ))
+}
+
+// closes the running entry, if any, and appends it to the entries file
+fn finish() -> std::io::Result<Option<Value>> {
+    let Some((label, started)) = running() else {
+        return Ok(None);
+    };
+
+    let ended = now();
+    let format = format();
+    let path = entries_file(format);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    // appending and clearing the running entry have to happen together, or
+    // a crash in between would count the time twice
+    let content = entry(format, &label, started, ended);
+    let record = journal::begin(
+        "append",
+        json!({ "file": path, "content": content, "remove": state_file() }),
+    )?;
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?;
+
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+
+    std::fs::remove_file(state_file())?;
+    record.commit();
+
+    Ok(Some(json!({
+        "label": label,
+        "started": started,
+        "ended": ended,
+        "seconds": ended - started
+    })))
+}
+
+fn failure(cmd: &str, err: std::io::Error) -> Value {
+    error!("(timetrack::{}) Failed to save entry, error: {}", cmd, err);
+    json!({
+        "cmd": cmd,
+        "code": 2,
+        "error": err.to_string()
+    })
+}
+
+// a running entry is closed first, like `timew start`; without a label the
+// domain of `url` is used
+pub(crate) fn tt_start(label: Option<&str>, url: Option<&str>) -> Value {
+    let label = label
+        .map(String::from)
+        .or_else(|| {
+            let host = url_host(url?).unwrap_or(url?.to_string());
+            Some(host.strip_prefix("www.").unwrap_or(&host).to_string())
+        })
+        .filter(|v| !v.trim().is_empty())
+        .map(|v| v.replace(['\n', '\r'], " "));
+
+    let Some(label) = label else {
+        return json!({
+            "cmd": "tt_start",
+            "code": 1,
+            "error": "Expected a label or url"
+        });
+    };
+
+    let previous = match finish() {
+        Ok(previous) => previous,
+        Err(err) => return failure("tt_start", err),
+    };
+
+    let started = now();
+    let state = json!({ "label": label, "started": started });
+    let result = std::fs::create_dir_all(logging::data_directory())
+        .and_then(|_| write_atomic(&state_file(), state.to_string().as_bytes(), None));
+
+    if let Err(err) = result {
+        return failure("tt_start", err);
+    }
+
+    info!("(timetrack::tt_start) Tracking {}", label);
+    json!({
+        "cmd": "tt_start",
+        "code": SUCCESS_CODE,
+        "label": label,
+        "started": started,
+        "previous": previous
+    })
+}
+
+pub(crate) fn tt_stop() -> Value {
+    match finish() {
+        Ok(Some(entry)) => {
+            info!("(timetrack::tt_stop) Stopped {}", entry["label"]);
+            json!({
+                "cmd": "tt_stop",
+                "code": SUCCESS_CODE,
+                "entry": entry
+            })
+        }
+
+        Ok(None) => json!({
+            "cmd": "tt_stop",
+            "code": 1,
+            "error": "Nothing is being tracked"
+        }),
+
+        Err(err) => failure("tt_stop", err),
+    }
+}
+
+// (label, naive start, naive end) for every complete entry in the file
+fn read_entries(format: Format) -> Vec<(String, i64, i64)> {
+    let content = std::fs::read_to_string(entries_file(format)).unwrap_or_default();
+    let mut entries = Vec::new();
+
+    match format {
+        Format::Timewarrior => {
+            let regex = Regex::new(TIMEWARRIOR_LINE).unwrap();
+            for captures in content.lines().filter_map(|v| regex.captures(v)) {
+                let label = captures.get(3).map_or("", |v| v.as_str()).trim_matches('"');
+                if let (Some(start), Some(end)) =
+                    (parse_time(&captures[1]), parse_time(&captures[2]))
+                {
+                    entries.push((label.to_string(), start, end));
+                }
+            }
+        }
+
+        Format::Timeclock => {
+            let clock_in = Regex::new(TIMECLOCK_IN).unwrap();
+            let clock_out = Regex::new(TIMECLOCK_OUT).unwrap();
+
+            let mut open = None;
+            for line in content.lines() {
+                if let Some(captures) = clock_in.captures(line) {
+                    open = parse_time(&captures[1]).map(|v| (captures[2].trim().to_string(), v));
+                } else if let Some(captures) = clock_out.captures(line) {
+                    if let (Some((label, start)), Some(end)) =
+                        (open.take(), parse_time(&captures[1]))
+                    {
+                        entries.push((label, start, end));
+                    }
+                }
+            }
+        }
+    }
+
+    entries
+}
+
+// totals per label; `since` is a `YYYY-MM-DD` date, entries starting before
+// it are left out
+pub(crate) fn tt_report(since: Option<&str>) -> Value {
+    let since = match since {
+        Some(date) => match parse_time(&format!("{}000000", date)) {
+            Some(v) => Some(v),
+            None => {
+                return json!({
+                    "cmd": "tt_report",
+                    "code": 1,
+                    "error": "Expected since as YYYY-MM-DD"
+                })
+            }
+        },
+
+        None => None,
+    };
+
+    let mut totals = BTreeMap::<String, i64>::new();
+    for (label, start, end) in read_entries(format()) {
+        if since.is_none_or(|v| start >= v) {
+            *totals.entry(label).or_default() += (end - start).max(0);
+        }
+    }
+
+    let total = totals.values().sum::<i64>();
+    let content = totals
+        .into_iter()
+        .map(|(label, seconds)| json!({ "label": label, "seconds": seconds }))
+        .collect::<Vec<_>>();
+
+    let running = running().map(|(label, started)| {
+        json!({
+            "label": label,
+            "started": started,
+            "seconds": now() - started
+        })
+    });
+
+    json!({
+        "cmd": "tt_report",
+        "code": SUCCESS_CODE,
+        "content": content,
+        "total": total,
+        "running": running,
+        "file": entries_file(format())
+    })
+}
@@ -0,0 +1,122 @@
+use std::process::{Command, Stdio};
+
+use serde_json::{json, Value};
+
+use crate::commands::SUCCESS_CODE;
+
+const DEFAULT_LIMIT: usize = 50;
+
+struct Entry {
+    path: String,
+    score: f64,
+}
+
+fn query_zoxide(terms: &[&str]) -> Option<Vec<Entry>> {
+    let output = Command::new("zoxide")
+        .args(["query", "--list", "--score", "--"])
+        .args(terms)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    // zoxide exits with 1 when nothing matches, which is still a valid answer
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let entries = stdout
+        .lines()
+        .filter_map(|line| {
+            let (score, path) = line.trim_start().split_once(' ')?;
+            Some(Entry {
+                path: path.to_string(),
+                score: score.parse().ok()?,
+            })
+        })
+        .collect();
+
+    Some(entries)
+}
+
+// autojump keeps a plain `weight\tpath` database, matched in the same spirit
+// as zoxide: every term has to appear in order, the last one in the basename
+fn query_autojump(terms: &[&str]) -> Option<Vec<Entry>> {
+    let path = std::env::var("AUTOJUMP_DATA_DIR")
+        .map(std::path::PathBuf::from)
+        .unwrap_or(dirs::data_dir()?.join("autojump"))
+        .join("autojump.txt");
+
+    let content = std::fs::read_to_string(path).ok()?;
+    let terms = terms.iter().map(|v| v.to_lowercase()).collect::<Vec<_>>();
+
+    let mut entries = content
+        .lines()
+        .filter_map(|line| {
+            let (score, path) = line.split_once('\t')?;
+            Some(Entry {
+                path: path.to_string(),
+                score: score.parse().ok()?,
+            })
+        })
+        .filter(|entry| {
+            let path = entry.path.to_lowercase();
+            let mut rest = path.as_str();
+            for term in &terms {
+                match rest.find(term.as_str()) {
+                    Some(index) => rest = &rest[index + term.len()..],
+                    None => return false,
+                }
+            }
+
+            match terms.last() {
+                Some(last) => path
+                    .rsplit('/')
+                    .next()
+                    .is_some_and(|name| name.contains(last.as_str())),
+                None => true,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    entries.sort_by(|a, b| b.score.total_cmp(&a.score));
+    Some(entries)
+}
+
+pub(crate) fn zoxide_query(query: &str, limit: Option<usize>) -> Value {
+    let terms = query.split_whitespace().collect::<Vec<_>>();
+
+    let (source, entries) = if let Some(entries) = query_zoxide(&terms) {
+        ("zoxide", entries)
+    } else if let Some(entries) = query_autojump(&terms) {
+        ("autojump", entries)
+    } else {
+        error!("(zoxide::zoxide_query) Neither zoxide nor autojump is available");
+        return json!({
+            "cmd": "zoxide_query",
+            "code": 1
+        });
+    };
+
+    let entries = entries
+        .into_iter()
+        .take(limit.unwrap_or(DEFAULT_LIMIT))
+        .map(|entry| {
+            json!({
+                "path": entry.path,
+                "score": entry.score
+            })
+        })
+        .collect::<Vec<_>>();
+
+    info!(
+        "(zoxide::zoxide_query) query: '{}', source: {}, results: {}",
+        query,
+        source,
+        entries.len()
+    );
+
+    json!({
+        "cmd": "zoxide_query",
+        "code": SUCCESS_CODE,
+        "source": source,
+        "content": entries
+    })
+}
@@ -0,0 +1,1101 @@
+// golden replies for the messaging protocol; every host runs against a fresh
+// sandbox home so the replies only depend on the fixtures written here.
+// after an intended change, review and accept them with `cargo insta review`
+#![cfg(target_os = "linux")]
+
+use std::{
+    io::{Read, Write},
+    path::Path,
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+use insta::{assert_json_snapshot, assert_snapshot};
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+const BINARY: &str = env!("CARGO_BIN_EXE_tridactyl");
+
+struct Host {
+    sandbox: TempDir,
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: ChildStdout,
+}
+
+fn sandbox_command(sandbox: &Path) -> Command {
+    let mut command = Command::new(BINARY);
+    command
+        .env_clear()
+        .env("PATH", "/usr/bin:/bin")
+        .env("HOME", sandbox.join("home"))
+        .env("XDG_CONFIG_HOME", sandbox.join("config"))
+        .env("XDG_DATA_HOME", sandbox.join("data"))
+        .env("XDG_STATE_HOME", sandbox.join("state"))
+        .env("XDG_CACHE_HOME", sandbox.join("cache"))
+        .env("XDG_RUNTIME_DIR", sandbox.join("runtime"))
+        .env("TMPDIR", sandbox.join("tmp"))
+        .env("SNAPSHOT_VAR", "fixture")
+        .env("LANG", "C");
+
+    command
+}
+
+impl Host {
+    // `config` becomes native.json, with `@` standing for the sandbox
+    fn start(config: Value) -> Host {
+        Host::start_with(config, &[])
+    }
+
+    // for what the host reads as it starts, `fixtures` are written before it
+    // runs; `@` in their content stands for the sandbox too
+    fn start_with(config: Value, fixtures: &[(&str, &[u8])]) -> Host {
+        let sandbox = tempfile::tempdir().unwrap();
+        for directory in [
+            "home",
+            "config/tridactyl",
+            "data",
+            "state",
+            "cache",
+            "runtime",
+            "tmp",
+        ] {
+            std::fs::create_dir_all(sandbox.path().join(directory)).unwrap();
+        }
+
+        let config = config
+            .to_string()
+            .replace('@', &sandbox.path().to_string_lossy());
+        std::fs::write(sandbox.path().join("config/tridactyl/native.json"), config).unwrap();
+
+        for (name, content) in fixtures {
+            let path = sandbox.path().join(name);
+            let content =
+                String::from_utf8_lossy(content).replace('@', &sandbox.path().to_string_lossy());
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+
+        let mut child = sandbox_command(sandbox.path())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        Host {
+            stdin: child.stdin.take(),
+            stdout: child.stdout.take().unwrap(),
+            sandbox,
+            child,
+        }
+    }
+
+    fn path(&self, name: &str) -> String {
+        self.sandbox.path().join(name).to_string_lossy().to_string()
+    }
+
+    fn fixture(&self, name: &str, content: &[u8]) {
+        let path = self.sandbox.path().join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    // sends one message and returns the reply, with the sandbox path replaced
+    // so snapshots do not depend on where it was created
+    fn send(&mut self, message: Value) -> Value {
+        let message = message
+            .to_string()
+            .replace('@', &self.sandbox.path().to_string_lossy());
+
+        self.send_raw(message.as_bytes())
+    }
+
+    // sends one frame holding `body` as it is, json or not
+    fn send_raw(&mut self, body: &[u8]) -> Value {
+        let stdin = self.stdin.as_mut().unwrap();
+        stdin.write_all(&(body.len() as u32).to_ne_bytes()).unwrap();
+        stdin.write_all(body).unwrap();
+        stdin.flush().unwrap();
+
+        self.receive()
+    }
+
+    // reads the next frame, a reply or an event
+    fn receive(&mut self) -> Value {
+        let mut length = [0u8; 4];
+        self.stdout.read_exact(&mut length).unwrap();
+        let mut reply = vec![0u8; u32::from_ne_bytes(length) as usize];
+        self.stdout.read_exact(&mut reply).unwrap();
+
+        let reply = String::from_utf8(reply)
+            .unwrap()
+            .replace(&*self.sandbox.path().to_string_lossy(), "[sandbox]");
+        serde_json::from_str(&reply).unwrap()
+    }
+}
+
+impl Drop for Host {
+    fn drop(&mut self) {
+        drop(self.stdin.take());
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn schema() {
+    let sandbox = tempfile::tempdir().unwrap();
+    let output = sandbox_command(sandbox.path())
+        .arg("schema")
+        .output()
+        .unwrap();
+    assert_snapshot!(String::from_utf8(output.stdout).unwrap());
+}
+
+// the registry is kept by hand next to `dispatch`; every command handled there
+// needs a row for strict mode and the schema, and every row a handler
+#[test]
+fn registry_matches_dispatch() {
+    let sandbox = tempfile::tempdir().unwrap();
+    let output = sandbox_command(sandbox.path())
+        .arg("schema")
+        .output()
+        .unwrap();
+    let schema: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let mut registered = schema["$defs"]
+        .as_object()
+        .unwrap()
+        .keys()
+        .filter_map(|v| v.strip_suffix(".request"))
+        .map(String::from)
+        .collect::<Vec<_>>();
+    registered.sort();
+
+    let main = include_str!("../src/main.rs");
+    let dispatch = &main[main.find("fn dispatch(").unwrap()..];
+    let dispatch = &dispatch[..dispatch.find("\n}\n").unwrap()];
+    let mut handled = dispatch
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix('"'))
+        .filter_map(|line| line.split_once("\" =>"))
+        .map(|(name, _)| name.to_string())
+        .collect::<Vec<_>>();
+    handled.sort();
+
+    assert_eq!(registered, handled);
+}
+
+#[test]
+fn handshake() {
+    let mut host = Host::start(json!({}));
+
+    // which tools are installed differs between machines
+    let tools = {
+        let mut settings = insta::Settings::clone_current();
+        for field in [
+            ".capabilities.*.available",
+            ".capabilities.*.tool",
+            ".content.*.available",
+            ".content.*.tool",
+        ] {
+            settings.add_redaction(field, "[machine]");
+        }
+
+        settings.add_redaction(".probed", "[time]");
+        settings
+    };
+
+    tools.bind(|| {
+        assert_json_snapshot!("hello", host.send(json!({ "cmd": "hello", "protocol": 1 })));
+        assert_json_snapshot!(
+            "capabilities",
+            host.send(json!({ "cmd": "capabilities", "refresh": true }))
+        );
+    });
+    assert_json_snapshot!("version", host.send(json!({ "cmd": "version" })));
+    assert_json_snapshot!(
+        "health",
+        host.send(json!({ "cmd": "health" })),
+        {
+            ".pid" => "[pid]",
+            ".uptime" => "[uptime]",
+            ".maintenance" => "[maintenance]"
+        }
+    );
+    assert_json_snapshot!("unknown", host.send(json!({ "cmd": "nonexistent" })));
+    assert_json_snapshot!("not_json", host.send_raw(b"{ not json"));
+    assert_json_snapshot!(
+        "too_large",
+        host.send_raw(&vec![b' '; 64 * 1024 * 1024 + 1])
+    );
+    assert_json_snapshot!(
+        "ppid",
+        host.send(json!({ "cmd": "ppid" })),
+        { ".content" => "[pid]" }
+    );
+    assert_json_snapshot!(
+        "env",
+        host.send(json!({ "cmd": "env", "var": "SNAPSHOT_VAR" }))
+    );
+    assert_json_snapshot!(
+        "which_missing",
+        host.send(json!({ "cmd": "which", "name": "no-such-program" }))
+    );
+    assert_json_snapshot!(
+        "net_check_refused",
+        host.send(json!({ "cmd": "net_check", "host": "127.0.0.1", "port": 1 }))
+    );
+    assert_json_snapshot!(
+        "net_check_https",
+        host.send(json!({ "cmd": "net_check", "url": "https://example.com/" }))
+    );
+    assert_json_snapshot!(
+        "net_check_line_break",
+        host.send(json!({ "cmd": "net_check", "url": "http://127.0.0.1:1/ HTTP/1.1\r\nHost: x\r\n\r\nDELETE /" }))
+    );
+    assert_json_snapshot!(
+        "set_loglevel",
+        host.send(json!({ "cmd": "set_loglevel", "level": "debug" }))
+    );
+    assert_json_snapshot!(
+        "set_loglevel_invalid",
+        host.send(json!({ "cmd": "set_loglevel", "level": "loud" }))
+    );
+    assert_json_snapshot!(
+        "kill_every_process",
+        host.send(json!({ "cmd": "kill", "pid": 4294967295u64, "signal": "0" }))
+    );
+    assert_json_snapshot!(
+        "kill_init",
+        host.send(json!({ "cmd": "kill", "pid": 1, "signal": "0" }))
+    );
+}
+
+#[test]
+fn files() {
+    let mut host = Host::start(json!({ "security": { "fs": { "roots": ["@"] } } }));
+    host.fixture("files/a.txt", b"first\n");
+    host.fixture("files/table.csv", b"name,count\nfoo,1\nbar,2\n");
+    host.fixture("files/image.png", b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR");
+    host.fixture("files/latin1.txt", b"caf\xe9\n");
+
+    assert_json_snapshot!(
+        "read",
+        host.send(json!({ "cmd": "read", "file": "@/files/a.txt" }))
+    );
+    assert_json_snapshot!(
+        "read_missing",
+        host.send(json!({ "cmd": "read", "file": "@/files/missing" }))
+    );
+    assert_json_snapshot!(
+        "read_lossy",
+        host.send(json!({ "cmd": "read", "file": "@/files/latin1.txt" }))
+    );
+    assert_json_snapshot!(
+        "read_base64",
+        host.send(json!({ "cmd": "read", "file": "@/files/latin1.txt", "encoding": "base64" }))
+    );
+    assert_json_snapshot!(
+        "write",
+        host.send(json!({ "cmd": "write", "file": "@/files/b.txt", "content": "second\n" }))
+    );
+    let written = host.send(json!({
+        "cmd": "write",
+        "file": "@/files/binary.bin",
+        "content": "data:application/octet-stream;base64,/wCA"
+    }));
+    assert_eq!(written["code"], 0, "{}", written);
+    assert_eq!(
+        std::fs::read(host.path("files/binary.bin")).unwrap(),
+        b"\xff\x00\x80"
+    );
+    assert_json_snapshot!(
+        "write_data_url_invalid",
+        host.send(json!({
+            "cmd": "write",
+            "file": "@/files/binary.bin",
+            "content": "data:text/plain;base64,not base64!"
+        }))
+    );
+    assert_json_snapshot!(
+        "read_many",
+        host.send(json!({ "cmd": "read_many", "files": ["@/files/a.txt", "@/files/b.txt"] }))
+    );
+    assert_json_snapshot!(
+        "read_table",
+        host.send(json!({ "cmd": "read_table", "file": "@/files/table.csv", "header": true }))
+    );
+    assert_json_snapshot!(
+        "write_many",
+        host.send(json!({
+            "cmd": "write_many",
+            "files": { "@/files/c.txt": "third", "@/files/d.txt": "fourth" }
+        }))
+    );
+    assert_json_snapshot!(
+        "mkdir",
+        host.send(json!({ "cmd": "mkdir", "dir": "@/files/nested" }))
+    );
+    assert_json_snapshot!(
+        "copy",
+        host.send(json!({ "cmd": "copy", "from": "@/files/a.txt", "to": "@/files/nested/a.txt" }))
+    );
+    assert_json_snapshot!(
+        "copy_same_file",
+        host.send(json!({ "cmd": "copy", "from": "@/files/a.txt", "to": "@/files/./a.txt" }))
+    );
+    assert_json_snapshot!(
+        "move",
+        host.send(json!({ "cmd": "move", "from": "@/files/c.txt", "to": "@/files/nested/c.txt" }))
+    );
+    assert_json_snapshot!(
+        "move_exists",
+        host.send(json!({ "cmd": "move", "from": "@/files/a.txt", "to": "@/files/nested/c.txt" }))
+    );
+    assert_json_snapshot!(
+        "move_missing_source",
+        host.send(json!({ "cmd": "move", "from": "@/files/c.txt", "to": "@/files/nested/d.txt" }))
+    );
+    host.fixture("tmp/downloads/tmp1/download.txt", b"downloaded\n");
+    assert_json_snapshot!(
+        "move_cleanup",
+        host.send(json!({ "cmd": "move", "from": "@/tmp/downloads/tmp1/download.txt", "to": "@/files", "cleanup": true }))
+    );
+    assert_json_snapshot!(
+        "move_cleanup_removed",
+        host.send(json!({ "cmd": "list_dir", "path": "@/tmp/downloads" }))
+    );
+    host.fixture("files/downloads/last.txt", b"downloaded\n");
+    host.send(json!({ "cmd": "move", "from": "@/files/downloads/last.txt", "to": "@/files", "cleanup": true }));
+    assert_json_snapshot!(
+        "move_cleanup_kept",
+        host.send(json!({ "cmd": "list_dir", "path": "@/files/downloads" }))
+    );
+    assert_json_snapshot!(
+        "list_dir",
+        host.send(json!({ "cmd": "list_dir", "path": "@/files/nested" })),
+        {
+            ".files" => insta::sorted_redaction(),
+            ".entries" => insta::sorted_redaction(),
+            ".entries[].mtime" => "[time]"
+        }
+    );
+    host.fixture("files/listing/b.txt", b"abc");
+    host.fixture("files/listing/.hidden", b"");
+    host.fixture("files/listing/sub/x.txt", b"");
+    std::os::unix::fs::symlink("b.txt", host.path("files/listing/link")).unwrap();
+    assert_json_snapshot!(
+        "list_dir_entries",
+        host.send(json!({ "cmd": "list_dir", "path": "@/files/listing", "sort": "name" })),
+        { ".entries[].mtime" => "[time]" }
+    );
+    assert_json_snapshot!(
+        "list_dir_visible",
+        host.send(json!({
+            "cmd": "list_dir",
+            "path": "@/files/listing",
+            "sort": "kind",
+            "show_hidden": false
+        })),
+        { ".entries[].mtime" => "[time]" }
+    );
+    assert_json_snapshot!(
+        "mimetype",
+        host.send(json!({ "cmd": "mimetype", "file": "@/files/image.png" }))
+    );
+    assert_json_snapshot!(
+        "mimetype_text",
+        host.send(json!({ "cmd": "mimetype", "file": "@/files/table.csv" }))
+    );
+}
+
+#[test]
+fn temp_directory() {
+    let mut host = Host::start(json!({
+        "temp": { "dir": "@/scratch" },
+        "security": { "fs": { "roots": ["@"] } }
+    }));
+
+    let reply = host.send(json!({ "cmd": "temp", "prefix": "draft", "content": "kept\n" }));
+    let path = reply["content"].as_str().unwrap();
+    assert!(path.starts_with("[sandbox]/scratch/tmp_draft_"), "{}", path);
+    assert_json_snapshot!(
+        "temp_kept",
+        host.send(json!({ "cmd": "read", "file": path.replace("[sandbox]", "@") }))
+    );
+
+    let reply = host.send(json!({ "cmd": "temp", "prefix": "draft", "dir": "@/files/edit" }));
+    let path = reply["content"].as_str().unwrap();
+    assert!(
+        path.starts_with("[sandbox]/files/edit/tmp_draft_"),
+        "{}",
+        path
+    );
+
+    assert_json_snapshot!(
+        "temp_outside",
+        host.send(json!({ "cmd": "temp", "prefix": "draft", "dir": "/" }))
+    );
+}
+
+// the sweep only removes what `temp` made, not look-alikes of the user's
+#[test]
+fn temp_sweep() {
+    let mut host = Host::start_with(
+        json!({
+            "temp": { "dir": "@/scratch" },
+            "maintenance": { "temp_max_age": 0 },
+            "security": { "fs": { "roots": ["@"] } }
+        }),
+        &[
+            ("data/tridactyl/temp_files", b"@/scratch/tmp_draft_1.txt\n"),
+            ("scratch/tmp_draft_1.txt", b"made by temp\n"),
+            ("scratch/tmp_notes_2.txt", b"the user's\n"),
+        ],
+    );
+
+    for _ in 0..50 {
+        let health = host.send(json!({ "cmd": "health" }));
+        if health["maintenance"][1]["runs"] != 0 {
+            break;
+        }
+
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+
+    assert_json_snapshot!(
+        "temp_swept",
+        host.send(json!({ "cmd": "list_dir", "path": "@/scratch" })),
+        { ".entries[].mtime" => "[time]" }
+    );
+}
+
+#[test]
+fn tridactylrc() {
+    let mut host = Host::start(json!({ "security": { "fs": { "roots": ["@"] } } }));
+
+    assert_json_snapshot!(
+        "getconfig_missing",
+        host.send(json!({ "cmd": "getconfig" }))
+    );
+    assert_json_snapshot!(
+        "writerc",
+        host.send(json!({ "cmd": "writerc", "file": "@/config/tridactyl/tridactylrc", "content": "set smoothscroll true\n" }))
+    );
+    assert_json_snapshot!(
+        "getconfigpath",
+        host.send(json!({ "cmd": "getconfigpath" }))
+    );
+    assert_json_snapshot!("getconfig", host.send(json!({ "cmd": "getconfig" })));
+
+    host.fixture(
+        "config/tridactyl/tridactylrc.work",
+        b"set theme shydactyl\n",
+    );
+    assert_json_snapshot!(
+        "getconfig_profile",
+        host.send(json!({ "cmd": "getconfig", "profile": "work" }))
+    );
+    assert_json_snapshot!(
+        "getconfigpath_profile_fallback",
+        host.send(json!({ "cmd": "getconfigpath", "profile": "personal" }))
+    );
+    assert_json_snapshot!(
+        "getconfig_profile_invalid",
+        host.send(json!({ "cmd": "getconfig", "profile": "../work" }))
+    );
+
+    let mut host = Host::start(json!({
+        "rc": { "paths": ["@/dotfiles/tridactylrc"] },
+        "security": { "fs": { "roots": ["@/dotfiles"] } }
+    }));
+    host.fixture("dotfiles/tridactylrc", b"set theme dark\n");
+    assert_json_snapshot!(
+        "getconfigpath_configured",
+        host.send(json!({ "cmd": "getconfigpath" }))
+    );
+
+    let mut linked = Host::start(json!({}));
+    linked.fixture("dotfiles/tridactylrc", b"set theme dark\n");
+    std::os::unix::fs::symlink(
+        linked.path("dotfiles/tridactylrc"),
+        linked.path("home/.tridactylrc"),
+    )
+    .unwrap();
+    assert_json_snapshot!(
+        "getconfigpath_symlink",
+        linked.send(json!({ "cmd": "getconfigpath" }))
+    );
+    assert_json_snapshot!(
+        "getconfigpath_resolved",
+        linked.send(json!({ "cmd": "getconfigpath", "resolve": true }))
+    );
+
+    host.fixture(
+        "dotfiles/tridactylrc",
+        b"set theme dark\nsource binds\nsource -url https://example.com/rc\n",
+    );
+    host.fixture("dotfiles/binds", b"bind J tabprev\nsource ./quickmarks\n");
+    host.fixture("dotfiles/quickmarks", b"quickmark g https://github.com\n");
+    assert_json_snapshot!(
+        "getconfig_includes",
+        host.send(json!({ "cmd": "getconfig" }))
+    );
+
+    host.fixture("dotfiles/quickmarks", b"source binds\n");
+    assert_json_snapshot!(
+        "getconfig_include_cycle",
+        host.send(json!({ "cmd": "getconfig" }))
+    );
+
+    host.fixture("dotfiles/quickmarks", b"source ../elsewhere/quickmarks\n");
+    host.fixture("elsewhere/quickmarks", b"quickmark g https://github.com\n");
+    assert_json_snapshot!(
+        "getconfig_include_outside",
+        host.send(json!({ "cmd": "getconfig" }))
+    );
+
+    let mut host = Host::start(json!({ "rc": { "templating": true } }));
+    host.fixture(
+        "config/tridactyl/tridactylrc",
+        b"set editorcmd ${SNAPSHOT_VAR} %{home}\nset ${NO_SUCH_VAR} %{nothing}\n",
+    );
+    assert_json_snapshot!(
+        "getconfig_templating",
+        host.send(json!({ "cmd": "getconfig" }))
+    );
+}
+
+#[test]
+fn processes() {
+    let mut host = Host::start(json!({}));
+
+    assert_json_snapshot!(
+        "run",
+        host.send(json!({ "cmd": "run", "command": "printf out; printf err >&2; exit 3" }))
+    );
+    assert_json_snapshot!(
+        "run_input",
+        host.send(json!({ "cmd": "run", "command": "tr a-z A-Z", "content": "shout" }))
+    );
+    assert_json_snapshot!(
+        "exec",
+        host.send(json!({ "cmd": "exec", "program": "echo", "args": ["one", "two"] }))
+    );
+    assert_json_snapshot!(
+        "exec_missing_program",
+        host.send(json!({ "cmd": "exec", "program": "no-such-program" }))
+    );
+    assert_json_snapshot!(
+        "run_async_missing_program",
+        host.send(json!({ "cmd": "run_async", "command": "no-such-program" }))
+    );
+    assert_json_snapshot!("jobs_list", host.send(json!({ "cmd": "jobs_list" })));
+    assert_json_snapshot!(
+        "job_status_missing",
+        host.send(json!({ "cmd": "job_status", "job": 42 }))
+    );
+    assert_json_snapshot!(
+        "kill_untracked",
+        host.send(json!({ "cmd": "kill", "pid": 2, "signal": "0" }))
+    );
+    assert_json_snapshot!(
+        "kill_unknown_signal",
+        host.send(json!({ "cmd": "kill", "pid": 2, "signal": "LOUD" }))
+    );
+    let job = host.send(json!({ "cmd": "run_async", "command": "sleep 5" }));
+    let kill = host.send(json!({ "cmd": "kill", "pid": job["pid"], "signal": "TERM" }));
+    assert_eq!(kill["code"], 0, "{}", kill);
+    assert_json_snapshot!("timer_list", host.send(json!({ "cmd": "timer_list" })));
+    assert_json_snapshot!(
+        "timer_cancel_missing",
+        host.send(json!({ "cmd": "timer_cancel", "timer": 42 }))
+    );
+}
+
+#[test]
+fn run_policy() {
+    let mut host = Host::start(json!({
+        "security": {
+            "run": {
+                "allow": ["echo"],
+                "allow_patterns": ["tr a-z A-Z", "echo a \\| .*"],
+                "deny": ["/usr/bin/printf"],
+                "deny_patterns": ["--force"]
+            }
+        },
+        "open": { "command": "xdg-open %f" }
+    }));
+    host.fixture("home/page.html", b"<p>page</p>\n");
+
+    assert_json_snapshot!(
+        "run_allowed",
+        host.send(json!({ "cmd": "run", "command": "echo allowed" }))
+    );
+    assert_json_snapshot!(
+        "run_pattern_allowed",
+        host.send(json!({ "cmd": "run", "command": "tr a-z A-Z", "content": "shout" }))
+    );
+    assert_json_snapshot!(
+        "run_not_allowed",
+        host.send(json!({ "cmd": "run", "command": "rm -r @/files" }))
+    );
+    assert_json_snapshot!(
+        "run_operators_denied",
+        host.send(json!({ "cmd": "run", "command": "echo a; rm -r @/files" }))
+    );
+    assert_json_snapshot!(
+        "run_denied_in_path",
+        host.send(json!({ "cmd": "run", "command": "printf x" }))
+    );
+    assert_json_snapshot!(
+        "exec_denied_through_symlink",
+        host.send(json!({ "cmd": "exec", "program": "/bin/printf", "args": ["x"] }))
+    );
+    assert_json_snapshot!(
+        "run_denied_after_operator",
+        host.send(json!({ "cmd": "run", "command": "echo a | printf b" }))
+    );
+    assert_json_snapshot!(
+        "exec_pattern_denied",
+        host.send(json!({ "cmd": "exec", "program": "echo", "args": ["--force"] }))
+    );
+    assert_json_snapshot!(
+        "run_async_not_allowed",
+        host.send(json!({ "cmd": "run_async", "command": "sleep 1" }))
+    );
+    assert_json_snapshot!(
+        "help_lookup_not_allowed",
+        host.send(json!({ "cmd": "help_lookup", "program": "ls", "source": "help" }))
+    );
+    assert_json_snapshot!(
+        "open_not_allowed",
+        host.send(json!({ "cmd": "open", "target": "~/page.html" }))
+    );
+}
+
+// the dialog accepts whatever question mentions "yes"
+#[test]
+fn confirm() {
+    let mut host = Host::start(json!({
+        "security": {
+            "fs": { "roots": ["@"] },
+            "confirm": {
+                "enabled": true,
+                "dialog": "sh -c 'case \"$0\" in *yes*) exit 0;; esac; exit 1' %m"
+            }
+        }
+    }));
+    host.fixture("files/existing.txt", b"old\n");
+    host.fixture("home/existing.txt", b"old\n");
+
+    assert_json_snapshot!(
+        "confirm_run_accepted",
+        host.send(json!({ "cmd": "run", "command": "echo yes" }))
+    );
+    assert_json_snapshot!(
+        "confirm_run_refused",
+        host.send(json!({ "cmd": "run", "command": "echo no" }))
+    );
+    assert_json_snapshot!(
+        "confirm_overwrite_refused",
+        host.send(json!({ "cmd": "write", "file": "@/files/existing.txt", "content": "new" }))
+    );
+    assert_json_snapshot!(
+        "confirm_new_file",
+        host.send(json!({ "cmd": "write", "file": "@/files/new.txt", "content": "new" }))
+    );
+    assert_json_snapshot!(
+        "confirm_home",
+        host.send(json!({ "cmd": "write", "file": "~/existing.txt", "content": "new" }))
+    );
+}
+
+#[test]
+fn desktop() {
+    let mut host = Host::start(json!({}));
+
+    assert_json_snapshot!(
+        "clipboard_without_tool",
+        host.send(json!({ "cmd": "clipboard", "action": "get" }))
+    );
+    assert_json_snapshot!(
+        "pick_without_launcher",
+        host.send(json!({ "cmd": "pick", "choices": ["a", "b"] }))
+    );
+    assert_json_snapshot!(
+        "open_missing",
+        host.send(json!({ "cmd": "open", "target": "@/missing.pdf" }))
+    );
+    assert_json_snapshot!(
+        "secret_disabled",
+        host.send(json!({ "cmd": "secret", "attributes": { "service": "github" } }))
+    );
+    assert_json_snapshot!("fix_manifest", host.send(json!({ "cmd": "fix_manifest" })));
+    assert_json_snapshot!("profiles", host.send(json!({ "cmd": "profiles" })));
+    assert_json_snapshot!(
+        "rewrite_url",
+        host.send(json!({ "cmd": "rewrite_url", "url": "https://example.com/" }))
+    );
+}
+
+// a manifest set up with `--extensions` and `--bin-path` is left as it is
+#[test]
+fn manifest_repair() {
+    let mut host = Host::start(json!({}));
+    host.fixture(
+        "home/.mozilla/firefox/profiles.ini",
+        b"[Profile0]\nName=default\nPath=default\n",
+    );
+    host.fixture(
+        "home/.mozilla/native-messaging-hosts/tridactyl.json",
+        json!({
+            "name": "tridactyl",
+            "description": "Tridactyl native command handler",
+            "path": "/bin/sh",
+            "type": "stdio",
+            "allowed_extensions": [
+                "tridactyl.vim@cmcaine.co.uk",
+                "tridactyl.vim.betas@cmcaine.co.uk",
+                "tridactyl.vim.betas.nonewtab@cmcaine.co.uk",
+                "fork@example.com"
+            ]
+        })
+        .to_string()
+        .as_bytes(),
+    );
+
+    assert_json_snapshot!(
+        "fix_manifest_kept",
+        host.send(json!({ "cmd": "fix_manifest" }))
+    );
+}
+
+#[test]
+fn notes() {
+    let mut host = Host::start(json!({}));
+
+    assert_json_snapshot!(
+        "note_set",
+        host.send(json!({ "cmd": "note_set", "key": "https://www.example.com/page", "content": "remember this" }))
+    );
+    assert_json_snapshot!(
+        "note_get",
+        host.send(json!({ "cmd": "note_get", "key": "example.com" }))
+    );
+    assert_json_snapshot!(
+        "note_search",
+        host.send(json!({ "cmd": "note_search", "query": "REMEMBER" }))
+    );
+}
+
+#[test]
+fn tracking() {
+    let mut host = Host::start(json!({}));
+    let times = {
+        let mut settings = insta::Settings::clone_current();
+        for field in [
+            ".session.started",
+            ".session.ends",
+            ".session.remaining",
+            ".started",
+            ".entry.started",
+            ".entry.ended",
+            ".entry.seconds",
+        ] {
+            settings.add_redaction(field, "[time]");
+        }
+
+        settings
+    };
+
+    times.bind(|| {
+        assert_json_snapshot!("focus_status", host.send(json!({ "cmd": "focus_status" })));
+        assert_json_snapshot!(
+            "focus_start_too_long",
+            host.send(json!({ "cmd": "focus_start", "duration": u64::MAX }))
+        );
+        assert_json_snapshot!(
+            "focus_start",
+            host.send(json!({ "cmd": "focus_start", "duration": 60000, "label": "work", "block": ["example.com"] }))
+        );
+        assert_json_snapshot!(
+            "should_block",
+            host.send(json!({ "cmd": "should_block", "domains": ["www.example.com", "example.org"] }))
+        );
+        assert_json_snapshot!("focus_stop", host.send(json!({ "cmd": "focus_stop" })));
+        assert_json_snapshot!(
+            "tt_start",
+            host.send(json!({ "cmd": "tt_start", "url": "https://www.example.com/" }))
+        );
+        assert_json_snapshot!("tt_stop", host.send(json!({ "cmd": "tt_stop" })));
+        assert_json_snapshot!(
+            "tt_report_bad_date",
+            host.send(json!({ "cmd": "tt_report", "since": "yesterday" }))
+        );
+    });
+}
+
+#[test]
+fn fs_sandbox() {
+    let mut host = Host::start(json!({}));
+    host.fixture("home/notes.txt", b"inside\n");
+    host.fixture("files/secret.txt", b"outside\n");
+    std::os::unix::fs::symlink(host.path("files"), host.path("home/link")).unwrap();
+    std::os::unix::fs::symlink(host.path("files/new.txt"), host.path("home/dangling")).unwrap();
+
+    assert_json_snapshot!(
+        "fs_inside",
+        host.send(json!({ "cmd": "read", "file": "~/notes.txt" }))
+    );
+    assert_json_snapshot!(
+        "fs_outside",
+        host.send(json!({ "cmd": "read", "file": "@/files/secret.txt" }))
+    );
+    assert_json_snapshot!(
+        "fs_traversal",
+        host.send(json!({ "cmd": "read", "file": "~/../files/secret.txt" }))
+    );
+    assert_json_snapshot!(
+        "fs_symlink",
+        host.send(json!({ "cmd": "read", "file": "~/link/secret.txt" }))
+    );
+    assert_json_snapshot!(
+        "fs_dangling_symlink",
+        host.send(json!({ "cmd": "write", "file": "~/dangling", "content": "escaped" }))
+    );
+    assert!(!std::path::Path::new(&host.path("files/new.txt")).exists());
+    assert_json_snapshot!(
+        "fs_move_outside",
+        host.send(json!({ "cmd": "move", "from": "~/notes.txt", "to": "@/files/notes.txt" }))
+    );
+    assert_json_snapshot!(
+        "fs_temp",
+        host.send(json!({ "cmd": "write", "file": "@/tmp/tmp_example.txt", "content": "x" }))
+    );
+    assert_json_snapshot!(
+        "fs_vars",
+        host.send(json!({ "cmd": "read", "file": "${HOME}/./notes.txt" }))
+    );
+    assert_json_snapshot!(
+        "fs_other_user",
+        host.send(json!({ "cmd": "read", "file": "~root/.profile" }))
+    );
+    assert_json_snapshot!(
+        "fs_profiledir_outside",
+        host.send(json!({ "cmd": "setpref", "name": "a", "value": 1, "profiledir": "@/files" }))
+    );
+}
+
+#[test]
+fn audit() {
+    let mut host = Host::start(json!({ "audit": { "redact": ["prefix"] } }));
+
+    host.send(json!({ "cmd": "write", "file": "~/secret.txt", "content": "hunter2" }));
+    host.send(json!({ "cmd": "temp", "prefix": "private", "content": "hunter2" }));
+    host.send(json!({ "cmd": "run", "command": "exit 3" }));
+    host.send(json!({ "cmd": "write_many", "files": { "~/a.txt": "hunter2" } }));
+    host.send(json!({ "cmd": "setpref", "name": "a", "value": "hunter2" }));
+
+    let mut settings = insta::Settings::clone_current();
+    settings.add_redaction(".content[].time", "[time]");
+    settings.add_redaction(".content[].duration", "[time]");
+    settings.bind(|| {
+        assert_json_snapshot!(
+            "audit_tail",
+            host.send(json!({ "cmd": "audit_tail", "lines": 5 }))
+        );
+    });
+}
+
+// every command taking a path expands it the same way
+#[test]
+fn path_expansion() {
+    let mut host = Host::start(json!({}));
+
+    assert_json_snapshot!(
+        "expansion_mkdir",
+        host.send(json!({ "cmd": "mkdir", "dir": "~/$SNAPSHOT_VAR" }))
+    );
+    assert_json_snapshot!(
+        "expansion_write",
+        host.send(json!({ "cmd": "write", "file": "~/${SNAPSHOT_VAR}/a.txt", "content": "a" }))
+    );
+    assert_json_snapshot!(
+        "expansion_copy",
+        host.send(json!({ "cmd": "copy", "from": "$HOME/fixture/a.txt", "to": "~/fixture/b.txt" }))
+    );
+    assert_json_snapshot!(
+        "expansion_move",
+        host.send(
+            json!({ "cmd": "move", "from": "~/fixture/b.txt", "to": "~/$SNAPSHOT_VAR/c.txt" })
+        )
+    );
+    assert_json_snapshot!(
+        "expansion_list_dir",
+        host.send(json!({ "cmd": "list_dir", "path": "$HOME/${SNAPSHOT_VAR}" })),
+        {
+            ".files" => insta::sorted_redaction(),
+            ".entries" => insta::sorted_redaction(),
+            ".entries[].mtime" => "[time]"
+        }
+    );
+    assert_json_snapshot!(
+        "expansion_read",
+        host.send(json!({ "cmd": "read", "file": "~/$SNAPSHOT_VAR/c.txt" }))
+    );
+}
+
+#[test]
+fn readonly() {
+    let mut host = Host::start(json!({ "readonly": true }));
+    host.fixture("home/notes.txt", b"kept\n");
+
+    assert_json_snapshot!(
+        "readonly_read",
+        host.send(json!({ "cmd": "read", "file": "~/notes.txt" }))
+    );
+    assert_json_snapshot!(
+        "readonly_write",
+        host.send(json!({ "cmd": "write", "file": "~/notes.txt", "content": "lost" }))
+    );
+    assert_json_snapshot!(
+        "readonly_run",
+        host.send(json!({ "cmd": "run", "command": "echo hi" }))
+    );
+    assert_json_snapshot!(
+        "readonly_tt_start",
+        host.send(json!({ "cmd": "tt_start", "label": "work" }))
+    );
+    assert_json_snapshot!(
+        "readonly_timer_cancel",
+        host.send(json!({ "cmd": "timer_cancel", "timer": 1 }))
+    );
+    assert_json_snapshot!(
+        "readonly_clipboard_set",
+        host.send(json!({ "cmd": "clipboard", "action": "set", "content": "lost" }))
+    );
+    assert_json_snapshot!(
+        "readonly_sqlite_read",
+        host.send(
+            json!({ "cmd": "sqlite_query", "db": "@/none.db", "query": "select 1", "write": false })
+        )
+    );
+
+    host.fixture(
+        "config/tridactyl/native-plugins/echo.sh",
+        b"#!/bin/sh\ncat\n",
+    );
+    std::fs::set_permissions(
+        host.path("config/tridactyl/native-plugins/echo.sh"),
+        std::os::unix::fs::PermissionsExt::from_mode(0o755),
+    )
+    .unwrap();
+    assert_json_snapshot!(
+        "readonly_plugin",
+        host.send(json!({ "cmd": "plugin:echo" }))
+    );
+}
+
+#[test]
+fn shell_disabled() {
+    let mut host = Host::start(json!({ "security": { "run": { "enabled": false } } }));
+
+    assert_json_snapshot!(
+        "shell_disabled_run",
+        host.send(json!({ "cmd": "run", "command": "echo hi" }))
+    );
+    assert_json_snapshot!(
+        "shell_disabled_run_async",
+        host.send(json!({ "cmd": "run_async", "command": "echo hi" }))
+    );
+    assert_json_snapshot!(
+        "shell_disabled_help_lookup",
+        host.send(json!({ "cmd": "help_lookup", "program": "ls", "source": "help" }))
+    );
+    assert_json_snapshot!(
+        "shell_disabled_capabilities",
+        host.send(json!({ "cmd": "capabilities", "refresh": true }))["content"]["shell"]
+    );
+}
+
+// a log directory that cannot be made leaves the log on stderr
+#[test]
+fn log_dir_unusable() {
+    let mut host = Host::start_with(
+        json!({ "log": { "dir": "@/home/file/log" } }),
+        &[("home/file", b"not a directory\n")],
+    );
+
+    assert_json_snapshot!(
+        "log_dir_unusable",
+        host.send(json!({ "cmd": "set_loglevel", "level": "debug" }))
+    );
+}
+
+// events go through the shims like replies do
+#[test]
+fn shims() {
+    let mut host = Host::start(json!({ "compat": { "shims": ["camel_case"] } }));
+
+    assert_json_snapshot!(
+        "shims_run_stream",
+        host.send(json!({ "cmd": "run_stream", "command": "true", "id": "stream" })),
+        { ".pid" => "[pid]" }
+    );
+    assert_json_snapshot!("shims_run_stream_exit", host.receive());
+}
+
+#[test]
+fn limits() {
+    let mut host = Host::start(json!({ "limits": { "rate": 1, "burst": 2, "max_reply": 100 } }));
+    host.fixture("home/large.txt", &[b'x'; 200]);
+
+    assert_json_snapshot!(
+        "limits_reply_too_large",
+        host.send(json!({ "cmd": "read", "file": "~/large.txt" }))
+    );
+    host.send(json!({ "cmd": "ppid" }));
+
+    let mut settings = insta::Settings::clone_current();
+    settings.add_redaction(".retry_after", "[time]");
+    settings.bind(|| {
+        assert_json_snapshot!("limits_throttled", host.send(json!({ "cmd": "ppid" })));
+    });
+
+    // negative limits are ignored rather than wrapped into huge ones
+    let mut host = Host::start(json!({ "limits": { "rate": -1, "max_reply": -1 } }));
+    host.fixture("home/large.txt", &[b'x'; 200]);
+    for _ in 0..3 {
+        let reply = host.send(json!({ "cmd": "read", "file": "~/large.txt" }));
+        assert_eq!(reply["code"], 0, "{}", reply);
+    }
+}
+
+#[test]
+fn sqlite() {
+    let mut host = Host::start(json!({
+        "sqlite": { "databases": ["@/fixture.db"], "writable": true }
+    }));
+
+    let connection = rusqlite::Connection::open(host.path("fixture.db")).unwrap();
+    connection
+        .execute_batch(
+            "create table bookmarks (id integer primary key, url text, title text);
+             insert into bookmarks (url, title) values ('https://example.com', 'Example');",
+        )
+        .unwrap();
+    drop(connection);
+
+    assert_json_snapshot!(
+        "sqlite_query",
+        host.send(json!({ "cmd": "sqlite_query", "db": "@/fixture.db", "query": "select * from bookmarks" }))
+    );
+    assert_json_snapshot!(
+        "sqlite_query_params",
+        host.send(json!({ "cmd": "sqlite_query", "db": "@/fixture.db", "query": "select title from bookmarks where url = ?", "params": ["https://example.com"] }))
+    );
+    assert_json_snapshot!(
+        "sqlite_query_refused",
+        host.send(json!({ "cmd": "sqlite_query", "db": "@/other.db", "query": "select 1" }))
+    );
+}